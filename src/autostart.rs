@@ -6,24 +6,38 @@ use winreg::{
     RegKey,
 };
 
-use crate::{on_settings_changed, service::BaseService, settings::SpotickAppSettings};
+use crate::{
+    on_settings_changed,
+    service::BaseService,
+    settings::SpotickAppSettings,
+    ui::window::{MsgType, Notifier},
+};
 
 const AUTO_START_KEY: &'static str = "Software\\Microsoft\\Windows\\CurrentVersion\\Run";
 const AUTO_START_VALUE_NAME: &'static str = env!("CARGO_PKG_NAME");
 
-pub fn enable_autostart() -> Result<()> {
+/// Enables autostart. In a [dry_run], the registry change is only described
+/// by the returned message instead of being executed.
+pub fn enable_autostart(dry_run: bool) -> Result<Option<String>> {
     if is_autostart_enabled()? {
-        return Ok(());
+        return Ok(None);
     }
 
     let app_path = std::env::current_exe()?; //.canonicalize()?;
+    if dry_run {
+        return Ok(Some(format!(
+            "Would set HKCU\\{}\\{} to {:?}",
+            AUTO_START_KEY, AUTO_START_VALUE_NAME, app_path
+        )));
+    }
+
     let auto_start_key = get_autostart_key()?;
     auto_start_key
         .set_value(AUTO_START_VALUE_NAME, &app_path.as_os_str())
         .context("Could not set autostart key")?;
 
     log::info!("Enabled autostart with path: {:?}", &app_path);
-    Ok(())
+    Ok(None)
 }
 
 fn get_autostart_key() -> Result<RegKey> {
@@ -34,9 +48,16 @@ fn get_autostart_key() -> Result<RegKey> {
     Ok(auto_start_key)
 }
 
-pub fn disable_autostart() -> Result<()> {
+/// Disables autostart. See [enable_autostart] for [dry_run].
+pub fn disable_autostart(dry_run: bool) -> Result<Option<String>> {
     if !is_autostart_enabled()? {
-        return Ok(());
+        return Ok(None);
+    }
+    if dry_run {
+        return Ok(Some(format!(
+            "Would delete HKCU\\{}\\{}",
+            AUTO_START_KEY, AUTO_START_VALUE_NAME
+        )));
     }
 
     let auto_start_key = get_autostart_key()?;
@@ -44,7 +65,7 @@ pub fn disable_autostart() -> Result<()> {
         .delete_value(AUTO_START_VALUE_NAME)
         .context("Could not delete autostart key")?;
     log::info!("Disabled autostart");
-    Ok(())
+    Ok(None)
 }
 
 pub fn is_autostart_enabled() -> Result<bool> {
@@ -59,17 +80,37 @@ pub fn is_autostart_enabled() -> Result<bool> {
     Ok(ok)
 }
 
-pub async fn register_autostart_changed(settings: SpotickAppSettings) {
+/// Applies the autostart setting to the registry whenever it changes.
+/// Registry changes are only logged and posted to the [notifier] if [cli_dry_run]
+/// or [SpotickSettings::dry_run](crate::settings::SpotickSettings::dry_run) is set.
+pub async fn register_autostart_changed(
+    settings: SpotickAppSettings,
+    cli_dry_run: bool,
+    notifier: Notifier,
+) {
+    // Every settings save is broadcasted, so only touch the registry
+    // if the autostart setting (or the dry run mode) actually changed.
+    let mut applied = None;
     on_settings_changed!(settings, |settings| {
         let auto_start_set = settings.auto_start;
-        let res = if auto_start_set {
-            enable_autostart()
-        } else {
-            disable_autostart()
-        };
+        let dry_run = cli_dry_run || settings.dry_run.unwrap_or(false);
+        if applied != Some((auto_start_set, dry_run)) {
+            let res = if auto_start_set {
+                enable_autostart(dry_run)
+            } else {
+                disable_autostart(dry_run)
+            };
 
-        if let Err(e) = res {
-            log::error!("Could not toggle autostart: {}", e);
+            match res {
+                Ok(change) => {
+                    applied = Some((auto_start_set, dry_run));
+                    if let Some(change) = change {
+                        log::info!("Dry run: {}", change);
+                        notifier.notify(format!("Dry run: {}", change), MsgType::Info);
+                    }
+                }
+                Err(e) => log::error!("Could not toggle autostart: {}", e),
+            }
         }
     });
 }
@@ -0,0 +1,162 @@
+use std::{
+    fs::File,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+use chrono::Local;
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+/// Files in a backup larger than this (bytes) aren't restored,
+/// so a broken or crafted archive can't exhaust the memory.
+const MAX_RESTORED_FILE_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Name of a new backup archive (e.g. spotick-backup-2025-01-31.zip).
+pub fn default_backup_name() -> String {
+    format!(
+        "{}-backup-{}.zip",
+        env!("CARGO_PKG_NAME"),
+        Local::now().format("%Y-%m-%d")
+    )
+}
+
+/// Writes every file in [data_dir] (settings, caches,...) into a zip archive at [target].
+/// Returns the number of backed up files.
+pub fn create_backup(data_dir: &Path, target: &Path) -> Result<usize> {
+    let archive = File::create(target).context("Could not create backup file")?;
+    // The archive might be created inside the data directory
+    let target = target.canonicalize()?;
+    let mut files = Vec::new();
+    collect_files(data_dir, &mut files)?;
+    files.retain(|file| file.canonicalize().ok().as_ref() != Some(&target));
+
+    let mut zip = ZipWriter::new(archive);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    for file in &files {
+        let name = file
+            .strip_prefix(data_dir)?
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        zip.start_file(name, options)?;
+        zip.write_all(&std::fs::read(file)?)?;
+    }
+    zip.finish()?;
+
+    log::info!("Backed up {} files to {:?}", files.len(), target);
+    Ok(files.len())
+}
+
+/// Extracts the backup at [source] into [data_dir], overwriting existing files.
+/// Files not contained in the backup are kept.
+/// Returns the number of restored files.
+pub fn restore_backup(data_dir: &Path, source: &Path) -> Result<usize> {
+    let mut zip = ZipArchive::new(File::open(source).context("Could not open backup file")?)
+        .context("Not a valid backup archive")?;
+    let is_spotick_backup = zip
+        .file_names()
+        .any(|name| name.starts_with("settings") && name.ends_with(".json"));
+    if !is_spotick_backup {
+        bail!("Archive doesn't contain any Spotick settings");
+    }
+
+    let mut restored = 0;
+    for idx in 0..zip.len() {
+        let mut entry = zip.by_index(idx)?;
+        // Skip entries escaping the data directory (e.g. "../../evil.exe")
+        let Some(relative_path) = entry.enclosed_name() else {
+            log::warn!("Skipping invalid backup entry: {}", entry.name());
+            continue;
+        };
+        if entry.is_dir() {
+            continue;
+        }
+
+        let path = data_dir.join(relative_path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // The size in the archive might be made up, so only the actual contents count
+        let mut contents = Vec::new();
+        (&mut entry)
+            .take(MAX_RESTORED_FILE_SIZE + 1)
+            .read_to_end(&mut contents)?;
+        if contents.len() as u64 > MAX_RESTORED_FILE_SIZE {
+            bail!("{} is too large to be restored", entry.name());
+        }
+        std::fs::write(&path, contents).with_context(|| format!("Could not restore {:?}", path))?;
+        restored += 1;
+    }
+
+    log::info!("Restored {} files from {:?}", restored, source);
+    Ok(restored)
+}
+
+/// Runs the `backup <file>` and `restore <file>` maintenance commands.
+/// Returns [None] if [args] don't contain any of them, so the app should start normally.
+pub fn run_command(mut args: impl Iterator<Item = String>, data_dir: &Path) -> Option<Result<()>> {
+    let command = args.next()?;
+    let res = match command.as_str() {
+        "backup" => {
+            let target = args
+                .next()
+                .map(PathBuf::from)
+                .unwrap_or_else(|| PathBuf::from(default_backup_name()));
+            create_backup(data_dir, &target).map(|_| ())
+        }
+        "restore" => match args.next() {
+            Some(source) => restore_backup(data_dir, Path::new(&source)).map(|_| ()),
+            None => Err(anyhow::anyhow!("Usage: restore <backup file>")),
+        },
+        _ => return None,
+    };
+    Some(res)
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn backup_roundtrip() -> Result<()> {
+        let root = std::env::temp_dir().join(format!("spotick-test/backup-{}", std::process::id()));
+        let data_dir = root.join("data");
+        std::fs::create_dir_all(data_dir.join("covers"))?;
+        std::fs::write(data_dir.join("settings.json"), "{}")?;
+        std::fs::write(data_dir.join("covers/last.png"), [1, 2, 3])?;
+
+        // Backing up into the data directory must not include the archive itself
+        let archive = data_dir.join("backup.zip");
+        assert_eq!(create_backup(&data_dir, &archive)?, 2);
+
+        let restored_dir = root.join("restored");
+        assert_eq!(restore_backup(&restored_dir, &archive)?, 2);
+        assert_eq!(std::fs::read(restored_dir.join("settings.json"))?, b"{}");
+        assert_eq!(
+            std::fs::read(restored_dir.join("covers/last.png"))?,
+            [1, 2, 3]
+        );
+
+        // Anything else isn't a backup
+        std::fs::remove_file(data_dir.join("settings.json"))?;
+        create_backup(&data_dir, &archive)?;
+        assert!(restore_backup(&restored_dir, &archive).is_err());
+
+        std::fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,199 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    sync::broadcast::error::RecvError,
+};
+
+use crate::service::{PlaybackChangedEvent, SharedMediaService};
+
+/// A line read from stdin (e.g. `{"command": "next"}`).
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(tag = "command", rename_all = "snake_case")]
+enum Request {
+    /// Answered with [Message::Track]
+    Track,
+    /// Answered with [Message::State]
+    State,
+    Play,
+    Pause,
+    TogglePlayback,
+    Next,
+    Previous,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+struct TrackInfo {
+    title: String,
+    artist: String,
+    album: String,
+    /// Seconds
+    length: u64,
+    /// Name of the app playing the track
+    source_app: String,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+struct StateInfo {
+    is_playing: bool,
+    /// %
+    volume: u32,
+    /// Seconds
+    position: Option<u64>,
+    shuffle: bool,
+}
+
+/// A line written to stdout (e.g. `{"type": "ok"}`).
+/// Every [Request] is answered in order, changes of the playback are sent in between.
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Message {
+    Track { track: Option<TrackInfo> },
+    State { state: StateInfo },
+    Ok,
+    Error { message: String },
+    TrackChanged { track: Option<TrackInfo> },
+    PlaybackChanged { state: StateInfo },
+    SourceCrashed { app_id: String, exit_code: u32 },
+}
+
+/// Also fails on [line]s which aren't UTF-8, but these don't end the session.
+fn parse_request(line: &[u8]) -> Result<Request, Message> {
+    serde_json::from_slice(line).map_err(|e| Message::Error {
+        message: format!("Invalid request: {}", e),
+    })
+}
+
+async fn track_info(media_service: &SharedMediaService) -> Option<TrackInfo> {
+    let srv = media_service.read().await;
+    let track = srv.current_track()?;
+    Some(TrackInfo {
+        title: track.title.clone(),
+        artist: track.artist.clone(),
+        album: track.album_title.clone(),
+        length: track.length,
+        source_app: srv.get_source_app_name(srv.get_active_app_id()),
+    })
+}
+
+async fn state_info(media_service: &SharedMediaService) -> StateInfo {
+    let srv = media_service.read().await;
+    let state = srv.current_playback_state();
+    StateInfo {
+        is_playing: state.is_playing,
+        volume: state.volume,
+        position: state.position,
+        shuffle: state.shuffle,
+    }
+}
+
+async fn handle_request(media_service: &SharedMediaService, request: Request) -> Message {
+    let res = match request {
+        Request::Track => {
+            return Message::Track {
+                track: track_info(media_service).await,
+            }
+        }
+        Request::State => {
+            return Message::State {
+                state: state_info(media_service).await,
+            }
+        }
+        Request::Play => media_service.write().await.play().await,
+        Request::Pause => media_service.write().await.pause().await,
+        Request::TogglePlayback => media_service.write().await.toggle_playback().await,
+        Request::Next => media_service.write().await.next_track().await,
+        Request::Previous => media_service.write().await.previous_track().await,
+    };
+    match res {
+        Ok(()) => Message::Ok,
+        Err(e) => Message::Error {
+            message: e.to_string(),
+        },
+    }
+}
+
+/// Lets other tools script the [media_service] without any UI, see [Request] and [Message].
+/// Returns once stdin is closed (or can't be read anymore).
+/// Release builds have no console of their own, so stdio has to be redirected (e.g. piped).
+pub async fn run(media_service: SharedMediaService) -> Result<()> {
+    let mut media_events = media_service.read().await.subscribe();
+    let mut lines = BufReader::new(tokio::io::stdin()).split(b'\n');
+    let mut stdout = tokio::io::stdout();
+    log::info!("Running headless, reading commands from stdin");
+
+    loop {
+        let msg = tokio::select! {
+            line = lines.next_segment() => match line? {
+                Some(line) if line.trim_ascii().is_empty() => continue,
+                Some(line) => match parse_request(&line) {
+                    Ok(request) => handle_request(&media_service, request).await,
+                    Err(msg) => msg,
+                },
+                None => break,
+            },
+            e = media_events.recv() => match e {
+                Ok(PlaybackChangedEvent::TrackChanged) => Message::TrackChanged {
+                    track: track_info(&media_service).await,
+                },
+                Ok(PlaybackChangedEvent::Play | PlaybackChangedEvent::Pause) => {
+                    Message::PlaybackChanged {
+                        state: state_info(&media_service).await,
+                    }
+                }
+                Ok(PlaybackChangedEvent::SourceCrashed(app)) => Message::SourceCrashed {
+                    app_id: app.app_id,
+                    exit_code: app.exit_code,
+                },
+                Ok(_) | Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            },
+        };
+
+        let mut line = serde_json::to_string(&msg)?;
+        line.push('\n');
+        stdout.write_all(line.as_bytes()).await?;
+        stdout.flush().await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn speaks_json_lines() {
+        assert_eq!(
+            parse_request(br#"{"command": "toggle_playback"}"#),
+            Ok(Request::TogglePlayback)
+        );
+        assert!(matches!(
+            parse_request(br#"{"command": "rewind"}"#),
+            Err(Message::Error { .. })
+        ));
+        assert!(parse_request(b"next").is_err());
+        assert!(parse_request(b"{\"command\": \"n\xffxt\"}").is_err());
+
+        let msg = Message::Track {
+            track: Some(TrackInfo {
+                title: "A".into(),
+                artist: "X".into(),
+                album: String::new(),
+                length: 180,
+                source_app: "Spotify".into(),
+            }),
+        };
+        assert_eq!(
+            serde_json::to_string(&msg).unwrap(),
+            concat!(
+                r#"{"type":"track","track":{"title":"A","artist":"X","album":"","#,
+                r#""length":180,"source_app":"Spotify"}}"#
+            )
+        );
+        assert_eq!(
+            serde_json::to_string(&Message::Ok).unwrap(),
+            r#"{"type":"ok"}"#
+        );
+    }
+}
@@ -0,0 +1,337 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::{
+    service::{PlaybackChangedEvent, SharedMediaService, TrackIdentity},
+    storage::SharedStorage,
+};
+
+const HISTORY_FILE: &str = "history.jsonl";
+
+/// A track which has been listened to.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct HistoryEntry {
+    pub title: String,
+    pub artist: String,
+    pub album: String,
+    /// Name of the app the track was played in
+    pub source_app: String,
+    pub started_at: DateTime<Local>,
+    /// Seconds the track was actually playing
+    pub listened: u64,
+    /// Seconds, 0 if unknown (or recorded before it was)
+    #[serde(default)]
+    pub length: u64,
+}
+
+impl HistoryEntry {
+    pub fn identity(&self) -> TrackIdentity {
+        TrackIdentity::new(&self.title, &self.artist, self.length)
+    }
+
+    /// Whether [query] is part of the title, artist or album, ignoring case.
+    pub fn matches(&self, query: &str) -> bool {
+        let query = query.to_lowercase();
+        [&self.title, &self.artist, &self.album]
+            .iter()
+            .any(|field| field.to_lowercase().contains(&query))
+    }
+}
+
+/// How often and how long a track has been listened to, see [most_listened].
+#[derive(Clone, Debug, PartialEq)]
+pub struct ListenStats {
+    pub title: String,
+    pub artist: String,
+    pub plays: u32,
+    /// Seconds
+    pub listened: u64,
+}
+
+/// Sums up the [entries] by track and returns the [count] tracks listened to the longest.
+/// Tracks are told apart by their normalized title and artist, see [TrackIdentity].
+pub fn most_listened<'a>(
+    entries: impl IntoIterator<Item = &'a HistoryEntry>,
+    count: usize,
+) -> Vec<ListenStats> {
+    let mut stats: HashMap<(String, String), ListenStats> = HashMap::new();
+    for entry in entries {
+        let identity = entry.identity();
+        let (title, artist) = identity.key();
+        let key = (title.to_string(), artist.to_string());
+        let track_stats = stats.entry(key).or_insert_with(|| ListenStats {
+            title: entry.title.clone(),
+            artist: entry.artist.clone(),
+            plays: 0,
+            listened: 0,
+        });
+        track_stats.plays += 1;
+        track_stats.listened += entry.listened;
+    }
+
+    let mut stats: Vec<_> = stats.into_values().collect();
+    stats.sort_by(|a, b| {
+        b.listened
+            .cmp(&a.listened)
+            .then(b.plays.cmp(&a.plays))
+            .then_with(|| a.title.cmp(&b.title))
+    });
+    stats.truncate(count);
+    stats
+}
+
+/// Measures how long the current track is actually playing.
+#[derive(Default)]
+pub struct ListenTracker {
+    current: Option<HistoryEntry>,
+    /// Play time of the current track before it was last paused
+    listened: Duration,
+    playing_since: Option<Instant>,
+}
+
+impl ListenTracker {
+    /// Starts tracking [track], if it is another one than the current (see [TrackIdentity]).
+    /// Returns the entry of the previous track if it has been played at all.
+    pub fn track_changed(
+        &mut self,
+        track: Option<HistoryEntry>,
+        is_playing: bool,
+        now: Instant,
+    ) -> Option<HistoryEntry> {
+        let same_track = match (&self.current, &track) {
+            (Some(current), Some(track)) => current.identity().matches(&track.identity()),
+            (None, None) => true,
+            _ => false,
+        };
+        if same_track {
+            // The latest metadata tends to be the most complete (e.g. the length)
+            if let (Some(current), Some(track)) = (&mut self.current, track) {
+                *current = HistoryEntry {
+                    started_at: current.started_at,
+                    ..track
+                };
+            }
+            self.playback_changed(is_playing, now);
+            return None;
+        }
+
+        let finished = self.finish(now);
+        self.current = track;
+        self.playback_changed(is_playing, now);
+        finished
+    }
+
+    pub fn playback_changed(&mut self, is_playing: bool, now: Instant) {
+        match (self.playing_since, is_playing) {
+            (None, true) => self.playing_since = Some(now),
+            (Some(since), false) => {
+                self.listened += now.saturating_duration_since(since);
+                self.playing_since = None;
+            }
+            _ => {}
+        }
+    }
+
+    /// Stops tracking the current track.
+    /// Returns its entry if it has been played at all.
+    pub fn finish(&mut self, now: Instant) -> Option<HistoryEntry> {
+        self.playback_changed(false, now);
+        let listened = std::mem::take(&mut self.listened).as_secs();
+        let mut entry = self.current.take()?;
+        entry.listened = listened;
+        (listened > 0).then_some(entry)
+    }
+}
+
+/// The tracks listened to, stored as JSON lines.
+#[derive(Clone)]
+pub struct TrackHistory {
+    storage: SharedStorage,
+}
+
+impl TrackHistory {
+    pub fn new(storage: SharedStorage) -> Self {
+        TrackHistory { storage }
+    }
+
+    /// Loads all entries, oldest first.
+    /// Entries which can't be read (e.g. cut off by a crash) are skipped.
+    pub async fn load(&self) -> Result<Vec<HistoryEntry>> {
+        let Some(content) = self.storage.read(HISTORY_FILE).await? else {
+            return Ok(Vec::new());
+        };
+
+        let entries = String::from_utf8_lossy(&content)
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .filter_map(|line| match serde_json::from_str(line) {
+                Ok(entry) => Some(entry),
+                Err(e) => {
+                    log::warn!("Skipping unreadable history entry: {}", e);
+                    None
+                }
+            })
+            .collect();
+        Ok(entries)
+    }
+
+    pub async fn append(&self, entry: &HistoryEntry) -> Result<()> {
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+        self.storage.append(HISTORY_FILE, line.as_bytes()).await
+    }
+}
+
+/// Records every track played by a media service into the [TrackHistory].
+pub struct HistoryRecorder {
+    history: TrackHistory,
+    tracker: Arc<Mutex<ListenTracker>>,
+}
+
+impl HistoryRecorder {
+    /// Starts recording the tracks of the [media_service].
+    pub async fn new(history: TrackHistory, media_service: SharedMediaService) -> Self {
+        let tracker = Arc::new(Mutex::new(ListenTracker::default()));
+        let mut media_events = media_service.read().await.subscribe();
+        tokio::spawn({
+            let history = history.clone();
+            let tracker = tracker.clone();
+            async move {
+                loop {
+                    let finished = match media_events.recv().await {
+                        // Missed events may have changed the track, so catch up with it
+                        Ok(PlaybackChangedEvent::TrackChanged) | Err(RecvError::Lagged(_)) => {
+                            let srv = media_service.read().await;
+                            let source_app = srv.get_source_app_name(srv.get_active_app_id());
+                            let track = srv.current_track().map(|track| HistoryEntry {
+                                title: track.title.clone(),
+                                artist: track.artist.clone(),
+                                album: track.album_title.clone(),
+                                source_app,
+                                started_at: Local::now(),
+                                listened: 0,
+                                length: track.length,
+                            });
+                            let is_playing = srv.current_playback_state().is_playing;
+                            let mut tracker = tracker.lock().unwrap();
+                            tracker.track_changed(track, is_playing, Instant::now())
+                        }
+                        Ok(PlaybackChangedEvent::Play | PlaybackChangedEvent::Pause) => {
+                            let is_playing = media_service
+                                .read()
+                                .await
+                                .current_playback_state()
+                                .is_playing;
+                            let mut tracker = tracker.lock().unwrap();
+                            tracker.playback_changed(is_playing, Instant::now());
+                            None
+                        }
+                        Ok(_) => None,
+                        Err(RecvError::Closed) => break,
+                    };
+
+                    if let Some(entry) = finished {
+                        if let Err(e) = history.append(&entry).await {
+                            log::error!("Could not record track in history: {:?}", e);
+                        }
+                    }
+                }
+            }
+        });
+
+        HistoryRecorder { history, tracker }
+    }
+
+    /// Records the current track as it has been listened to until now.
+    pub async fn finish(&self) -> Result<()> {
+        let finished = self.tracker.lock().unwrap().finish(Instant::now());
+        if let Some(entry) = finished {
+            self.history.append(&entry).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn entry(title: &str, artist: &str, listened: u64) -> HistoryEntry {
+        HistoryEntry {
+            title: title.to_string(),
+            artist: artist.to_string(),
+            album: String::new(),
+            source_app: "Spotify".to_string(),
+            started_at: Local::now(),
+            listened,
+            length: 0,
+        }
+    }
+
+    #[test]
+    fn tracks_play_time() {
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let mut tracker = ListenTracker::default();
+
+        assert_eq!(
+            tracker.track_changed(Some(entry("A", "X", 0)), true, at(0)),
+            None
+        );
+        tracker.playback_changed(false, at(10));
+        tracker.playback_changed(true, at(20));
+        // Metadata updates of the same track don't start a new entry
+        assert_eq!(
+            tracker.track_changed(Some(entry("A (Official Video)", "X", 0)), true, at(25)),
+            None
+        );
+
+        let finished = tracker.track_changed(Some(entry("B", "X", 0)), false, at(30));
+        assert_eq!(
+            finished.map(|e| (e.title, e.listened)),
+            Some(("A (Official Video)".into(), 20))
+        );
+
+        // Tracks which never played are left out
+        assert_eq!(tracker.track_changed(None, false, at(40)), None);
+        assert_eq!(tracker.finish(at(50)), None);
+    }
+
+    #[test]
+    fn sums_up_most_listened() {
+        let entries = [
+            entry("A", "X", 100),
+            entry("B", "Y", 150),
+            entry("a", "x", 100),
+            entry("C", "Z", 10),
+        ];
+        let stats = most_listened(&entries, 2);
+        assert_eq!(
+            stats,
+            vec![
+                ListenStats {
+                    title: "A".into(),
+                    artist: "X".into(),
+                    plays: 2,
+                    listened: 200,
+                },
+                ListenStats {
+                    title: "B".into(),
+                    artist: "Y".into(),
+                    plays: 1,
+                    listened: 150,
+                },
+            ]
+        );
+        assert!(entries[1].matches("y"));
+        assert!(!entries[1].matches("x"));
+    }
+}
@@ -1,40 +1,167 @@
 // Prevent console window in addition to Slint window in Windows release builds when, e.g., starting the app via file manager. Ignored on other platforms.
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+use std::{sync::Arc, time::Instant};
+
 use anyhow::Result;
+use tokio::sync::RwLock;
 
+#[cfg(target_os = "linux")]
+use crate::service::MprisMediaService;
+#[cfg(windows)]
 use crate::{
     autostart::register_autostart_changed,
+    history::{HistoryRecorder, TrackHistory},
     service::WindowsMediaService,
-    settings::{AppSettings, SpotickSettings},
+    state_cache::StateCache,
+    storage::FileStorage,
     ui::{
         init_backend,
-        window::{MainWindow, SettingsWindow},
+        window::{HistoryWindow, MainWindow, NotificationsWindow, SettingsWindow},
     },
 };
+use crate::{
+    schedule::register_source_schedule,
+    service::{register_fallback_apps_changed, register_metadata_rules_changed, ThumbnailLimits},
+    settings::{get_data_dir, AppSettings, SpotickAppSettings, SpotickSettings},
+};
 
+#[cfg(windows)]
 mod autostart;
+mod backup;
+mod headless;
+#[cfg(windows)]
+mod history;
+mod schedule;
 mod service;
 mod settings;
+#[cfg(windows)]
+mod state_cache;
+mod storage;
+#[cfg(windows)]
+mod system_events;
 mod ui;
 
+/// The media service of the current platform.
+#[cfg(windows)]
+type PlatformMediaService = WindowsMediaService;
+#[cfg(target_os = "linux")]
+type PlatformMediaService = MprisMediaService;
+
 #[tokio::main(flavor = "multi_thread", worker_threads = 4)]
 async fn main() -> Result<()> {
+    let startup = Instant::now();
     env_logger::init();
-    init_backend()?;
+
+    // Maintenance commands (e.g. `spotick backup <file>`) run without any UI
+    if let Some(res) = backup::run_command(std::env::args().skip(1), &get_data_dir()) {
+        return res;
+    }
 
     let settings = AppSettings::<SpotickSettings>::default()?;
-    settings.write().await.load().await?;
-    register_autostart_changed(settings.clone()).await;
+    let first_run = settings.write().await.load_or_default().await?;
+    log::info!("Startup: Settings loaded after {:?}", startup.elapsed());
+    let media_service = create_media_service(&settings).await;
+
+    // Tools scripting the media service don't need any UI, which only exists on Windows
+    #[cfg(windows)]
+    if !std::env::args().any(|arg| arg == "--headless") {
+        return run_ui(settings, media_service, first_run, startup).await;
+    }
+    #[cfg(not(windows))]
+    let _ = first_run;
 
-    let win_media_service =
-        WindowsMediaService::new(settings.read().await.get_settings().source_app.clone());
-    win_media_service.write().await.begin_monitor_sessions()?;
+    let monitor = media_service.clone();
+    tokio::task::spawn_blocking(move || monitor.blocking_write().begin_monitor_sessions())
+        .await??;
+    headless::run(media_service).await
+}
+
+/// Shows the widget until it is closed.
+#[cfg(windows)]
+async fn run_ui(
+    settings: SpotickAppSettings,
+    win_media_service: Arc<RwLock<WindowsMediaService>>,
+    first_run: bool,
+    startup: Instant,
+) -> Result<()> {
+    let dry_run = std::env::args().any(|arg| arg == "--dry-run");
+    init_backend()?;
+    let notifications_window = NotificationsWindow::new()?;
+    register_autostart_changed(settings.clone(), dry_run, notifications_window.notifier()).await;
+    // Subscribe before the media session attaches to record the very first track
+    let storage = FileStorage::data_dir()?;
+    let history = TrackHistory::new(storage.clone());
+    let history_recorder = HistoryRecorder::new(history.clone(), win_media_service.clone()).await;
+
+    let settings_window = SettingsWindow::new(
+        settings.clone(),
+        win_media_service.clone(),
+        notifications_window.notifier(),
+    )?;
+    let main_window = MainWindow::new(
+        win_media_service.clone(),
+        settings_window,
+        notifications_window,
+        HistoryWindow::new(history)?,
+        dry_run,
+    )
+    .await?;
+    log::info!("Startup: Windows created after {:?}", startup.elapsed());
+    if first_run {
+        main_window.show_welcome().await;
+    }
 
-    let settings_window = SettingsWindow::new(settings.clone(), win_media_service.clone())?;
-    let main_window = MainWindow::new(win_media_service, settings_window).await?;
+    // Show the last known track until the media session is attached
+    let state_cache = StateCache::new(storage);
+    match state_cache.load().await {
+        Ok(Some((track, cover))) => main_window.show_cached_track(track, cover),
+        Ok(None) => {}
+        Err(e) => log::warn!("Could not load state cache: {:?}", e),
+    }
+
+    // Attaching to the media session synchronously reads the current track.
+    // Defer it until the event loop runs so the widget shows up as fast as possible.
+    let media_service = win_media_service.clone();
+    slint::invoke_from_event_loop(move || {
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = media_service.blocking_write().begin_monitor_sessions() {
+                log::error!("Could not monitor media sessions: {:?}", e);
+            }
+            log::info!(
+                "Startup: Media sessions attached after {:?}",
+                startup.elapsed()
+            );
+        });
+    })?;
 
     main_window.run_blocking()?;
     settings.write().await.save().await?;
+    if let Err(e) = history_recorder.finish().await {
+        log::error!("Could not record the last track in history: {:?}", e);
+    }
+    if let Err(e) = state_cache.save(win_media_service).await {
+        log::error!("Could not save state cache: {:?}", e);
+    }
     Ok(())
 }
+
+/// The media service of the [settings], following their changes.
+async fn create_media_service(settings: &SpotickAppSettings) -> Arc<RwLock<PlatformMediaService>> {
+    let media_service = {
+        let sg = settings.read().await;
+        let spotick_settings = sg.get_settings();
+        let srv = PlatformMediaService::new(spotick_settings.source_app.clone());
+        srv.write()
+            .await
+            .set_thumbnail_limits(ThumbnailLimits::from_settings(
+                spotick_settings.thumbnail_max_size,
+                spotick_settings.thumbnail_timeout,
+            ));
+        srv
+    };
+    register_source_schedule(settings.clone(), media_service.clone()).await;
+    register_metadata_rules_changed(settings.clone(), media_service.clone()).await;
+    register_fallback_apps_changed(settings.clone(), media_service.clone()).await;
+    media_service
+}
@@ -0,0 +1,300 @@
+use std::{sync::Arc, time::Duration};
+
+#[cfg(windows)]
+use anyhow::{anyhow, bail, Result};
+use chrono::{Datelike, Local, Timelike, Weekday};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::{
+    service::{BaseService, SharedMediaService},
+    settings::SpotickAppSettings,
+};
+
+/// Monitors [SourceScheduleRule::source_app] on the given [SourceScheduleRule::days]
+/// from [SourceScheduleRule::start_hour] (inclusive) to [SourceScheduleRule::end_hour] (exclusive).
+/// A start hour greater than the end hour spans midnight (e.g. 22 - 2),
+/// the hours after midnight then belong to the day before.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SourceScheduleRule {
+    pub days: Vec<Weekday>,
+    pub start_hour: u32,
+    pub end_hour: u32,
+    pub source_app: String,
+}
+
+impl SourceScheduleRule {
+    pub fn is_active(&self, day: Weekday, hour: u32) -> bool {
+        if self.start_hour <= self.end_hour {
+            self.days.contains(&day) && hour >= self.start_hour && hour < self.end_hour
+        } else if hour >= self.start_hour {
+            self.days.contains(&day)
+        } else {
+            hour < self.end_hour && self.days.contains(&day.pred())
+        }
+    }
+}
+
+/// Parses [days] like "Mon-Fri" or "Sat, Sun" (ranges may wrap around, e.g. "Fri-Mon").
+/// Returns the days ordered from Monday.
+#[cfg(windows)]
+pub fn parse_days(days: &str) -> Result<Vec<Weekday>> {
+    let parse = |day: &str| {
+        let day = day.trim();
+        day.parse::<Weekday>()
+            .map_err(|_| anyhow!("Unknown day: {}", day))
+    };
+    let mut parsed = Vec::new();
+    for part in days.split(',').filter(|part| !part.trim().is_empty()) {
+        match part.split_once('-') {
+            Some((first, last)) => {
+                let (mut day, last) = (parse(first)?, parse(last)?);
+                parsed.push(day);
+                while day != last {
+                    day = day.succ();
+                    parsed.push(day);
+                }
+            }
+            None => parsed.push(parse(part)?),
+        }
+    }
+    if parsed.is_empty() {
+        bail!("No days given");
+    }
+    parsed.sort_by_key(Weekday::num_days_from_monday);
+    parsed.dedup();
+    Ok(parsed)
+}
+
+/// Formats [days] the way [parse_days] reads them, joining three or more
+/// consecutive days into a range (e.g. "Mon-Fri, Sun").
+#[cfg(windows)]
+pub fn format_days(days: &[Weekday]) -> String {
+    let mut days = days.to_vec();
+    days.sort_by_key(Weekday::num_days_from_monday);
+    days.dedup();
+
+    let mut runs: Vec<Vec<Weekday>> = Vec::new();
+    for day in days {
+        match runs.last_mut() {
+            Some(run) if run.last().is_some_and(|last| last.succ() == day) => run.push(day),
+            _ => runs.push(vec![day]),
+        }
+    }
+    runs.iter()
+        .map(|run| match run.as_slice() {
+            [first, .., last] if run.len() > 2 => format!("{}-{}", first, last),
+            run => run
+                .iter()
+                .map(Weekday::to_string)
+                .collect::<Vec<_>>()
+                .join(", "),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Gets the source app of the first rule active at the given time.
+pub fn scheduled_source_app(rules: &[SourceScheduleRule], day: Weekday, hour: u32) -> Option<&str> {
+    rules
+        .iter()
+        .find(|rule| rule.is_active(day, hour))
+        .map(|rule| rule.source_app.as_str())
+}
+
+/// Gets in how many hours from the given time the scheduled source app changes next,
+/// [None] if it never does (e.g. without any rules).
+pub fn hours_until_change(rules: &[SourceScheduleRule], day: Weekday, hour: u32) -> Option<u32> {
+    let current = scheduled_source_app(rules, day, hour);
+    // The schedule repeats every week
+    (1..=7 * 24).find(|hours| {
+        let later = hour + hours;
+        let mut later_day = day;
+        for _ in 0..later / 24 {
+            later_day = later_day.succ();
+        }
+        scheduled_source_app(rules, later_day, later % 24) != current
+    })
+}
+
+/// Switches the source app of the [media_service] according to the
+/// source schedule in the [settings]. Outside of any scheduled time
+/// the source app from the settings is used.
+pub async fn register_source_schedule(
+    settings: SpotickAppSettings,
+    media_service: SharedMediaService,
+) {
+    let mut settings_rv = settings.read().await.subscribe();
+    let settings = Arc::downgrade(&settings);
+    let media_service = Arc::downgrade(&media_service);
+
+    tokio::spawn(async move {
+        loop {
+            let (Some(settings), Some(media_service)) =
+                (settings.upgrade(), media_service.upgrade())
+            else {
+                break;
+            };
+
+            let now = Local::now();
+            let (source_app, hours_until_change) = {
+                let sg = settings.read().await;
+                let spotick_settings = sg.get_settings();
+                let rules = spotick_settings
+                    .source_schedule
+                    .as_deref()
+                    .unwrap_or_default();
+                let source_app = scheduled_source_app(rules, now.weekday(), now.hour())
+                    .unwrap_or(spotick_settings.source_app.as_str())
+                    .to_string();
+                (
+                    source_app,
+                    hours_until_change(rules, now.weekday(), now.hour()),
+                )
+            };
+
+            // Most settings changes are unrelated, which only need a look at the source app
+            let switch = !media_service
+                .read()
+                .await
+                .get_source_app_id()
+                .eq_ignore_ascii_case(&source_app);
+            if switch {
+                log::info!("Switching to scheduled source app: {}", &source_app);
+                if let Err(e) = media_service.write().await.set_source_app_id(source_app) {
+                    log::error!("Could not set scheduled source app: {}", e);
+                }
+            }
+            drop(media_service);
+            drop(settings);
+
+            // Rules are hour based, so we only need to wake up once another rule applies
+            // or if the schedule changes. Don't wake up at all without any rule to apply.
+            let until_change = hours_until_change.map(|hours| {
+                let secs_into_hour = (now.minute() * 60 + now.second()) as u64;
+                Duration::from_secs(hours as u64 * 3600 - secs_into_hour)
+            });
+            let settings_changed = tokio::select! {
+                _ = tokio::time::sleep(until_change.unwrap_or_default()),
+                    if until_change.is_some() => Ok(()),
+                res = settings_rv.recv() => res,
+            };
+            if let Err(RecvError::Closed) = settings_changed {
+                break;
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rule(
+        days: &[Weekday],
+        start_hour: u32,
+        end_hour: u32,
+        source_app: &str,
+    ) -> SourceScheduleRule {
+        SourceScheduleRule {
+            days: days.to_vec(),
+            start_hour,
+            end_hour,
+            source_app: source_app.into(),
+        }
+    }
+
+    #[test]
+    fn rule_within_day() {
+        let work = rule(&[Weekday::Mon, Weekday::Fri], 9, 17, "teams");
+        assert!(work.is_active(Weekday::Mon, 9));
+        assert!(work.is_active(Weekday::Fri, 16));
+        assert!(!work.is_active(Weekday::Mon, 17));
+        assert!(!work.is_active(Weekday::Mon, 8));
+        assert!(!work.is_active(Weekday::Tue, 12));
+    }
+
+    #[test]
+    fn rule_spanning_midnight() {
+        let night = rule(&[Weekday::Sat], 22, 2, "spotify.exe");
+        assert!(night.is_active(Weekday::Sat, 23));
+        // Saturday night continues into Sunday
+        assert!(night.is_active(Weekday::Sun, 1));
+        assert!(!night.is_active(Weekday::Sat, 1));
+        assert!(!night.is_active(Weekday::Sun, 2));
+        assert!(!night.is_active(Weekday::Sun, 23));
+        assert!(!night.is_active(Weekday::Sat, 12));
+    }
+
+    #[test]
+    fn wakes_up_when_the_source_changes() {
+        let rules = [
+            rule(&[Weekday::Mon, Weekday::Tue], 9, 17, "teams"),
+            rule(&[Weekday::Fri], 22, 2, "spotify.exe"),
+        ];
+        assert_eq!(hours_until_change(&rules, Weekday::Mon, 8), Some(1));
+        assert_eq!(hours_until_change(&rules, Weekday::Mon, 9), Some(8));
+        // Both days are covered by the same rule, but not the night in between
+        assert_eq!(hours_until_change(&rules, Weekday::Mon, 17), Some(16));
+        assert_eq!(
+            hours_until_change(&rules, Weekday::Tue, 17),
+            Some(3 * 24 + 5)
+        );
+        assert_eq!(hours_until_change(&rules, Weekday::Sat, 0), Some(2));
+        assert_eq!(
+            hours_until_change(&rules, Weekday::Sat, 2),
+            Some(2 * 24 + 7)
+        );
+
+        let always = [rule(&[Weekday::Sun], 0, 24, "spotify.exe")];
+        assert_eq!(hours_until_change(&always, Weekday::Sun, 12), Some(12));
+        assert_eq!(hours_until_change(&[], Weekday::Sun, 12), None);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn days_as_text() {
+        let weekdays = [
+            Weekday::Mon,
+            Weekday::Tue,
+            Weekday::Wed,
+            Weekday::Thu,
+            Weekday::Fri,
+        ];
+        assert_eq!(parse_days("Mon-Fri").unwrap(), weekdays);
+        assert_eq!(
+            parse_days("sun, Saturday").unwrap(),
+            [Weekday::Sat, Weekday::Sun]
+        );
+        assert_eq!(
+            parse_days("Sat-Mon").unwrap(),
+            [Weekday::Mon, Weekday::Sat, Weekday::Sun]
+        );
+        assert!(parse_days("").is_err());
+        assert!(parse_days("Mon-Someday").is_err());
+
+        assert_eq!(format_days(&weekdays), "Mon-Fri");
+        assert_eq!(format_days(&[Weekday::Sun, Weekday::Sat]), "Sat, Sun");
+        assert_eq!(
+            format_days(&[Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Sun]),
+            "Mon-Wed, Sun"
+        );
+    }
+
+    #[test]
+    fn first_active_rule_wins() {
+        let rules = [
+            rule(&[Weekday::Mon], 9, 17, "teams"),
+            rule(&[Weekday::Mon], 0, 24, "spotify.exe"),
+        ];
+        assert_eq!(
+            scheduled_source_app(&rules, Weekday::Mon, 10),
+            Some("teams")
+        );
+        assert_eq!(
+            scheduled_source_app(&rules, Weekday::Mon, 20),
+            Some("spotify.exe")
+        );
+        assert_eq!(scheduled_source_app(&rules, Weekday::Tue, 10), None);
+    }
+}
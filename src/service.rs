@@ -1,9 +1,51 @@
 use tokio::sync::broadcast::Receiver;
 
+pub use crate::service::activity_log::{Activity, ActivityLog};
+#[cfg(windows)]
+pub use crate::service::audio_session::watch_app_level;
+#[cfg(windows)]
+pub use crate::service::lyrics::{Lyrics, LyricsProvider, LyricsQuery};
 pub use crate::service::media_service::{AlbumCover, PlaybackChangedEvent, SharedMediaService};
+#[cfg(windows)]
+pub use crate::service::media_service::{CrashedApp, MediaSessionInfo, RepeatMode};
+#[cfg(windows)]
+pub use crate::service::metadata_rules::MetadataField;
+pub use crate::service::metadata_rules::{
+    register_metadata_rules_changed, MetadataRule, MetadataRules,
+};
+#[cfg(target_os = "linux")]
+pub use crate::service::mpris_media_service::MprisMediaService;
+#[cfg(windows)]
+pub use crate::service::normalization::Normalization;
+pub use crate::service::normalization::NormalizationRule;
+pub use crate::service::source_fallback::register_fallback_apps_changed;
+#[cfg(windows)]
+pub use crate::service::source_process::restart_app;
+pub use crate::service::thumbnail::{decode_thumbnail, ThumbnailError, ThumbnailLimits};
+#[cfg(windows)]
+pub use crate::service::track_identity::TrackIdentity;
+#[cfg(windows)]
 pub use crate::service::windows_media_service::WindowsMediaService;
 
+mod activity_log;
+#[cfg(windows)]
+mod audio_session;
+#[cfg(windows)]
+mod lyrics;
 mod media_service;
+mod metadata_rules;
+#[cfg(target_os = "linux")]
+mod mpris_media_service;
+mod normalization;
+#[cfg(windows)]
+mod smtc;
+mod source_fallback;
+#[cfg(windows)]
+mod source_process;
+mod thumbnail;
+#[cfg(windows)]
+mod track_identity;
+#[cfg(windows)]
 mod windows_media_service;
 
 pub trait BaseService<E: Clone>: Send + Sync {
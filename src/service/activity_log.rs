@@ -0,0 +1,72 @@
+use std::{collections::VecDeque, fmt::Display, time::Instant};
+
+/// Something noteworthy which happened inside a media service.
+#[derive(Clone, Debug)]
+pub enum Activity {
+    SessionFound(String),
+    SessionLost(String),
+    TrackChanged(Option<String>),
+    Playing,
+    Paused,
+    Error(String),
+    /// App id and exit code of the source app
+    #[cfg(windows)]
+    SourceCrashed(String, u32),
+}
+
+impl Display for Activity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Activity::SessionFound(app_id) => write!(f, "Session found: {}", app_id),
+            Activity::SessionLost(app_id) => write!(f, "Session lost: {}", app_id),
+            Activity::TrackChanged(Some(title)) => write!(f, "Track changed: {}", title),
+            Activity::TrackChanged(None) => write!(f, "Track changed: No track"),
+            Activity::Playing => write!(f, "Playing"),
+            Activity::Paused => write!(f, "Paused"),
+            Activity::Error(e) => write!(f, "Error: {}", e),
+            #[cfg(windows)]
+            Activity::SourceCrashed(app_id, exit_code) => {
+                write!(f, "Source crashed: {} (exit code {:#x})", app_id, exit_code)
+            }
+        }
+    }
+}
+
+/// A timeline of the most recent [Activity]s of a media service.
+/// Only the last [ActivityLog::CAPACITY] activities are kept.
+#[derive(Default, Debug)]
+pub struct ActivityLog {
+    entries: VecDeque<(Instant, Activity)>,
+}
+
+impl ActivityLog {
+    pub const CAPACITY: usize = 256;
+
+    pub fn record(&mut self, activity: Activity) {
+        if self.entries.len() == Self::CAPACITY {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((Instant::now(), activity));
+    }
+
+    /// All recorded activities, oldest first.
+    pub fn entries(&self) -> impl Iterator<Item = &(Instant, Activity)> {
+        self.entries.iter()
+    }
+
+    /// Writes the whole timeline to the log.
+    /// Timestamps are relative to now (hh:mm:ss ago).
+    pub fn dump(&self) {
+        log::info!("Activity timeline ({} entries):", self.entries.len());
+        for (time, activity) in self.entries() {
+            let secs = time.elapsed().as_secs();
+            log::info!(
+                "  -{:02}:{:02}:{:02} {}",
+                secs / 3600,
+                (secs / 60) % 60,
+                secs % 60,
+                activity
+            );
+        }
+    }
+}
@@ -0,0 +1,344 @@
+use std::time::{Duration, Instant};
+
+use tokio::sync::watch;
+use windows::{
+    core::{implement, Interface, Result as WinResult, BOOL, GUID, PCWSTR, PWSTR},
+    Win32::{
+        Foundation::CloseHandle,
+        Media::Audio::{
+            eRender, AudioSessionDisconnectReason, AudioSessionState, AudioSessionStateExpired,
+            Endpoints::IAudioMeterInformation, IAudioSessionControl2, IAudioSessionEvents,
+            IAudioSessionEvents_Impl, IAudioSessionManager2, IMMDeviceEnumerator,
+            ISimpleAudioVolume, MMDeviceEnumerator, DEVICE_STATE_ACTIVE,
+        },
+        System::{
+            Com::{CoCreateInstance, CoInitializeEx, CLSCTX_ALL, COINIT_MULTITHREADED},
+            Threading::{
+                OpenProcess, QueryFullProcessImageNameW, PROCESS_NAME_WIN32,
+                PROCESS_QUERY_LIMITED_INFORMATION,
+            },
+        },
+    },
+};
+
+/// The output level is read this often while metering.
+const METER_INTERVAL: Duration = Duration::from_millis(50);
+/// Apps create and drop audio sessions at will (e.g. per track),
+/// so they are looked up again this often while metering.
+const METER_REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+/// Range of the level meter in dB, anything quieter shows as silence.
+const METER_RANGE_DB: f32 = 60.0;
+/// Share of the level kept per [METER_INTERVAL] while the output gets quieter.
+const METER_DECAY: f32 = 0.85;
+
+// The media service is used from arbitrary (tokio) threads
+thread_local! {
+    static COM_INITIALIZED: () = unsafe {
+        // Fails on threads already in a single threaded apartment, which can use COM anyway
+        let _ = CoInitializeEx(None, COINIT_MULTITHREADED);
+    };
+}
+
+/// Gets the volume (0 - 100) of the audio sessions of [app_id] in the volume mixer.
+/// Returns [None] if the app currently has no audio session
+/// (e.g. some players only create one when starting playback).
+pub fn get_app_volume(app_id: &str) -> WinResult<Option<u32>> {
+    let Some(volume) = find_app_sessions::<ISimpleAudioVolume>(app_id)?
+        .into_iter()
+        .next()
+    else {
+        return Ok(None);
+    };
+    let level = unsafe { volume.GetMasterVolume()? };
+    Ok(Some((level * 100.0).round() as u32))
+}
+
+/// Sets the [volume] (0 - 100) of all audio sessions of [app_id].
+/// Returns false if the app currently has no audio session.
+pub fn set_app_volume(app_id: &str, volume: u32) -> WinResult<bool> {
+    let volumes = find_app_sessions::<ISimpleAudioVolume>(app_id)?;
+    let level = volume.min(100) as f32 / 100.0;
+    for volume in &volumes {
+        unsafe { volume.SetMasterVolume(level, std::ptr::null())? };
+    }
+    Ok(!volumes.is_empty())
+}
+
+/// Forwards volume changes of audio sessions, see [watch_app_volume].
+#[implement(IAudioSessionEvents)]
+struct VolumeEvents {
+    on_changed: Box<dyn Fn(u32) + Send + Sync>,
+}
+
+impl IAudioSessionEvents_Impl for VolumeEvents_Impl {
+    fn OnDisplayNameChanged(&self, _: &PCWSTR, _: *const GUID) -> WinResult<()> {
+        Ok(())
+    }
+
+    fn OnIconPathChanged(&self, _: &PCWSTR, _: *const GUID) -> WinResult<()> {
+        Ok(())
+    }
+
+    fn OnSimpleVolumeChanged(&self, volume: f32, _: BOOL, _: *const GUID) -> WinResult<()> {
+        (self.on_changed)((volume * 100.0).round() as u32);
+        Ok(())
+    }
+
+    fn OnChannelVolumeChanged(
+        &self,
+        _: u32,
+        _: *const f32,
+        _: u32,
+        _: *const GUID,
+    ) -> WinResult<()> {
+        Ok(())
+    }
+
+    fn OnGroupingParamChanged(&self, _: *const GUID, _: *const GUID) -> WinResult<()> {
+        Ok(())
+    }
+
+    fn OnStateChanged(&self, _: AudioSessionState) -> WinResult<()> {
+        Ok(())
+    }
+
+    fn OnSessionDisconnected(&self, _: AudioSessionDisconnectReason) -> WinResult<()> {
+        Ok(())
+    }
+}
+
+/// The audio sessions of an app watched for volume changes, see [watch_app_volume].
+/// Stops watching once dropped.
+pub struct AppVolumeWatcher {
+    sessions: Vec<IAudioSessionControl2>,
+    events: IAudioSessionEvents,
+}
+
+// The audio sessions are only used in the multithreaded apartment (see [COM_INITIALIZED])
+unsafe impl Send for AppVolumeWatcher {}
+unsafe impl Sync for AppVolumeWatcher {}
+
+impl Drop for AppVolumeWatcher {
+    fn drop(&mut self) {
+        for session in &self.sessions {
+            let _ = unsafe { session.UnregisterAudioSessionNotification(&self.events) };
+        }
+    }
+}
+
+/// Calls [on_changed] with the new volume (0 - 100) whenever one of the audio sessions
+/// of [app_id] changes its volume, also outside of Spotick (e.g. in the volume mixer).
+/// It is called from a thread of Windows. Returns [None] if the app currently has no
+/// audio session, and sessions created later on aren't watched.
+pub fn watch_app_volume(
+    app_id: &str,
+    on_changed: impl Fn(u32) + Send + Sync + 'static,
+) -> WinResult<Option<AppVolumeWatcher>> {
+    let sessions = find_app_session_controls(app_id)?;
+    if sessions.is_empty() {
+        return Ok(None);
+    }
+
+    let mut watcher = AppVolumeWatcher {
+        sessions: Vec::with_capacity(sessions.len()),
+        events: VolumeEvents {
+            on_changed: Box::new(on_changed),
+        }
+        .into(),
+    };
+    for (session, _, _) in sessions {
+        unsafe { session.RegisterAudioSessionNotification(&watcher.events)? };
+        watcher.sessions.push(session);
+    }
+    Ok(Some(watcher))
+}
+
+/// Meters the output of the app the [app_id] receiver holds (none to pause),
+/// see [perceived_level]. The meters can't leave the thread they were created on,
+/// so they are read on a thread of their own, which sleeps while paused.
+/// It ends once the returned receiver (or the sender of [app_id]) is dropped.
+pub fn watch_app_level(mut app_id: watch::Receiver<Option<String>>) -> watch::Receiver<f32> {
+    let (level_tx, level_rv) = watch::channel(0.0);
+    let runtime = tokio::runtime::Handle::current();
+    std::thread::spawn(move || {
+        COM_INITIALIZED.with(|_| {});
+        let mut meters: Vec<IAudioMeterInformation> = Vec::new();
+        let mut metered_app = None;
+        let mut refreshed = Instant::now();
+        let mut level = 0.0;
+        while !level_tx.is_closed() {
+            let current_app = app_id.borrow_and_update().clone();
+            if current_app != metered_app || refreshed.elapsed() >= METER_REFRESH_INTERVAL {
+                meters = match &current_app {
+                    Some(app_id) => find_app_sessions(app_id).unwrap_or_else(|e| {
+                        log::debug!("Could not find audio sessions to meter: {}", e);
+                        Vec::new()
+                    }),
+                    None => Vec::new(),
+                };
+                metered_app = current_app;
+                refreshed = Instant::now();
+            }
+
+            let peak = meters
+                .iter()
+                .filter_map(|meter| unsafe { meter.GetPeakValue() }.ok())
+                .fold(0.0, f32::max);
+            level = match metered_app {
+                Some(_) => perceived_level(level, peak),
+                None => 0.0,
+            };
+            level_tx.send_if_modified(|sent| {
+                let changed = *sent != level;
+                *sent = level;
+                changed
+            });
+            if metered_app.is_none() {
+                if runtime.block_on(app_id.changed()).is_err() {
+                    break;
+                }
+                continue;
+            }
+            std::thread::sleep(METER_INTERVAL);
+        }
+    });
+    level_rv
+}
+
+/// Maps the [peak] amplitude (0 - 1) onto an even scale of loudness (0 - 1).
+/// Rises right away, but falls slowly from the [previous] level, so short gaps don't flicker.
+fn perceived_level(previous: f32, peak: f32) -> f32 {
+    let db = 20.0 * peak.max(f32::MIN_POSITIVE).log10();
+    let level = (1.0 + db / METER_RANGE_DB).clamp(0.0, 1.0);
+    level.max(previous * METER_DECAY)
+}
+
+/// Gets the id and image path of the process playing the audio of [app_id].
+/// Returns [None] if the app currently has no audio session.
+pub fn find_app_process(app_id: &str) -> WinResult<Option<(u32, String)>> {
+    let process = find_app_session_controls(app_id)?
+        .into_iter()
+        .next()
+        .map(|(_, pid, image_path)| (pid, image_path));
+    Ok(process)
+}
+
+/// Finds the audio sessions of [app_id] on all active output devices,
+/// as [T] (e.g. [ISimpleAudioVolume]).
+fn find_app_sessions<T: Interface>(app_id: &str) -> WinResult<Vec<T>> {
+    find_app_session_controls(app_id)?
+        .into_iter()
+        .map(|(session, _, _)| session.cast())
+        .collect()
+}
+
+/// Finds the audio sessions of [app_id] on all active output devices,
+/// together with the id and image path of their process.
+fn find_app_session_controls(app_id: &str) -> WinResult<Vec<(IAudioSessionControl2, u32, String)>> {
+    COM_INITIALIZED.with(|_| {});
+
+    let mut found = Vec::new();
+    unsafe {
+        let enumerator: IMMDeviceEnumerator =
+            CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL)?;
+        let devices = enumerator.EnumAudioEndpoints(eRender, DEVICE_STATE_ACTIVE)?;
+        for device_idx in 0..devices.GetCount()? {
+            let manager: IAudioSessionManager2 =
+                devices.Item(device_idx)?.Activate(CLSCTX_ALL, None)?;
+            let sessions = manager.GetSessionEnumerator()?;
+            for session_idx in 0..sessions.GetCount()? {
+                let session: IAudioSessionControl2 = sessions.GetSession(session_idx)?.cast()?;
+                if session.GetState()? == AudioSessionStateExpired {
+                    continue;
+                }
+                let pid = session.GetProcessId()?;
+                let Some(image_path) = get_process_image_path(pid) else {
+                    continue;
+                };
+                if matches_app_id(&image_path, app_id) {
+                    found.push((session, pid, image_path));
+                }
+            }
+        }
+    }
+    Ok(found)
+}
+
+fn get_process_image_path(pid: u32) -> Option<String> {
+    // System sounds
+    if pid == 0 {
+        return None;
+    }
+
+    unsafe {
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid).ok()?;
+        let mut buffer = [0u16; 1024];
+        let mut size = buffer.len() as u32;
+        let res = QueryFullProcessImageNameW(
+            process,
+            PROCESS_NAME_WIN32,
+            PWSTR(buffer.as_mut_ptr()),
+            &mut size,
+        );
+        let _ = CloseHandle(process);
+        res.ok()?;
+        Some(String::from_utf16_lossy(&buffer[..size as usize]))
+    }
+}
+
+/// Whether the process at [image_path] belongs to the media session [app_id].
+/// Packaged apps (e.g. "SpotifyAB.SpotifyMusic_zpdnekdrzrea0!Spotify") are installed into
+/// "WindowsApps\<Name>_<Version>_<Arch>__<PublisherId>\". Any other app id is an executable name.
+fn matches_app_id(image_path: &str, app_id: &str) -> bool {
+    let image_path = image_path.to_lowercase();
+    let app_id = app_id.to_lowercase();
+
+    if let Some((family_name, _)) = app_id.split_once('!') {
+        let Some((name, publisher_id)) = family_name.rsplit_once('_') else {
+            return false;
+        };
+        return image_path.contains(&format!("\\{}_", name))
+            && image_path.contains(&format!("__{}\\", publisher_id));
+    }
+
+    let exe_name = image_path.rsplit(['\\', '/']).next().unwrap_or_default();
+    exe_name == app_id
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn matches_executables() {
+        let path = "C:\\Users\\test\\AppData\\Roaming\\Spotify\\Spotify.exe";
+        assert!(matches_app_id(path, "spotify.exe"));
+        assert!(!matches_app_id(path, "chrome.exe"));
+        assert!(!matches_app_id(path, "spotify"));
+    }
+
+    #[test]
+    fn matches_packaged_apps() {
+        let path = "C:\\Program Files\\WindowsApps\\SpotifyAB.SpotifyMusic_1.250.0.0_x64__zpdnekdrzrea0\\Spotify.exe";
+        assert!(matches_app_id(
+            path,
+            "SpotifyAB.SpotifyMusic_zpdnekdrzrea0!Spotify"
+        ));
+        assert!(!matches_app_id(
+            path,
+            "Microsoft.ZuneMusic_8wekyb3d8bbwe!Microsoft.ZuneMusic"
+        ));
+        assert!(!matches_app_id(path, "invalid!app"));
+    }
+
+    #[test]
+    fn perceives_levels_in_decibels() {
+        assert_eq!(perceived_level(0.0, 1.0), 1.0);
+        assert_eq!(perceived_level(0.0, 0.0), 0.0);
+        // -20 dB
+        assert!((perceived_level(0.0, 0.1) - 2.0 / 3.0).abs() < 1e-4);
+        // Falls slowly
+        assert_eq!(perceived_level(1.0, 0.0), METER_DECAY);
+        assert_eq!(perceived_level(0.5, 2.0), 1.0);
+    }
+}
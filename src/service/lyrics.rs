@@ -0,0 +1,306 @@
+use std::{io::ErrorKind, path::PathBuf, time::Duration};
+
+use serde::Deserialize;
+use windows::{
+    core::HSTRING,
+    Foundation::Uri,
+    Web::Http::{HttpClient, HttpStatusCode},
+};
+
+use crate::{service::media_service::MediaTrack, settings::get_data_dir};
+
+const LRCLIB_URL: &str = "https://lrclib.net/api/get";
+/// LRCLIB asks clients to identify themselves
+const USER_AGENT: &str = concat!(
+    "Spotick/",
+    env!("CARGO_PKG_VERSION"),
+    " (https://github.com/Kaaeveth/spotick)"
+);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const CACHE_DIR: &str = "lyrics";
+/// Keeps cache file names within the path limits of Windows
+const MAX_CACHE_NAME_LEN: usize = 120;
+
+#[derive(thiserror::Error, Debug)]
+pub enum LyricsError {
+    #[error("Requesting lyrics timed out")]
+    Timeout,
+    #[error("Invalid lyrics response: {0}")]
+    Response(#[from] serde_json::Error),
+    #[error("Lyrics request failed: {0}")]
+    Task(#[from] tokio::task::JoinError),
+    #[error("WinRT error: {0}")]
+    WinRt(#[from] windows::core::Error),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LyricLine {
+    /// Position in the track at which the line starts
+    pub time: Duration,
+    /// Empty for instrumental parts
+    pub text: String,
+}
+
+/// Lyrics of a track, timed line by line.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Lyrics {
+    /// Sorted by time
+    pub lines: Vec<LyricLine>,
+}
+
+impl Lyrics {
+    /// Parses lyrics in the LRC format (e.g. `[01:23.45] Some line`).
+    /// Lines with several timestamps are repeated.
+    /// Untimed lines and tags (e.g. `[ar:Artist]`) are skipped.
+    pub fn parse_lrc(lrc: &str) -> Self {
+        let mut lines = Vec::new();
+        for line in lrc.lines() {
+            let mut rest = line.trim();
+            let mut times = Vec::new();
+            while let Some((tag, after)) =
+                rest.strip_prefix('[').and_then(|tag| tag.split_once(']'))
+            {
+                match parse_lrc_time(tag) {
+                    Some(time) => times.push(time),
+                    None => break,
+                }
+                rest = after;
+            }
+
+            let text = rest.trim();
+            lines.extend(times.into_iter().map(|time| LyricLine {
+                time,
+                text: text.to_string(),
+            }));
+        }
+        // Stable, so lines at the same time keep their order
+        lines.sort_by_key(|line| line.time);
+        Lyrics { lines }
+    }
+
+    /// Gets the index of the line sung at [position], if any has started yet.
+    pub fn line_at(&self, position: Duration) -> Option<usize> {
+        self.lines
+            .partition_point(|line| line.time <= position)
+            .checked_sub(1)
+    }
+}
+
+/// Parses a LRC timestamp (`mm:ss`, `mm:ss.xx` or `mm:ss.xxx`).
+fn parse_lrc_time(tag: &str) -> Option<Duration> {
+    let (minutes, seconds) = tag.split_once(':')?;
+    let minutes: u64 = minutes.parse().ok()?;
+    let (seconds, fraction) = seconds.split_once('.').unwrap_or((seconds, ""));
+    let seconds: u64 = seconds.parse().ok()?;
+    let millis = if fraction.is_empty() {
+        0
+    } else if fraction.len() <= 3 && fraction.chars().all(|c| c.is_ascii_digit()) {
+        // "5" means 500ms and "05" 50ms
+        format!("{:0<3}", fraction).parse().ok()?
+    } else {
+        return None;
+    };
+    Some(Duration::from_millis(
+        (minutes * 60 + seconds) * 1000 + millis,
+    ))
+}
+
+/// Identifies the track to get the lyrics of.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LyricsQuery {
+    pub artist: String,
+    pub title: String,
+    pub album: String,
+    /// Seconds, 0 if unknown
+    pub length: u64,
+}
+
+impl From<&MediaTrack> for LyricsQuery {
+    fn from(track: &MediaTrack) -> Self {
+        LyricsQuery {
+            artist: track.artist.clone(),
+            title: track.title.clone(),
+            album: track.album_title.clone(),
+            length: track.length,
+        }
+    }
+}
+
+impl LyricsQuery {
+    /// Name of the cache file, derived from the artist and title.
+    fn cache_name(&self) -> String {
+        let key = format!("{} - {}", self.artist, self.title).to_lowercase();
+        let name: String = key
+            .chars()
+            .map(|c| {
+                if c.is_alphanumeric() || c == ' ' || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .take(MAX_CACHE_NAME_LEN)
+            .collect();
+        format!("{}.lrc", name.trim())
+    }
+}
+
+/// Gets timed lyrics from LRCLIB (https://lrclib.net).
+/// Lyrics are cached on disk, including which tracks have none.
+#[derive(Clone)]
+pub struct LyricsProvider {
+    cache_dir: PathBuf,
+}
+
+impl Default for LyricsProvider {
+    fn default() -> Self {
+        LyricsProvider {
+            cache_dir: get_data_dir().join(CACHE_DIR),
+        }
+    }
+}
+
+impl LyricsProvider {
+    /// Gets the lyrics of the track identified by [query].
+    /// Returns [None] if there are no timed lyrics for the track.
+    pub async fn get(&self, query: &LyricsQuery) -> Result<Option<Lyrics>, LyricsError> {
+        let path = self.cache_dir.join(query.cache_name());
+        let lrc = match tokio::fs::read_to_string(&path).await {
+            Ok(lrc) => lrc,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                let lrc = fetch_lrclib(query.clone()).await?.unwrap_or_default();
+                // An empty file remembers that there are no lyrics
+                let res = async {
+                    tokio::fs::create_dir_all(&self.cache_dir).await?;
+                    tokio::fs::write(&path, &lrc).await
+                };
+                if let Err(e) = res.await {
+                    log::warn!("Could not cache lyrics in {:?}: {}", path, e);
+                }
+                lrc
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let lyrics = Lyrics::parse_lrc(&lrc);
+        Ok(if lyrics.lines.is_empty() {
+            None
+        } else {
+            Some(lyrics)
+        })
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LrclibTrack {
+    synced_lyrics: Option<String>,
+}
+
+/// Fetches the timed lyrics of the track identified by [query] in the LRC format.
+async fn fetch_lrclib(query: LyricsQuery) -> Result<Option<String>, LyricsError> {
+    // WinRT http contents can't be sent between threads, so the whole request stays on one thread
+    let rt_handle = tokio::runtime::Handle::current();
+    tokio::task::spawn_blocking(move || {
+        rt_handle.block_on(async {
+            tokio::time::timeout(REQUEST_TIMEOUT, request_lrclib(&query))
+                .await
+                .unwrap_or(Err(LyricsError::Timeout))
+        })
+    })
+    .await?
+}
+
+async fn request_lrclib(query: &LyricsQuery) -> Result<Option<String>, LyricsError> {
+    let length = query.length.to_string();
+    let mut params = vec![
+        ("artist_name", query.artist.as_str()),
+        ("track_name", query.title.as_str()),
+        ("album_name", query.album.as_str()),
+    ];
+    // LRCLIB matches the duration within a few seconds, so an unknown one must be left out
+    if query.length > 0 {
+        params.push(("duration", &length));
+    }
+
+    let mut url = LRCLIB_URL.to_string();
+    for (i, (name, value)) in params.into_iter().enumerate() {
+        let value = Uri::EscapeComponent(&HSTRING::from(value))?;
+        url += &format!("{}{}={}", if i == 0 { '?' } else { '&' }, name, value);
+    }
+
+    let client = HttpClient::new()?;
+    client
+        .DefaultRequestHeaders()?
+        .UserAgent()?
+        .TryParseAdd(&HSTRING::from(USER_AGENT))?;
+    let response = client
+        .GetAsync(&Uri::CreateUri(&HSTRING::from(url))?)?
+        .await?;
+    if response.StatusCode()? == HttpStatusCode::NotFound {
+        return Ok(None);
+    }
+    response.EnsureSuccessStatusCode()?;
+
+    let body = response.Content()?.ReadAsStringAsync()?.await?;
+    let track: LrclibTrack = serde_json::from_str(&body.to_string())?;
+    Ok(track.synced_lyrics)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn line(secs: f64, text: &str) -> LyricLine {
+        LyricLine {
+            time: Duration::from_secs_f64(secs),
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_lrc() {
+        let lrc = "[ar:Artist]\n[ti:Title]\n\
+            [00:12.50]First line\n\
+            [00:05.1] Intro \n\
+            [00:20.00][01:02.003]Chorus\n\
+            [00:30]\n\
+            untimed line\n";
+        let lyrics = Lyrics::parse_lrc(lrc);
+        assert_eq!(
+            lyrics.lines,
+            vec![
+                line(5.1, "Intro"),
+                line(12.5, "First line"),
+                line(20.0, "Chorus"),
+                line(30.0, ""),
+                line(62.003, "Chorus"),
+            ]
+        );
+        assert!(Lyrics::parse_lrc("").lines.is_empty());
+        assert!(Lyrics::parse_lrc("[00:1x.00]Broken").lines.is_empty());
+    }
+
+    #[test]
+    fn finds_line_at_position() {
+        let lyrics = Lyrics::parse_lrc("[00:05.00]One\n[00:10.00]Two\n[00:15.00]Three");
+        assert_eq!(lyrics.line_at(Duration::from_secs(2)), None);
+        assert_eq!(lyrics.line_at(Duration::from_secs(5)), Some(0));
+        assert_eq!(lyrics.line_at(Duration::from_secs(12)), Some(1));
+        assert_eq!(lyrics.line_at(Duration::from_secs(300)), Some(2));
+        assert_eq!(Lyrics::default().line_at(Duration::from_secs(1)), None);
+    }
+
+    #[test]
+    fn cache_name_is_a_valid_file_name() {
+        let query = LyricsQuery {
+            artist: "AC/DC".to_string(),
+            title: "Who Made Who?".to_string(),
+            album: String::new(),
+            length: 0,
+        };
+        assert_eq!(query.cache_name(), "ac_dc - who made who_.lrc");
+    }
+}
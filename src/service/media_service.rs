@@ -5,20 +5,39 @@ use anyhow::Result;
 use async_trait::async_trait;
 use tokio::sync::RwLock;
 
-use crate::service::BaseService;
+use crate::service::{ActivityLog, BaseService, MetadataRules};
 
 #[derive(Clone, Debug)]
 pub enum PlaybackChangedEvent {
     TrackChanged,
+    /// The pending cover of the current track finished loading (or failed to).
+    CoverChanged,
     Play,
     Pause,
     Volume,
+    /// Shuffle or repeat changed.
+    PlaybackMode,
     PlaybackProgress,
+    /// Something went wrong in the background (e.g. while handling player events).
+    ServiceError(String),
+    /// The process of the source app ended unexpectedly while playing.
+    SourceCrashed(CrashedApp),
+}
+
+/// See [PlaybackChangedEvent::SourceCrashed].
+#[derive(Clone, Debug)]
+pub struct CrashedApp {
+    pub app_id: String,
+    /// Executable of the process, to restart it
+    pub image_path: String,
+    pub exit_code: u32,
 }
 
 pub enum AlbumCover {
     Url(String),
     Image(image::RgbaImage),
+    /// Still loading, see [PlaybackChangedEvent::CoverChanged]
+    Pending,
     None,
 }
 
@@ -35,6 +54,7 @@ impl Debug for AlbumCover {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let img_type = match self {
             AlbumCover::Image(_) => "RgbaImage",
+            AlbumCover::Pending => "Pending",
             AlbumCover::None => "None",
             AlbumCover::Url(_) => "Url",
         };
@@ -51,16 +71,53 @@ pub struct MediaTrack {
     pub length: u64, // seconds
 }
 
+/// A media session of any application, see [MediaService::list_sessions].
+#[derive(Clone, Debug)]
+pub struct MediaSessionInfo {
+    pub app_id: String,
+    pub app_name: String,
+    /// Empty if the session has no track (yet)
+    pub track_title: String,
+    pub track_artist: String,
+    pub is_playing: bool,
+}
+
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub enum RepeatMode {
+    #[default]
+    Off,
+    /// Repeats the current track
+    Track,
+    /// Repeats the current playlist (or album,...)
+    List,
+}
+
+impl RepeatMode {
+    /// The mode following this one when cycling through them like most players do:
+    /// Off, List, Track.
+    pub fn next(self) -> Self {
+        match self {
+            RepeatMode::Off => RepeatMode::List,
+            RepeatMode::List => RepeatMode::Track,
+            RepeatMode::Track => RepeatMode::Off,
+        }
+    }
+}
+
 #[derive(Default, Debug)]
 pub struct PlaybackState {
     pub is_playing: bool,
     pub volume: u32,           // %
     pub progress: Option<u32>, // %
+    pub position: Option<u64>, // seconds
+    pub shuffle: bool,
+    pub repeat: RepeatMode,
 }
 
 #[derive(thiserror::Error, Debug)]
 pub enum MediaServiceError {
-    #[error("WinRT error")]
+    #[cfg(windows)]
+    #[error("WinRT error: {0}")]
     WinRt(#[from] windows::core::Error),
     #[error(transparent)]
     Other(#[from] anyhow::Error),
@@ -99,6 +156,14 @@ pub trait MediaService: BaseService<PlaybackChangedEvent> {
     /// [volume] must be between 0 and 100 (inclusive) and will be clamped otherwise.
     async fn set_volume(&mut self, volume: u32) -> Result<(), MediaServiceError>;
 
+    /// Enables or disables shuffling the tracks of the current playlist (or album,...).
+    /// Does nothing if the player doesn't support shuffling.
+    async fn set_shuffle(&mut self, shuffle: bool) -> Result<(), MediaServiceError>;
+
+    /// Sets whether the current track or playlist is repeated.
+    /// Does nothing if the player doesn't support repeating.
+    async fn set_repeat(&mut self, repeat: RepeatMode) -> Result<(), MediaServiceError>;
+
     /// Sets the id of the media application to be controled and observed for changes.
     /// This id is platform dependent.
     /// On Windows, for example, it is the name of the application executable.
@@ -108,12 +173,37 @@ pub trait MediaService: BaseService<PlaybackChangedEvent> {
     /// See [MediaService::set_source_app_id] for more.
     fn get_source_app_id(&self) -> &str;
 
-    /// Gets all available media app IDs which can be monitored.
-    fn get_available_source_apps_ids(&self) -> Result<Vec<String>, MediaServiceError>;
+    /// Sets the media applications to fall back to, in order of preference.
+    /// The service then follows whichever of the source app and these apps is playing,
+    /// preferring the source app. An empty list only follows the source app.
+    fn set_fallback_app_ids(&mut self, app_ids: Vec<String>) -> Result<(), MediaServiceError>;
+
+    /// Gets the id of the media application actually followed.
+    /// This is the source app unless the service fell back to another app.
+    fn get_active_app_id(&self) -> &str;
+
+    /// Whether the media application is currently running and observed.
+    fn has_source_session(&self) -> bool;
+
+    /// Sets the rules rewriting the metadata of tracks before they are published.
+    /// The rules are also applied to the current track.
+    fn set_metadata_rules(&mut self, rules: MetadataRules) -> Result<(), MediaServiceError>;
+
+    /// Gets all media sessions which can be monitored, including their current track.
+    /// May block while querying the sessions.
+    fn list_sessions(&self) -> Result<Vec<MediaSessionInfo>, MediaServiceError>;
+
+    /// Gets a human readable name of the media application identified by [app_id].
+    /// Falls back to a name derived from [app_id] if the application is unknown.
+    fn get_source_app_name(&self, app_id: &str) -> String;
 
     fn current_track(&self) -> Option<&MediaTrack>;
     fn current_playback_state(&self) -> &PlaybackState;
 
+    /// Gets the timeline of the most recent activities (sessions, tracks, errors)
+    /// of this service for diagnostic purposes.
+    fn activity_log(&self) -> &ActivityLog;
+
     async fn toggle_playback(&mut self) -> Result<(), MediaServiceError> {
         let playback_state = self.current_playback_state();
         if playback_state.is_playing {
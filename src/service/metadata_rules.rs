@@ -0,0 +1,190 @@
+use std::{borrow::Cow, sync::Arc};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    on_settings_changed,
+    service::{media_service::MediaTrack, BaseService, NormalizationRule, SharedMediaService},
+    settings::SpotickAppSettings,
+};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum MetadataField {
+    Title,
+    Artist,
+    AlbumTitle,
+}
+
+/// A user defined find and replace rule for track metadata.
+/// [MetadataRule::find] is a regular expression and [MetadataRule::replace]
+/// may reference its capture groups (e.g. `$1`).
+/// The rule only applies to tracks of [MetadataRule::source_app] if set.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MetadataRule {
+    pub source_app: Option<String>,
+    pub field: MetadataField,
+    pub find: String,
+    pub replace: String,
+}
+
+/// Compiled [MetadataRule]s ready to be applied to [MediaTrack]s,
+/// followed by optional [NormalizationRule]s.
+#[derive(Default, Debug)]
+pub struct MetadataRules {
+    rules: Vec<(MetadataRule, Regex)>,
+    normalization: Vec<NormalizationRule>,
+}
+
+impl MetadataRules {
+    /// Compiles the given rules.
+    /// Rules with an invalid regular expression are skipped.
+    pub fn new(rules: &[MetadataRule]) -> Self {
+        let rules = rules
+            .iter()
+            .filter_map(|rule| match Regex::new(&rule.find) {
+                Ok(regex) => Some((rule.clone(), regex)),
+                Err(e) => {
+                    log::error!("Skipping invalid metadata rule {:?}: {}", rule, e);
+                    None
+                }
+            })
+            .collect();
+        MetadataRules {
+            rules,
+            normalization: Vec::new(),
+        }
+    }
+
+    /// Normalizes the metadata after the find and replace rules were applied.
+    pub fn with_normalization(mut self, normalization: &[NormalizationRule]) -> Self {
+        self.normalization = normalization.to_vec();
+        self
+    }
+
+    /// Applies all rules in order to the [track] of the [source_app].
+    pub fn apply(&self, source_app: &str, track: &mut MediaTrack) {
+        for (rule, regex) in &self.rules {
+            if let Some(rule_app) = &rule.source_app {
+                if !rule_app.eq_ignore_ascii_case(source_app) {
+                    continue;
+                }
+            }
+
+            let value = match rule.field {
+                MetadataField::Title => &mut track.title,
+                MetadataField::Artist => &mut track.artist,
+                MetadataField::AlbumTitle => &mut track.album_title,
+            };
+            let replaced = match regex.replace_all(value, rule.replace.as_str()) {
+                Cow::Owned(replaced) => replaced,
+                Cow::Borrowed(_) => continue,
+            };
+            *value = replaced.trim().to_string();
+        }
+
+        for rule in &self.normalization {
+            rule.apply(source_app, track);
+        }
+    }
+}
+
+/// Keeps the metadata rules of the [media_service] in sync with the [settings].
+pub async fn register_metadata_rules_changed(
+    settings: SpotickAppSettings,
+    media_service: SharedMediaService,
+) {
+    let media_service = Arc::downgrade(&media_service);
+    let mut applied_rules = None;
+    on_settings_changed!(settings, |settings| {
+        let rules = (
+            settings.metadata_rules.clone().unwrap_or_default(),
+            settings.metadata_normalization.clone().unwrap_or_default(),
+        );
+        if applied_rules.as_ref() != Some(&rules) {
+            if let Some(media_service) = media_service.upgrade() {
+                let (find_replace, normalization) = &rules;
+                let res = media_service.write().await.set_metadata_rules(
+                    MetadataRules::new(find_replace).with_normalization(normalization),
+                );
+                if let Err(e) = res {
+                    log::error!("Could not apply metadata rules: {}", e);
+                }
+            }
+            applied_rules = Some(rules);
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use crate::service::AlbumCover;
+
+    use super::*;
+
+    fn track(title: &str, artist: &str) -> MediaTrack {
+        MediaTrack {
+            title: title.into(),
+            artist: artist.into(),
+            album_title: "Album".into(),
+            album_cover: AlbumCover::None,
+            length: 180,
+        }
+    }
+
+    fn rule(
+        source_app: Option<&str>,
+        field: MetadataField,
+        find: &str,
+        replace: &str,
+    ) -> MetadataRule {
+        MetadataRule {
+            source_app: source_app.map(String::from),
+            field,
+            find: find.into(),
+            replace: replace.into(),
+        }
+    }
+
+    #[test]
+    fn strips_video_suffix() {
+        let rules = MetadataRules::new(&[rule(
+            None,
+            MetadataField::Title,
+            r"\((Official )?(Music )?Video\)",
+            "",
+        )]);
+        let mut t = track("Song (Official Music Video)", "Artist");
+        rules.apply("chrome.exe", &mut t);
+        assert_eq!(t.title, "Song");
+        assert_eq!(t.artist, "Artist");
+    }
+
+    #[test]
+    fn capture_groups_and_scoping() {
+        let rules = MetadataRules::new(&[rule(
+            Some("Chrome.exe"),
+            MetadataField::Artist,
+            r"^(.+) - Topic$",
+            "$1",
+        )]);
+
+        let mut t = track("Song", "Artist - Topic");
+        rules.apply("spotify.exe", &mut t);
+        assert_eq!(t.artist, "Artist - Topic");
+
+        rules.apply("chrome.exe", &mut t);
+        assert_eq!(t.artist, "Artist");
+    }
+
+    #[test]
+    fn invalid_rules_are_skipped() {
+        let rules = MetadataRules::new(&[
+            rule(None, MetadataField::Title, "(unclosed", ""),
+            rule(None, MetadataField::Title, "a", "b"),
+        ]);
+        let mut t = track("aaa", "Artist");
+        rules.apply("spotify.exe", &mut t);
+        assert_eq!(t.title, "bbb");
+    }
+}
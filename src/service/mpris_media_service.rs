@@ -0,0 +1,976 @@
+use std::{
+    fmt::Display,
+    io::{self, Read},
+    path::PathBuf,
+    sync::{
+        mpsc::{self, RecvTimeoutError, TryRecvError},
+        Arc, Weak,
+    },
+    thread,
+    time::Duration,
+};
+
+use image::RgbaImage;
+use mpris::{LoopStatus, Metadata, PlaybackStatus, Player, PlayerFinder};
+use tokio::sync::{
+    broadcast::{channel, Receiver, Sender},
+    mpsc::{unbounded_channel, UnboundedSender},
+    oneshot, RwLock,
+};
+
+use crate::service::{
+    decode_thumbnail,
+    media_service::{
+        MediaService, MediaServiceError, MediaSessionInfo, MediaTrack, PlaybackChangedEvent,
+        PlaybackState, RepeatMode,
+    },
+    Activity, ActivityLog, AlbumCover, BaseService, MetadataRules, ThumbnailError, ThumbnailLimits,
+};
+
+/// How often we look for the source player while it isn't running.
+const PLAYER_SEARCH_INTERVAL: Duration = Duration::from_secs(2);
+/// How often the source player is polled for changes.
+const PLAYER_TICK_INTERVAL_MS: u32 = 250;
+
+/// A media service observing one media player via D-Bus MPRIS.
+///
+/// The D-Bus connection of the mpris crate can't be shared between threads,
+/// so a dedicated worker thread owns the player. It receives [Command]s from
+/// the service and reports back [WorkerEvent]s, which are applied to the service
+/// by a tokio task. The worker never locks the service itself, so awaiting a command
+/// while holding the service lock can't dead lock.
+pub struct MprisMediaService {
+    self_ref: Weak<RwLock<MprisMediaService>>,
+    source_app_id: String,
+    source_app_name: Option<String>,
+    /// Commands to the worker thread, once started.
+    commands: Option<mpsc::Sender<Command>>,
+    current_track: Option<MediaTrack>,
+    playback_state: PlaybackState,
+    event_sender: Sender<PlaybackChangedEvent>,
+    activity_log: ActivityLog,
+    metadata_rules: MetadataRules,
+    thumbnail_limits: ThumbnailLimits,
+}
+
+enum PlayerControl {
+    Next,
+    Previous,
+    Play,
+    Pause,
+    Seek(u32),
+    SetVolume(u32),
+    SetShuffle(bool),
+    SetRepeat(RepeatMode),
+}
+
+enum Command {
+    Control(
+        PlayerControl,
+        oneshot::Sender<Result<(), MediaServiceError>>,
+    ),
+    SetSourceApp(String),
+    ListSessions(mpsc::Sender<Vec<MediaSessionInfo>>),
+    /// Publishes the current track again (e.g. after the metadata rules changed).
+    Refresh,
+    SetThumbnailLimits(ThumbnailLimits),
+}
+
+enum WorkerEvent {
+    PlayerFound(String),
+    PlayerLost,
+    TrackChanged(Option<MediaTrack>),
+    /// The remote cover of the current track finished loading.
+    CoverLoaded(Result<RgbaImage, ThumbnailError>),
+    PlaybackChanged {
+        is_playing: bool,
+        volume: u32,
+        shuffle: bool,
+        repeat: RepeatMode,
+    },
+    PositionChanged(u64),
+    Error(String),
+}
+
+/// What the worker follows and how.
+struct WorkerConfig {
+    source_app_id: String,
+    thumbnail_limits: ThumbnailLimits,
+}
+
+/// What the worker should do after handling a [Command].
+enum Flow {
+    Continue,
+    Refresh,
+    SearchPlayer,
+    Stop,
+}
+
+fn dbus_error(e: impl Display) -> MediaServiceError {
+    MediaServiceError::Other(anyhow::anyhow!("D-Bus error: {}", e))
+}
+
+fn worker_stopped() -> MediaServiceError {
+    MediaServiceError::Other(anyhow::anyhow!("MPRIS worker is not running"))
+}
+
+/// Whether the MPRIS bus name of a player (without the "org.mpris.MediaPlayer2." prefix)
+/// belongs to [source_app_id]. Instances of the same player share a prefix
+/// (e.g. "vlc.instance1234").
+fn is_source_player(bus_name: &str, source_app_id: &str) -> bool {
+    let bus_name = bus_name.to_lowercase();
+    bus_name == source_app_id
+        || bus_name
+            .strip_prefix(source_app_id)
+            .is_some_and(|instance| instance.starts_with('.'))
+}
+
+/// Turns a local "file://" art url into a path. Returns [None] for any other url.
+fn file_url_to_path(url: &str) -> Option<PathBuf> {
+    let path = url.strip_prefix("file://")?;
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut chars = path.bytes();
+    while let Some(b) = chars.next() {
+        if b == b'%' {
+            let hex = [chars.next()?, chars.next()?];
+            let hex = std::str::from_utf8(&hex).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+        } else {
+            bytes.push(b);
+        }
+    }
+    String::from_utf8(bytes).ok().map(PathBuf::from)
+}
+
+fn is_remote_url(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
+/// Reads the cover of a track from a local file.
+/// Remote covers are [AlbumCover::Pending] until [fetch_album_cover] loaded them.
+fn read_album_cover(art_url: Option<&str>) -> AlbumCover {
+    let Some(art_url) = art_url.filter(|url| !url.is_empty()) else {
+        return AlbumCover::None;
+    };
+    let Some(path) = file_url_to_path(art_url) else {
+        if is_remote_url(art_url) {
+            return AlbumCover::Pending;
+        }
+        log::warn!("Unsupported album cover url: {}", art_url);
+        return AlbumCover::None;
+    };
+
+    let cover = std::fs::read(&path)
+        .map_err(Into::into)
+        .and_then(|buffer| decode_thumbnail(&buffer, art_url));
+    match cover {
+        Ok(img) => AlbumCover::Image(img),
+        Err(e) => {
+            log::error!("Unable to read album cover {:?}: {}", path, e);
+            AlbumCover::None
+        }
+    }
+}
+
+fn http_error(e: ureq::Error) -> ThumbnailError {
+    let timed_out = std::error::Error::source(&e)
+        .and_then(|source| source.downcast_ref::<io::Error>())
+        .is_some_and(|e| e.kind() == io::ErrorKind::TimedOut);
+    if timed_out {
+        ThumbnailError::Timeout
+    } else {
+        ThumbnailError::Http(Box::new(e))
+    }
+}
+
+/// Downloads and decodes a remote cover.
+/// Fails once the cover exceeds [limits.max_size] bytes, no matter the size reported by the server.
+fn fetch_album_cover(url: &str, limits: ThumbnailLimits) -> Result<RgbaImage, ThumbnailError> {
+    let agent = ureq::AgentBuilder::new().timeout(limits.timeout).build();
+    let response = agent.get(url).call().map_err(http_error)?;
+    let content_type = response.content_type().to_string();
+    let reported_size = response
+        .header("Content-Length")
+        .and_then(|size| size.parse::<u64>().ok());
+    log::info!(
+        "Album cover content-type: {}, Size: {:?}",
+        &content_type,
+        reported_size
+    );
+    if let Some(size) = reported_size.filter(|size| *size > limits.max_size) {
+        return Err(ThumbnailError::TooLarge(size));
+    }
+
+    let mut buffer = Vec::new();
+    response
+        .into_reader()
+        .take(limits.max_size + 1)
+        .read_to_end(&mut buffer)
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::TimedOut => ThumbnailError::Timeout,
+            _ => e.into(),
+        })?;
+    if buffer.len() as u64 > limits.max_size {
+        return Err(ThumbnailError::TooLarge(buffer.len() as u64));
+    }
+
+    decode_thumbnail(&buffer, &content_type)
+}
+
+fn read_track(metadata: &Metadata) -> Option<MediaTrack> {
+    let title = metadata.title().filter(|title| !title.is_empty())?;
+    let artist = metadata
+        .artists()
+        .map(|artists| artists.join(", "))
+        .filter(|artist| !artist.is_empty());
+
+    Some(MediaTrack {
+        title: title.to_string(),
+        artist: artist.unwrap_or_else(|| "No Artist".into()),
+        album_title: metadata.album_name().unwrap_or("No Title").to_string(),
+        album_cover: read_album_cover(metadata.art_url()),
+        length: metadata.length().map_or(0, |length| length.as_secs()),
+    })
+}
+
+fn read_session_info(player: &Player) -> MediaSessionInfo {
+    let metadata = player.get_metadata().ok();
+    MediaSessionInfo {
+        app_id: player.bus_name_trimmed().to_string(),
+        app_name: player.identity().to_string(),
+        track_title: metadata
+            .as_ref()
+            .and_then(|metadata| metadata.title())
+            .unwrap_or_default()
+            .to_string(),
+        track_artist: metadata
+            .as_ref()
+            .and_then(|metadata| metadata.artists())
+            .map(|artists| artists.join(", "))
+            .unwrap_or_default(),
+        is_playing: matches!(player.get_playback_status(), Ok(PlaybackStatus::Playing)),
+    }
+}
+
+impl MprisMediaService {
+    /// Creates a new media service monitoring the player identified by
+    /// the [source_app_id] (the MPRIS bus name without the
+    /// "org.mpris.MediaPlayer2." prefix - e.g. "spotify").
+    ///
+    /// You have to call [MprisMediaService::begin_monitor_sessions] to receive
+    /// [PlaybackChangedEvent]s.
+    pub fn new(source_app_id: impl Into<String>) -> Arc<RwLock<Self>> {
+        Arc::new_cyclic(|weak| {
+            let (tx, _) = channel(16);
+            RwLock::new(MprisMediaService {
+                self_ref: weak.clone(),
+                source_app_name: None,
+                commands: None,
+                source_app_id: source_app_id.into().to_lowercase(),
+                current_track: None,
+                playback_state: PlaybackState::default(),
+                event_sender: tx,
+                activity_log: ActivityLog::default(),
+                metadata_rules: MetadataRules::default(),
+                thumbnail_limits: ThumbnailLimits::default(),
+            })
+        })
+    }
+
+    /// Starts the worker thread looking for and monitoring the source player.
+    /// Does nothing if already started.
+    pub fn begin_monitor_sessions(&mut self) -> Result<(), MediaServiceError> {
+        if self.commands.is_some() {
+            return Ok(());
+        }
+
+        let (commands_tx, commands_rv) = mpsc::channel();
+        let (events_tx, mut events_rv) = unbounded_channel();
+        let config = WorkerConfig {
+            source_app_id: self.source_app_id.clone(),
+            thumbnail_limits: self.thumbnail_limits,
+        };
+        thread::Builder::new()
+            .name("mpris-worker".into())
+            .spawn(move || run_worker(commands_rv, events_tx, config))
+            .map_err(|e| MediaServiceError::Other(e.into()))?;
+        self.commands = Some(commands_tx);
+
+        let srv = self.self_ref.clone();
+        tokio::spawn(async move {
+            while let Some(ev) = events_rv.recv().await {
+                let Some(srv) = srv.upgrade() else {
+                    break;
+                };
+                srv.write().await.handle_worker_event(ev);
+            }
+        });
+        Ok(())
+    }
+
+    fn send_event(&mut self, ev: PlaybackChangedEvent) {
+        match ev {
+            PlaybackChangedEvent::TrackChanged => {
+                log::info!("{:?}: {:?}", ev, self.current_track);
+                let title = self.current_track.as_ref().map(|t| t.title.clone());
+                self.activity_log.record(Activity::TrackChanged(title));
+            }
+            PlaybackChangedEvent::Play => {
+                log::info!("{:?}: {:?}", ev, self.playback_state);
+                self.activity_log.record(Activity::Playing);
+            }
+            PlaybackChangedEvent::Pause => {
+                log::info!("{:?}: {:?}", ev, self.playback_state);
+                self.activity_log.record(Activity::Paused);
+            }
+            _ => {}
+        };
+        let _ = self.event_sender.send(ev);
+    }
+
+    fn handle_worker_event(&mut self, ev: WorkerEvent) {
+        match ev {
+            WorkerEvent::PlayerFound(name) => {
+                log::info!("Beginning to monitor player: {}", &self.source_app_id);
+                self.activity_log
+                    .record(Activity::SessionFound(self.source_app_id.clone()));
+                self.source_app_name = Some(name);
+            }
+            WorkerEvent::PlayerLost => {
+                log::info!("Stopping monitoring player: {}", &self.source_app_id);
+                self.activity_log
+                    .record(Activity::SessionLost(self.source_app_id.clone()));
+                self.source_app_name = None;
+                self.current_track = None;
+                self.playback_state = PlaybackState::default();
+                self.send_event(PlaybackChangedEvent::TrackChanged);
+            }
+            WorkerEvent::TrackChanged(mut track) => {
+                if let Some(track) = &mut track {
+                    self.metadata_rules.apply(&self.source_app_id, track);
+                }
+                self.current_track = track;
+                self.update_progress();
+                self.send_event(PlaybackChangedEvent::TrackChanged);
+            }
+            WorkerEvent::CoverLoaded(res) => {
+                let cover = match res {
+                    Ok(img) => AlbumCover::Image(img),
+                    Err(e) => {
+                        log::error!("Unable to fetch album cover: {}", e);
+                        // Let the user know why there is no cover
+                        if let ThumbnailError::UnsupportedFormat(_) = e {
+                            self.send_event(PlaybackChangedEvent::ServiceError(e.to_string()));
+                        }
+                        AlbumCover::None
+                    }
+                };
+                if let Some(track) = &mut self.current_track {
+                    track.album_cover = cover;
+                    self.send_event(PlaybackChangedEvent::CoverChanged);
+                }
+            }
+            WorkerEvent::PlaybackChanged {
+                is_playing,
+                volume,
+                shuffle,
+                repeat,
+            } => {
+                let volume_changed = self.playback_state.volume != volume;
+                self.playback_state.volume = volume;
+                if volume_changed {
+                    self.send_event(PlaybackChangedEvent::Volume);
+                }
+                if self.playback_state.shuffle != shuffle || self.playback_state.repeat != repeat {
+                    self.playback_state.shuffle = shuffle;
+                    self.playback_state.repeat = repeat;
+                    self.send_event(PlaybackChangedEvent::PlaybackMode);
+                }
+                if self.playback_state.is_playing != is_playing {
+                    self.playback_state.is_playing = is_playing;
+                    self.send_event(if is_playing {
+                        PlaybackChangedEvent::Play
+                    } else {
+                        PlaybackChangedEvent::Pause
+                    });
+                }
+            }
+            WorkerEvent::PositionChanged(position) => {
+                self.playback_state.position = Some(position);
+                self.update_progress();
+                self.send_event(PlaybackChangedEvent::PlaybackProgress);
+            }
+            WorkerEvent::Error(e) => {
+                self.activity_log.record(Activity::Error(e.clone()));
+                self.send_event(PlaybackChangedEvent::ServiceError(e));
+            }
+        }
+    }
+
+    fn update_progress(&mut self) {
+        let length = self.current_track.as_ref().map_or(0, |t| t.length);
+        self.playback_state.progress = match self.playback_state.position {
+            Some(position) if length > 0 => Some((position.min(length) * 100 / length) as u32),
+            _ => None,
+        };
+    }
+
+    /// Overrides the default limits for fetching the remote covers of tracks.
+    pub fn set_thumbnail_limits(&mut self, limits: ThumbnailLimits) {
+        self.thumbnail_limits = limits;
+        if self.commands.is_some() {
+            if let Err(e) = self.send_command(Command::SetThumbnailLimits(limits)) {
+                log::error!("Could not update thumbnail limits: {}", e);
+            }
+        }
+    }
+
+    fn send_command(&self, cmd: Command) -> Result<(), MediaServiceError> {
+        self.commands
+            .as_ref()
+            .ok_or_else(worker_stopped)?
+            .send(cmd)
+            .map_err(|_| worker_stopped())
+    }
+
+    async fn control(&self, control: PlayerControl) -> Result<(), MediaServiceError> {
+        // Nothing to control before monitoring started
+        if self.commands.is_none() {
+            return Ok(());
+        }
+
+        let (tx, rx) = oneshot::channel();
+        self.send_command(Command::Control(control, tx))?;
+        rx.await.map_err(|_| worker_stopped())?
+    }
+}
+
+impl Drop for MprisMediaService {
+    fn drop(&mut self) {
+        // Dropping the command sender stops the worker
+        self.activity_log.dump();
+    }
+}
+
+impl BaseService<PlaybackChangedEvent> for MprisMediaService {
+    fn subscribe(&self) -> Receiver<PlaybackChangedEvent> {
+        self.event_sender.subscribe()
+    }
+}
+
+#[async_trait::async_trait]
+impl MediaService for MprisMediaService {
+    async fn next_track(&mut self) -> Result<(), MediaServiceError> {
+        self.control(PlayerControl::Next).await
+    }
+
+    async fn previous_track(&mut self) -> Result<(), MediaServiceError> {
+        self.control(PlayerControl::Previous).await
+    }
+
+    async fn play(&mut self) -> Result<(), MediaServiceError> {
+        self.control(PlayerControl::Play).await
+    }
+
+    async fn pause(&mut self) -> Result<(), MediaServiceError> {
+        self.control(PlayerControl::Pause).await
+    }
+
+    async fn seek(&mut self, playback_percent: u32) -> Result<(), MediaServiceError> {
+        self.control(PlayerControl::Seek(playback_percent.min(100)))
+            .await
+    }
+
+    async fn set_volume(&mut self, volume: u32) -> Result<(), MediaServiceError> {
+        self.control(PlayerControl::SetVolume(volume.min(100)))
+            .await
+    }
+
+    async fn set_shuffle(&mut self, shuffle: bool) -> Result<(), MediaServiceError> {
+        self.control(PlayerControl::SetShuffle(shuffle)).await
+    }
+
+    async fn set_repeat(&mut self, repeat: RepeatMode) -> Result<(), MediaServiceError> {
+        self.control(PlayerControl::SetRepeat(repeat)).await
+    }
+
+    fn set_source_app_id(&mut self, app_id: String) -> Result<(), MediaServiceError> {
+        self.source_app_id = app_id.to_lowercase();
+        if self.commands.is_some() {
+            self.send_command(Command::SetSourceApp(self.source_app_id.clone()))?;
+        }
+        Ok(())
+    }
+
+    fn get_source_app_id(&self) -> &str {
+        &self.source_app_id
+    }
+
+    fn set_fallback_app_ids(&mut self, app_ids: Vec<String>) -> Result<(), MediaServiceError> {
+        // The worker follows a single player for now
+        if !app_ids.is_empty() {
+            log::warn!("Fallback apps aren't supported with MPRIS: {:?}", app_ids);
+        }
+        Ok(())
+    }
+
+    fn get_active_app_id(&self) -> &str {
+        &self.source_app_id
+    }
+
+    fn has_source_session(&self) -> bool {
+        self.source_app_name.is_some()
+    }
+
+    fn set_metadata_rules(&mut self, rules: MetadataRules) -> Result<(), MediaServiceError> {
+        self.metadata_rules = rules;
+        if self.commands.is_some() {
+            self.send_command(Command::Refresh)?;
+        }
+        Ok(())
+    }
+
+    fn list_sessions(&self) -> Result<Vec<MediaSessionInfo>, MediaServiceError> {
+        let (tx, rx) = mpsc::channel();
+        self.send_command(Command::ListSessions(tx))?;
+        rx.recv_timeout(Duration::from_secs(2))
+            .map_err(|_| worker_stopped())
+    }
+
+    fn get_source_app_name(&self, app_id: &str) -> String {
+        if let Some(name) = &self.source_app_name {
+            if app_id.eq_ignore_ascii_case(&self.source_app_id) {
+                return name.clone();
+            }
+        }
+
+        let mut chars = app_id.chars();
+        match chars.next() {
+            Some(first) => first.to_uppercase().chain(chars).collect(),
+            None => app_id.to_string(),
+        }
+    }
+
+    fn current_track(&self) -> Option<&MediaTrack> {
+        self.current_track.as_ref()
+    }
+
+    fn current_playback_state(&self) -> &PlaybackState {
+        &self.playback_state
+    }
+
+    fn activity_log(&self) -> &ActivityLog {
+        &self.activity_log
+    }
+}
+
+fn run_worker(
+    commands: mpsc::Receiver<Command>,
+    events: UnboundedSender<WorkerEvent>,
+    mut config: WorkerConfig,
+) {
+    let finder = match PlayerFinder::new() {
+        Ok(finder) => finder,
+        Err(e) => {
+            log::error!("Could not connect to D-Bus: {}", e);
+            let _ = events.send(WorkerEvent::Error(dbus_error(e).to_string()));
+            return;
+        }
+    };
+
+    loop {
+        let Some(player) = wait_for_player(&finder, &commands, &mut config) else {
+            break;
+        };
+
+        let _ = events.send(WorkerEvent::PlayerFound(player.identity().to_string()));
+        let flow = match monitor_player(&finder, &player, &commands, &events, &mut config) {
+            Ok(flow) => flow,
+            Err(e) => {
+                log::error!("Monitoring player failed: {}", e);
+                let _ = events.send(WorkerEvent::Error(e.to_string()));
+                Flow::SearchPlayer
+            }
+        };
+        let _ = events.send(WorkerEvent::PlayerLost);
+
+        if let Flow::Stop = flow {
+            break;
+        }
+    }
+    log::info!("Stopped MPRIS worker");
+}
+
+fn find_player(finder: &PlayerFinder, source_app_id: &str) -> Option<Player> {
+    match finder.find_all() {
+        Ok(players) => players
+            .into_iter()
+            .find(|player| is_source_player(player.bus_name_trimmed(), source_app_id)),
+        Err(e) => {
+            log::warn!("Could not list players: {}", e);
+            None
+        }
+    }
+}
+
+/// Blocks until the source player is running.
+/// Returns [None] if the service is gone.
+fn wait_for_player(
+    finder: &PlayerFinder,
+    commands: &mpsc::Receiver<Command>,
+    config: &mut WorkerConfig,
+) -> Option<Player> {
+    loop {
+        if let Some(player) = find_player(finder, &config.source_app_id) {
+            return Some(player);
+        }
+
+        match commands.recv_timeout(PLAYER_SEARCH_INTERVAL) {
+            Ok(cmd) => {
+                if let Flow::Stop = handle_command(cmd, None, finder, config) {
+                    return None;
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return None,
+        }
+    }
+}
+
+/// Publishes changes of the [player] until it quits or another player is requested.
+fn monitor_player(
+    finder: &PlayerFinder,
+    player: &Player,
+    commands: &mpsc::Receiver<Command>,
+    events: &UnboundedSender<WorkerEvent>,
+    config: &mut WorkerConfig,
+) -> Result<Flow, MediaServiceError> {
+    let mut tracker = player
+        .track_progress(PLAYER_TICK_INTERVAL_MS)
+        .map_err(dbus_error)?;
+    let mut refresh = true;
+    let mut last_track = None;
+    let mut last_position = None;
+    // Remote covers are fetched on their own threads, so slow servers don't stall the player.
+    // Only the cover of the current track is published.
+    let (covers_tx, covers_rv) = mpsc::channel();
+    let mut pending_cover: Option<String> = None;
+
+    loop {
+        let tick = tracker.tick();
+        if tick.player_quit {
+            return Ok(Flow::SearchPlayer);
+        }
+
+        let progress = tick.progress;
+        if tick.progress_changed || refresh {
+            // Progress also changes on e.g. volume changes,
+            // so only read the (possibly large) cover for new tracks
+            let metadata = progress.metadata();
+            let track_key = (metadata.track_id(), metadata.title().map(String::from));
+            if refresh || last_track.as_ref() != Some(&track_key) {
+                let track = read_track(metadata);
+                pending_cover = track
+                    .as_ref()
+                    .and(metadata.art_url())
+                    .filter(|url| is_remote_url(url))
+                    .map(String::from);
+                if let Some(url) = pending_cover.clone() {
+                    let covers_tx = covers_tx.clone();
+                    let limits = config.thumbnail_limits;
+                    thread::spawn(move || {
+                        let res = fetch_album_cover(&url, limits);
+                        let _ = covers_tx.send((url, res));
+                    });
+                }
+                let _ = events.send(WorkerEvent::TrackChanged(track));
+                last_track = Some(track_key);
+            }
+
+            let _ = events.send(WorkerEvent::PlaybackChanged {
+                is_playing: progress.playback_status() == PlaybackStatus::Playing,
+                volume: (progress.current_volume() * 100.0).round() as u32,
+                shuffle: progress.shuffle(),
+                repeat: match progress.loop_status() {
+                    LoopStatus::None => RepeatMode::Off,
+                    LoopStatus::Track => RepeatMode::Track,
+                    LoopStatus::Playlist => RepeatMode::List,
+                },
+            });
+            refresh = false;
+        }
+
+        let position = progress.position().as_secs();
+        if last_position != Some(position) {
+            let _ = events.send(WorkerEvent::PositionChanged(position));
+            last_position = Some(position);
+        }
+
+        while let Ok((url, res)) = covers_rv.try_recv() {
+            if pending_cover.as_ref() == Some(&url) {
+                let _ = events.send(WorkerEvent::CoverLoaded(res));
+                pending_cover = None;
+            }
+        }
+
+        loop {
+            let cmd = match commands.try_recv() {
+                Ok(cmd) => cmd,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return Ok(Flow::Stop),
+            };
+            match handle_command(cmd, Some(player), finder, config) {
+                Flow::Continue => {}
+                Flow::Refresh => refresh = true,
+                flow => return Ok(flow),
+            }
+        }
+    }
+}
+
+fn handle_command(
+    cmd: Command,
+    player: Option<&Player>,
+    finder: &PlayerFinder,
+    config: &mut WorkerConfig,
+) -> Flow {
+    match cmd {
+        Command::Control(control, reply) => {
+            // Nothing to control without a player
+            let res = match player {
+                Some(player) => control_player(player, control),
+                None => Ok(()),
+            };
+            let _ = reply.send(res);
+            Flow::Continue
+        }
+        Command::SetSourceApp(app_id) => {
+            if config.source_app_id == app_id {
+                return Flow::Continue;
+            }
+            config.source_app_id = app_id;
+            Flow::SearchPlayer
+        }
+        Command::ListSessions(reply) => {
+            let sessions = finder
+                .find_all()
+                .map(|players| players.iter().map(read_session_info).collect())
+                .unwrap_or_else(|e| {
+                    log::error!("Could not list players: {}", e);
+                    Vec::new()
+                });
+            let _ = reply.send(sessions);
+            Flow::Continue
+        }
+        Command::Refresh => Flow::Refresh,
+        Command::SetThumbnailLimits(limits) => {
+            config.thumbnail_limits = limits;
+            Flow::Continue
+        }
+    }
+}
+
+fn control_player(player: &Player, control: PlayerControl) -> Result<(), MediaServiceError> {
+    match control {
+        PlayerControl::Next => player.next(),
+        PlayerControl::Previous => player.previous(),
+        PlayerControl::Play => player.play(),
+        PlayerControl::Pause => player.pause(),
+        PlayerControl::SetVolume(volume) => player.set_volume(volume as f64 / 100.0),
+        PlayerControl::SetShuffle(shuffle) => player.set_shuffle(shuffle),
+        PlayerControl::SetRepeat(repeat) => player.set_loop_status(match repeat {
+            RepeatMode::Off => LoopStatus::None,
+            RepeatMode::Track => LoopStatus::Track,
+            RepeatMode::List => LoopStatus::Playlist,
+        }),
+        PlayerControl::Seek(playback_percent) => {
+            let metadata = player.get_metadata().map_err(dbus_error)?;
+            let (Some(track_id), Some(length)) = (metadata.track_id(), metadata.length()) else {
+                return Ok(());
+            };
+            let position = length * playback_percent / 100;
+            player
+                .set_position(track_id, &position)
+                .and_then(|_| player.play())
+        }
+    }
+    .map_err(dbus_error)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        io::{Cursor, Write},
+        net::TcpListener,
+    };
+
+    use image::ImageFormat;
+
+    use super::*;
+
+    fn track(album_cover: AlbumCover) -> MediaTrack {
+        MediaTrack {
+            title: "Song".into(),
+            artist: "Artist".into(),
+            album_title: "Album".into(),
+            album_cover,
+            length: 120,
+        }
+    }
+
+    fn playback(is_playing: bool, volume: u32, shuffle: bool, repeat: RepeatMode) -> WorkerEvent {
+        WorkerEvent::PlaybackChanged {
+            is_playing,
+            volume,
+            shuffle,
+            repeat,
+        }
+    }
+
+    fn received(events: &mut Receiver<PlaybackChangedEvent>) -> Vec<String> {
+        std::iter::from_fn(|| events.try_recv().ok())
+            .map(|ev| format!("{:?}", ev))
+            .collect()
+    }
+
+    /// Answers a single request on a random local port with [body] as PNG.
+    fn serve_once(body: Vec<u8>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Could not bind test server");
+        let url = format!("http://{}/cover.png", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().expect("No request");
+            let _ = stream.read(&mut [0; 1024]);
+            let header = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: image/png\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            );
+            let _ = stream.write_all(header.as_bytes());
+            let _ = stream.write_all(&body);
+        });
+        url
+    }
+
+    #[test]
+    fn sends_only_changed_playback() {
+        let srv = MprisMediaService::new("spotify");
+        let mut srv = srv.try_write().unwrap();
+        let mut events = srv.subscribe();
+
+        srv.handle_worker_event(playback(true, 50, false, RepeatMode::Off));
+        assert_eq!(received(&mut events), ["Volume", "Play"]);
+        srv.handle_worker_event(playback(true, 50, false, RepeatMode::Off));
+        assert!(received(&mut events).is_empty());
+        srv.handle_worker_event(playback(true, 50, true, RepeatMode::Off));
+        assert_eq!(received(&mut events), ["PlaybackMode"]);
+        srv.handle_worker_event(playback(true, 50, true, RepeatMode::List));
+        assert_eq!(received(&mut events), ["PlaybackMode"]);
+        srv.handle_worker_event(playback(false, 70, false, RepeatMode::Off));
+        assert_eq!(received(&mut events), ["Volume", "PlaybackMode", "Pause"]);
+
+        let state = srv.current_playback_state();
+        assert!(!state.is_playing && !state.shuffle);
+        assert_eq!((state.volume, state.repeat), (70, RepeatMode::Off));
+    }
+
+    #[test]
+    fn clears_state_when_player_is_lost() {
+        let srv = MprisMediaService::new("spotify");
+        let mut srv = srv.try_write().unwrap();
+        let mut events = srv.subscribe();
+
+        srv.handle_worker_event(WorkerEvent::PlayerFound("Spotify".into()));
+        srv.handle_worker_event(WorkerEvent::TrackChanged(Some(track(AlbumCover::None))));
+        srv.handle_worker_event(playback(true, 50, true, RepeatMode::Track));
+        srv.handle_worker_event(WorkerEvent::PositionChanged(30));
+        assert!(srv.has_source_session());
+        assert_eq!(srv.get_source_app_name("spotify"), "Spotify");
+        assert_eq!(srv.current_playback_state().progress, Some(25));
+        received(&mut events);
+
+        srv.handle_worker_event(WorkerEvent::PlayerLost);
+        assert_eq!(received(&mut events), ["TrackChanged"]);
+        assert!(!srv.has_source_session());
+        assert!(srv.current_track().is_none());
+        let state = srv.current_playback_state();
+        assert!(!state.is_playing && !state.shuffle);
+        assert_eq!((state.volume, state.repeat), (0, RepeatMode::Off));
+        assert_eq!((state.position, state.progress), (None, None));
+    }
+
+    #[test]
+    fn applies_loaded_covers_to_current_track() {
+        let srv = MprisMediaService::new("spotify");
+        let mut srv = srv.try_write().unwrap();
+        let mut events = srv.subscribe();
+
+        srv.handle_worker_event(WorkerEvent::TrackChanged(Some(track(AlbumCover::Pending))));
+        srv.handle_worker_event(WorkerEvent::CoverLoaded(Ok(RgbaImage::new(2, 2))));
+        assert_eq!(received(&mut events), ["TrackChanged", "CoverChanged"]);
+        assert!(matches!(
+            srv.current_track().map(|t| &t.album_cover),
+            Some(AlbumCover::Image(_))
+        ));
+
+        srv.handle_worker_event(WorkerEvent::CoverLoaded(Err(
+            ThumbnailError::UnsupportedFormat("image/avif".into()),
+        )));
+        assert_eq!(
+            received(&mut events),
+            [
+                "ServiceError(\"Unsupported thumbnail format: image/avif\")",
+                "CoverChanged"
+            ]
+        );
+        assert!(srv.current_track().is_some_and(|t| t.album_cover.is_none()));
+    }
+
+    #[test]
+    fn remote_covers_are_pending() {
+        assert!(matches!(
+            read_album_cover(Some("https://i.scdn.co/image/ab67")),
+            AlbumCover::Pending
+        ));
+        assert!(read_album_cover(Some("ftp://example.com/cover.png")).is_none());
+        assert!(read_album_cover(Some("")).is_none());
+    }
+
+    #[test]
+    fn fetches_remote_covers_within_limits() {
+        let mut png = Cursor::new(Vec::new());
+        RgbaImage::new(4, 4)
+            .write_to(&mut png, ImageFormat::Png)
+            .expect("Could not encode test image");
+        let png = png.into_inner();
+
+        let limits = ThumbnailLimits::default();
+        let cover = fetch_album_cover(&serve_once(png.clone()), limits).unwrap();
+        assert_eq!(cover.dimensions(), (4, 4));
+
+        let limits = ThumbnailLimits {
+            max_size: png.len() as u64 - 1,
+            ..limits
+        };
+        assert!(matches!(
+            fetch_album_cover(&serve_once(png), limits),
+            Err(ThumbnailError::TooLarge(_))
+        ));
+    }
+
+    #[test]
+    fn matches_player_instances() {
+        assert!(is_source_player("spotify", "spotify"));
+        assert!(is_source_player("VLC.instance1234", "vlc"));
+        assert!(!is_source_player("vlcx", "vlc"));
+        assert!(!is_source_player("firefox", "spotify"));
+    }
+
+    #[test]
+    fn file_urls() {
+        assert_eq!(
+            file_url_to_path("file:///home/me/My%20Covers/cover.jpg"),
+            Some(PathBuf::from("/home/me/My Covers/cover.jpg"))
+        );
+        assert_eq!(file_url_to_path("https://i.scdn.co/image/ab67"), None);
+        assert_eq!(file_url_to_path("file:///broken%2"), None);
+    }
+}
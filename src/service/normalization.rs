@@ -0,0 +1,185 @@
+use std::sync::LazyLock;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use unicode_normalization::UnicodeNormalization;
+
+use crate::service::{media_service::MediaTrack, metadata_rules::MetadataField};
+
+/// Words which stay lowercase inside titles (unless first or last).
+const SMALL_WORDS: &[&str] = &[
+    "a", "an", "and", "as", "at", "but", "by", "feat.", "for", "in", "of", "on", "or", "the", "to",
+    "vs.", "with",
+];
+
+static FEATURING: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\b(?:featuring|feat|ft)\b\.?\s*").unwrap());
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Normalization {
+    /// Unicode NFC normalization and collapsing of whitespace.
+    Unicode,
+    /// Title cases values written in all-caps (or all lowercase).
+    /// Values with mixed casing are considered deliberate and kept.
+    TitleCase,
+    /// Harmonizes "ft.", "Feat" and "featuring" to "feat.".
+    Featuring,
+}
+
+impl Normalization {
+    pub fn apply(&self, value: &str) -> String {
+        match self {
+            Normalization::Unicode => normalize_unicode(value),
+            Normalization::TitleCase => smart_title_case(value),
+            Normalization::Featuring => FEATURING.replace_all(value, "feat. ").into_owned(),
+        }
+    }
+}
+
+/// Applies the [NormalizationRule::steps] in order to the [NormalizationRule::fields]
+/// of tracks. The rule only applies to tracks of [NormalizationRule::source_app] if set.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct NormalizationRule {
+    pub source_app: Option<String>,
+    pub fields: Vec<MetadataField>,
+    pub steps: Vec<Normalization>,
+}
+
+impl NormalizationRule {
+    pub fn apply(&self, source_app: &str, track: &mut MediaTrack) {
+        if let Some(rule_app) = &self.source_app {
+            if !rule_app.eq_ignore_ascii_case(source_app) {
+                return;
+            }
+        }
+
+        for field in &self.fields {
+            let value = match field {
+                MetadataField::Title => &mut track.title,
+                MetadataField::Artist => &mut track.artist,
+                MetadataField::AlbumTitle => &mut track.album_title,
+            };
+            for step in &self.steps {
+                *value = step.apply(value);
+            }
+        }
+    }
+}
+
+fn normalize_unicode(value: &str) -> String {
+    value
+        .nfc()
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn smart_title_case(value: &str) -> String {
+    let has_lowercase = value.chars().any(char::is_lowercase);
+    let has_uppercase = value.chars().any(char::is_uppercase);
+    if has_lowercase && has_uppercase {
+        return value.to_string();
+    }
+
+    let words: Vec<_> = value.split(' ').collect();
+    let last = words.len() - 1;
+    words
+        .iter()
+        .enumerate()
+        .map(|(idx, word)| {
+            let word = word.to_lowercase();
+            if idx != 0 && idx != last && SMALL_WORDS.contains(&word.as_str()) {
+                word
+            } else {
+                capitalize(&word)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Uppercases the first letter of [word], skipping leading punctuation (e.g. "(live)").
+fn capitalize(word: &str) -> String {
+    match word.char_indices().find(|(_, c)| c.is_alphabetic()) {
+        Some((idx, c)) => {
+            let rest = &word[idx + c.len_utf8()..];
+            format!("{}{}{}", &word[..idx], c.to_uppercase(), rest)
+        }
+        None => word.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn unicode() {
+        // "e" followed by a combining acute accent
+        assert_eq!(Normalization::Unicode.apply("Cafe\u{301}"), "Caf\u{e9}");
+        assert_eq!(
+            Normalization::Unicode.apply("  Two   Spaces\t"),
+            "Two Spaces"
+        );
+    }
+
+    #[test]
+    fn title_case() {
+        assert_eq!(
+            Normalization::TitleCase.apply("THE SOUND OF SILENCE"),
+            "The Sound of Silence"
+        );
+        assert_eq!(
+            Normalization::TitleCase.apply("song (live at the park)"),
+            "Song (Live at the Park)"
+        );
+        assert_eq!(Normalization::TitleCase.apply("WHERE TO"), "Where To");
+        assert_eq!(Normalization::TitleCase.apply("ÄRGER"), "Ärger");
+        assert_eq!(Normalization::TitleCase.apply("iPhone Song"), "iPhone Song");
+        assert_eq!(Normalization::TitleCase.apply("1999"), "1999");
+    }
+
+    #[test]
+    fn featuring() {
+        assert_eq!(
+            Normalization::Featuring.apply("Song (ft. Someone)"),
+            "Song (feat. Someone)"
+        );
+        assert_eq!(
+            Normalization::Featuring.apply("Song Featuring Someone"),
+            "Song feat. Someone"
+        );
+        assert_eq!(
+            Normalization::Featuring.apply("Song (FEAT Someone)"),
+            "Song (feat. Someone)"
+        );
+        assert_eq!(
+            Normalization::Featuring.apply("Soft Left Feats"),
+            "Soft Left Feats"
+        );
+    }
+
+    #[test]
+    fn rule_steps_in_order() {
+        let rule = NormalizationRule {
+            source_app: Some("Chrome.exe".into()),
+            fields: vec![MetadataField::Title],
+            steps: vec![Normalization::TitleCase, Normalization::Featuring],
+        };
+        let mut track = MediaTrack {
+            title: "SONG FT. SOMEONE".into(),
+            artist: "ARTIST".into(),
+            album_title: "".into(),
+            album_cover: crate::service::AlbumCover::None,
+            length: 0,
+        };
+
+        rule.apply("spotify.exe", &mut track);
+        assert_eq!(track.title, "SONG FT. SOMEONE");
+
+        rule.apply("chrome.exe", &mut track);
+        assert_eq!(track.title, "Song feat. Someone");
+        assert_eq!(track.artist, "ARTIST");
+    }
+}
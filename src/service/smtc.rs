@@ -0,0 +1,379 @@
+use std::sync::Arc;
+
+use windows::{
+    core::Result as WinResult,
+    Foundation::{DateTime, TypedEventHandler},
+    Media::{
+        Control::{
+            GlobalSystemMediaTransportControlsSession,
+            GlobalSystemMediaTransportControlsSessionManager,
+            GlobalSystemMediaTransportControlsSessionPlaybackStatus,
+        },
+        MediaPlaybackAutoRepeatMode,
+    },
+    Storage::Streams::IRandomAccessStreamReference,
+};
+
+use crate::service::RepeatMode;
+
+/// Called from a thread of Windows whenever an event occurs.
+pub type EventHandler = Box<dyn Fn() + Send + Sync>;
+
+/// Events of a [SmtcSession], named like their WinRT counterparts.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SessionEvent {
+    MediaPropertiesChanged,
+    PlaybackInfoChanged,
+    TimelinePropertiesChanged,
+}
+
+/// Requests to the app of a [SmtcSession].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SessionRequest {
+    Play,
+    Pause,
+    SkipNext,
+    SkipPrevious,
+    /// Position in ticks (100ns)
+    ChangePlaybackPosition(i64),
+    ChangeShuffleActive(bool),
+    ChangeAutoRepeatMode(RepeatMode),
+}
+
+/// What a session is playing.
+#[derive(Clone, Default)]
+pub struct MediaProperties {
+    pub title: String,
+    pub artist: String,
+    pub album_title: String,
+    pub thumbnail: Option<IRandomAccessStreamReference>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PlaybackInfo {
+    pub is_playing: bool,
+    pub shuffle: bool,
+    pub repeat: RepeatMode,
+}
+
+/// The timeline of a session, all times in ticks (100ns).
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Timeline {
+    pub position: i64,
+    /// When the session last updated the timeline
+    pub last_updated: DateTime,
+    pub start_time: i64,
+    pub end_time: i64,
+    pub min_seek_time: i64,
+    pub max_seek_time: i64,
+}
+
+/// The media sessions of all apps connected to the media controls of Windows
+/// (System Media Transport Controls). Abstracted so [crate::service::WindowsMediaService]
+/// can be tested without a real player, see [fake].
+pub trait SmtcSessionManager: Send + Sync {
+    fn sessions(&self) -> WinResult<Vec<Arc<dyn SmtcSession>>>;
+    /// Returns a token to remove the [handler] with.
+    fn add_sessions_changed(&self, handler: EventHandler) -> WinResult<i64>;
+    fn remove_sessions_changed(&self, token: i64);
+}
+
+/// The media session of a single app, see [SmtcSessionManager].
+pub trait SmtcSession: Send + Sync {
+    /// Usually the name of the executable or the id of a packaged app
+    fn app_id(&self) -> WinResult<String>;
+    /// Blocks until the app provided them.
+    fn media_properties(&self) -> WinResult<MediaProperties>;
+    fn playback_info(&self) -> WinResult<PlaybackInfo>;
+    fn timeline(&self) -> WinResult<Timeline>;
+    /// Returns a token to remove the [handler] with.
+    fn add_handler(&self, event: SessionEvent, handler: EventHandler) -> WinResult<i64>;
+    fn remove_handler(&self, event: SessionEvent, token: i64);
+    /// Blocks until the app handled the [request], returns whether it succeeded.
+    fn request(&self, request: SessionRequest) -> WinResult<bool>;
+}
+
+impl SmtcSessionManager for GlobalSystemMediaTransportControlsSessionManager {
+    fn sessions(&self) -> WinResult<Vec<Arc<dyn SmtcSession>>> {
+        let sessions = self
+            .GetSessions()?
+            .into_iter()
+            .map(|session| Arc::new(session) as Arc<dyn SmtcSession>)
+            .collect();
+        Ok(sessions)
+    }
+
+    fn add_sessions_changed(&self, handler: EventHandler) -> WinResult<i64> {
+        self.SessionsChanged(&TypedEventHandler::new(move |_, _| {
+            handler();
+            Ok(())
+        }))
+    }
+
+    fn remove_sessions_changed(&self, token: i64) {
+        let _ = self.RemoveSessionsChanged(token);
+    }
+}
+
+fn convert_repeat_mode(mode: MediaPlaybackAutoRepeatMode) -> RepeatMode {
+    match mode {
+        MediaPlaybackAutoRepeatMode::Track => RepeatMode::Track,
+        MediaPlaybackAutoRepeatMode::List => RepeatMode::List,
+        _ => RepeatMode::Off,
+    }
+}
+
+impl SmtcSession for GlobalSystemMediaTransportControlsSession {
+    fn app_id(&self) -> WinResult<String> {
+        Ok(self.SourceAppUserModelId()?.to_string())
+    }
+
+    fn media_properties(&self) -> WinResult<MediaProperties> {
+        let props = self.TryGetMediaPropertiesAsync()?.get()?;
+        // Apps leave out whatever they don't know
+        Ok(MediaProperties {
+            title: props.Title().unwrap_or_default().to_string(),
+            artist: props.Artist().unwrap_or_default().to_string(),
+            album_title: props.AlbumTitle().unwrap_or_default().to_string(),
+            thumbnail: props.Thumbnail().ok(),
+        })
+    }
+
+    fn playback_info(&self) -> WinResult<PlaybackInfo> {
+        let playback = self.GetPlaybackInfo()?;
+        // Players not supporting shuffle or repeat don't report them at all
+        Ok(PlaybackInfo {
+            is_playing: playback.PlaybackStatus()?
+                == GlobalSystemMediaTransportControlsSessionPlaybackStatus::Playing,
+            shuffle: playback
+                .IsShuffleActive()
+                .and_then(|shuffle| shuffle.Value())
+                .unwrap_or_default(),
+            repeat: playback
+                .AutoRepeatMode()
+                .and_then(|mode| mode.Value())
+                .map(convert_repeat_mode)
+                .unwrap_or_default(),
+        })
+    }
+
+    fn timeline(&self) -> WinResult<Timeline> {
+        let timeline = self.GetTimelineProperties()?;
+        Ok(Timeline {
+            position: timeline.Position()?.Duration,
+            last_updated: timeline.LastUpdatedTime()?,
+            start_time: timeline.StartTime()?.Duration,
+            end_time: timeline.EndTime()?.Duration,
+            min_seek_time: timeline.MinSeekTime()?.Duration,
+            max_seek_time: timeline.MaxSeekTime()?.Duration,
+        })
+    }
+
+    fn add_handler(&self, event: SessionEvent, handler: EventHandler) -> WinResult<i64> {
+        match event {
+            SessionEvent::MediaPropertiesChanged => {
+                self.MediaPropertiesChanged(&TypedEventHandler::new(move |_, _| {
+                    handler();
+                    Ok(())
+                }))
+            }
+            SessionEvent::PlaybackInfoChanged => {
+                self.PlaybackInfoChanged(&TypedEventHandler::new(move |_, _| {
+                    handler();
+                    Ok(())
+                }))
+            }
+            SessionEvent::TimelinePropertiesChanged => {
+                self.TimelinePropertiesChanged(&TypedEventHandler::new(move |_, _| {
+                    handler();
+                    Ok(())
+                }))
+            }
+        }
+    }
+
+    fn remove_handler(&self, event: SessionEvent, token: i64) {
+        let _ = match event {
+            SessionEvent::MediaPropertiesChanged => self.RemoveMediaPropertiesChanged(token),
+            SessionEvent::PlaybackInfoChanged => self.RemovePlaybackInfoChanged(token),
+            SessionEvent::TimelinePropertiesChanged => self.RemoveTimelinePropertiesChanged(token),
+        };
+    }
+
+    fn request(&self, request: SessionRequest) -> WinResult<bool> {
+        let op = match request {
+            SessionRequest::Play => self.TryPlayAsync()?,
+            SessionRequest::Pause => self.TryPauseAsync()?,
+            SessionRequest::SkipNext => self.TrySkipNextAsync()?,
+            SessionRequest::SkipPrevious => self.TrySkipPreviousAsync()?,
+            SessionRequest::ChangePlaybackPosition(position) => {
+                self.TryChangePlaybackPositionAsync(position)?
+            }
+            SessionRequest::ChangeShuffleActive(shuffle) => {
+                self.TryChangeShuffleActiveAsync(shuffle)?
+            }
+            SessionRequest::ChangeAutoRepeatMode(repeat) => {
+                self.TryChangeAutoRepeatModeAsync(match repeat {
+                    RepeatMode::Off => MediaPlaybackAutoRepeatMode::None,
+                    RepeatMode::Track => MediaPlaybackAutoRepeatMode::Track,
+                    RepeatMode::List => MediaPlaybackAutoRepeatMode::List,
+                })?
+            }
+        };
+        op.get()
+    }
+}
+
+/// A session manager and sessions driven by tests instead of players.
+/// Events are raised right away, like Windows does from its own threads.
+#[cfg(test)]
+pub mod fake {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    /// Handlers by their token
+    type Handlers<E> = Mutex<Vec<(i64, E, EventHandler)>>;
+
+    fn add<E>(handlers: &Handlers<E>, event: E, handler: EventHandler) -> i64 {
+        let mut handlers = handlers.lock().unwrap();
+        let token = handlers.last().map_or(1, |(token, _, _)| token + 1);
+        handlers.push((token, event, handler));
+        token
+    }
+
+    fn raise<E: PartialEq>(handlers: &Handlers<E>, event: E) {
+        for (_, _, handler) in handlers.lock().unwrap().iter().filter(|h| h.1 == event) {
+            handler();
+        }
+    }
+
+    /// Clones share their sessions, so tests can keep one to script them.
+    #[derive(Clone, Default)]
+    pub struct FakeSessionManager {
+        sessions: Arc<Mutex<Vec<Arc<FakeSession>>>>,
+        handlers: Arc<Handlers<()>>,
+    }
+
+    impl FakeSessionManager {
+        /// Adds the [session] and raises SessionsChanged.
+        pub fn add_session(&self, session: Arc<FakeSession>) {
+            self.sessions.lock().unwrap().push(session);
+            self.sessions_changed();
+        }
+
+        /// Removes the sessions of [app_id] and raises SessionsChanged.
+        pub fn remove_session(&self, app_id: &str) {
+            self.sessions
+                .lock()
+                .unwrap()
+                .retain(|session| session.app_id != app_id);
+            self.sessions_changed();
+        }
+
+        /// Raises SessionsChanged without changing any session, as Windows sometimes does.
+        pub fn sessions_changed(&self) {
+            raise(&self.handlers, ());
+        }
+    }
+
+    impl SmtcSessionManager for FakeSessionManager {
+        fn sessions(&self) -> WinResult<Vec<Arc<dyn SmtcSession>>> {
+            let sessions = self.sessions.lock().unwrap();
+            Ok(sessions
+                .iter()
+                .map(|session| session.clone() as Arc<dyn SmtcSession>)
+                .collect())
+        }
+
+        fn add_sessions_changed(&self, handler: EventHandler) -> WinResult<i64> {
+            Ok(add(&self.handlers, (), handler))
+        }
+
+        fn remove_sessions_changed(&self, token: i64) {
+            self.handlers.lock().unwrap().retain(|h| h.0 != token);
+        }
+    }
+
+    pub struct FakeSession {
+        app_id: String,
+        media_properties: Mutex<MediaProperties>,
+        playback_info: Mutex<PlaybackInfo>,
+        timeline: Mutex<Timeline>,
+        handlers: Handlers<SessionEvent>,
+        /// Received requests, oldest first
+        pub requests: Mutex<Vec<SessionRequest>>,
+    }
+
+    impl FakeSession {
+        pub fn new(app_id: &str) -> Arc<Self> {
+            Arc::new(FakeSession {
+                app_id: app_id.to_string(),
+                media_properties: Mutex::default(),
+                playback_info: Mutex::default(),
+                timeline: Mutex::default(),
+                handlers: Mutex::default(),
+                requests: Mutex::default(),
+            })
+        }
+
+        /// Starts a track [length] seconds long
+        /// and raises MediaPropertiesChanged and TimelinePropertiesChanged.
+        pub fn set_track(&self, title: &str, artist: &str, length: i64) {
+            *self.media_properties.lock().unwrap() = MediaProperties {
+                title: title.to_string(),
+                artist: artist.to_string(),
+                ..Default::default()
+            };
+            *self.timeline.lock().unwrap() = Timeline {
+                end_time: length * 10_000_000,
+                max_seek_time: length * 10_000_000,
+                ..Default::default()
+            };
+            raise(&self.handlers, SessionEvent::MediaPropertiesChanged);
+            raise(&self.handlers, SessionEvent::TimelinePropertiesChanged);
+        }
+
+        /// Raises PlaybackInfoChanged.
+        pub fn set_playing(&self, is_playing: bool) {
+            self.playback_info.lock().unwrap().is_playing = is_playing;
+            raise(&self.handlers, SessionEvent::PlaybackInfoChanged);
+        }
+
+        /// Number of handlers added and not removed yet.
+        pub fn handler_count(&self) -> usize {
+            self.handlers.lock().unwrap().len()
+        }
+    }
+
+    impl SmtcSession for FakeSession {
+        fn app_id(&self) -> WinResult<String> {
+            Ok(self.app_id.clone())
+        }
+
+        fn media_properties(&self) -> WinResult<MediaProperties> {
+            Ok(self.media_properties.lock().unwrap().clone())
+        }
+
+        fn playback_info(&self) -> WinResult<PlaybackInfo> {
+            Ok(*self.playback_info.lock().unwrap())
+        }
+
+        fn timeline(&self) -> WinResult<Timeline> {
+            Ok(*self.timeline.lock().unwrap())
+        }
+
+        fn add_handler(&self, event: SessionEvent, handler: EventHandler) -> WinResult<i64> {
+            Ok(add(&self.handlers, event, handler))
+        }
+
+        fn remove_handler(&self, _: SessionEvent, token: i64) {
+            self.handlers.lock().unwrap().retain(|h| h.0 != token);
+        }
+
+        fn request(&self, request: SessionRequest) -> WinResult<bool> {
+            self.requests.lock().unwrap().push(request);
+            Ok(true)
+        }
+    }
+}
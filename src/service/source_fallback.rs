@@ -0,0 +1,120 @@
+use std::sync::Arc;
+
+use crate::{
+    on_settings_changed,
+    service::{BaseService, SharedMediaService},
+    settings::SpotickAppSettings,
+};
+
+/// Returns the index of the session to follow out of [sessions] (app id and whether it's playing).
+/// [priorities] are the app ids to consider, most preferred first.
+/// The most preferred playing app wins. Without any playing app, the [current] app is kept
+/// while its session exists and it is still a priority, so pausing doesn't jump to another app.
+/// App ids are compared case-insensitive.
+#[cfg(windows)]
+pub fn select_source_session(
+    sessions: &[(String, bool)],
+    priorities: &[&str],
+    current: Option<&str>,
+) -> Option<usize> {
+    let find = |app_id: &str, playing_only: bool| {
+        sessions.iter().position(|(session_app_id, is_playing)| {
+            session_app_id.eq_ignore_ascii_case(app_id) && (*is_playing || !playing_only)
+        })
+    };
+
+    let current = current.filter(|current| {
+        priorities
+            .iter()
+            .any(|app_id| app_id.eq_ignore_ascii_case(current))
+    });
+    priorities
+        .iter()
+        .find_map(|app_id| find(app_id, true))
+        .or_else(|| current.and_then(|app_id| find(app_id, false)))
+        .or_else(|| priorities.iter().find_map(|app_id| find(app_id, false)))
+}
+
+/// Keeps the fallback apps of the [media_service] in sync with the [settings].
+pub async fn register_fallback_apps_changed(
+    settings: SpotickAppSettings,
+    media_service: SharedMediaService,
+) {
+    let media_service = Arc::downgrade(&media_service);
+    let mut applied_apps = None;
+    on_settings_changed!(settings, |settings| {
+        let apps = settings.fallback_apps.clone().unwrap_or_default();
+        if applied_apps.as_ref() != Some(&apps) {
+            if let Some(media_service) = media_service.upgrade() {
+                let res = media_service
+                    .write()
+                    .await
+                    .set_fallback_app_ids(apps.clone());
+                if let Err(e) = res {
+                    log::error!("Could not apply fallback apps: {}", e);
+                }
+            }
+            applied_apps = Some(apps);
+        }
+    });
+}
+
+#[cfg(all(test, windows))]
+mod test {
+    use super::*;
+
+    fn sessions(sessions: &[(&str, bool)]) -> Vec<(String, bool)> {
+        sessions
+            .iter()
+            .map(|(app_id, is_playing)| (app_id.to_string(), *is_playing))
+            .collect()
+    }
+
+    #[test]
+    fn prefers_playing_apps_by_priority() {
+        let priorities = ["spotify.exe", "chrome.exe"];
+        let both_playing = sessions(&[("chrome.exe", true), ("Spotify.exe", true)]);
+        assert_eq!(
+            select_source_session(&both_playing, &priorities, Some("chrome.exe")),
+            Some(1)
+        );
+
+        let browser_playing = sessions(&[("chrome.exe", true), ("Spotify.exe", false)]);
+        assert_eq!(
+            select_source_session(&browser_playing, &priorities, Some("spotify.exe")),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn keeps_current_app_while_paused() {
+        let priorities = ["spotify.exe", "chrome.exe"];
+        let paused = sessions(&[("Spotify.exe", false), ("chrome.exe", false)]);
+        assert_eq!(
+            select_source_session(&paused, &priorities, Some("chrome.exe")),
+            Some(1)
+        );
+        assert_eq!(select_source_session(&paused, &priorities, None), Some(0));
+        assert_eq!(
+            select_source_session(&paused, &["CHROME.EXE"], None),
+            Some(1)
+        );
+        // The current app is gone or no fallback anymore
+        assert_eq!(
+            select_source_session(&paused, &priorities, Some("vlc.exe")),
+            Some(0)
+        );
+        assert_eq!(
+            select_source_session(&paused, &priorities[..1], Some("chrome.exe")),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn ignores_other_apps() {
+        let priorities = ["spotify.exe"];
+        let others = sessions(&[("msedge.exe", true)]);
+        assert_eq!(select_source_session(&others, &priorities, None), None);
+        assert_eq!(select_source_session(&[], &priorities, None), None);
+    }
+}
@@ -0,0 +1,151 @@
+use std::{ffi::c_void, process::Command};
+
+use anyhow::Result;
+use windows::{
+    core::Result as WinResult,
+    Win32::{
+        Foundation::{CloseHandle, HANDLE, INVALID_HANDLE_VALUE, WAIT_OBJECT_0},
+        System::Threading::{
+            GetExitCodeProcess, OpenProcess, RegisterWaitForSingleObject, UnregisterWaitEx,
+            WaitForSingleObject, INFINITE, PROCESS_QUERY_LIMITED_INFORMATION, PROCESS_SYNCHRONIZE,
+            WT_EXECUTEONLYONCE,
+        },
+    },
+};
+
+use crate::service::CrashedApp;
+
+type ExitCallback = Box<dyn Fn() + Send + Sync>;
+
+/// The process of the source app, opened to notice it ending (e.g. by crashing).
+pub struct SourceProcess {
+    /// Path of the executable
+    pub image_path: String,
+    handle: HANDLE,
+    /// Wait of the thread pool for the process to end
+    wait: HANDLE,
+    on_exit: *mut ExitCallback,
+}
+
+// The handles are only waited on and closed, and the callback is Sync,
+// which is fine from any thread
+unsafe impl Send for SourceProcess {}
+unsafe impl Sync for SourceProcess {}
+
+unsafe extern "system" fn process_exited(on_exit: *mut c_void, _timed_out: bool) {
+    (*(on_exit as *const ExitCallback))();
+}
+
+impl SourceProcess {
+    /// Opens the process [pid] and calls [on_exit] once it ended,
+    /// from a thread of the Windows thread pool. See [SourceProcess::exit_code].
+    pub fn open(
+        pid: u32,
+        image_path: String,
+        on_exit: impl Fn() + Send + Sync + 'static,
+    ) -> WinResult<Self> {
+        let handle = unsafe {
+            OpenProcess(
+                PROCESS_SYNCHRONIZE | PROCESS_QUERY_LIMITED_INFORMATION,
+                false,
+                pid,
+            )?
+        };
+        let on_exit: ExitCallback = Box::new(on_exit);
+        let mut process = SourceProcess {
+            image_path,
+            handle,
+            wait: HANDLE::default(),
+            on_exit: Box::into_raw(Box::new(on_exit)),
+        };
+        unsafe {
+            RegisterWaitForSingleObject(
+                &mut process.wait,
+                process.handle,
+                Some(process_exited),
+                Some(process.on_exit as *const c_void),
+                INFINITE,
+                WT_EXECUTEONLYONCE,
+            )?;
+        }
+        Ok(process)
+    }
+
+    /// The exit code of the process, [None] while it is still running.
+    pub fn exit_code(&self) -> Option<u32> {
+        unsafe {
+            if WaitForSingleObject(self.handle, 0) != WAIT_OBJECT_0 {
+                return None;
+            }
+            let mut exit_code = 0;
+            GetExitCodeProcess(self.handle, &mut exit_code).ok()?;
+            Some(exit_code)
+        }
+    }
+}
+
+impl Drop for SourceProcess {
+    fn drop(&mut self) {
+        unsafe {
+            // Waits for a running callback, so it can be freed afterwards
+            if !self.wait.is_invalid() {
+                let _ = UnregisterWaitEx(self.wait, Some(INVALID_HANDLE_VALUE));
+            }
+            drop(Box::from_raw(self.on_exit));
+            let _ = CloseHandle(self.handle);
+        }
+    }
+}
+
+/// Packaged apps (e.g. from the Microsoft Store) can't be started by their executable,
+/// so they are started by their app id through the shell instead.
+fn restart_command(app: &CrashedApp) -> Command {
+    if app.app_id.contains('!') {
+        let mut command = Command::new("explorer.exe");
+        command.arg(format!("shell:AppsFolder\\{}", app.app_id));
+        command
+    } else {
+        Command::new(&app.image_path)
+    }
+}
+
+/// Starts the [app] again after it crashed.
+/// In a [dry_run], the command is only logged.
+pub fn restart_app(app: &CrashedApp, dry_run: bool) -> Result<()> {
+    let mut command = restart_command(app);
+    if dry_run {
+        log::info!("Would restart {} with {:?}", app.app_id, command);
+        return Ok(());
+    }
+    command.spawn()?;
+    log::info!("Restarted {}", app.app_id);
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn restarts_packaged_apps_by_id() {
+        let app = |app_id: &str| CrashedApp {
+            app_id: app_id.to_string(),
+            image_path: "C:\\Program Files\\Player\\player.exe".to_string(),
+            exit_code: 0xc0000005,
+        };
+
+        let command = restart_command(&app("player.exe"));
+        assert_eq!(
+            command.get_program(),
+            "C:\\Program Files\\Player\\player.exe"
+        );
+        assert_eq!(command.get_args().count(), 0);
+
+        let command = restart_command(&app("spotifyab.spotifymusic_zpdnekdrzrea0!spotify"));
+        assert_eq!(command.get_program(), "explorer.exe");
+        assert_eq!(
+            command.get_args().collect::<Vec<_>>(),
+            ["shell:AppsFolder\\spotifyab.spotifymusic_zpdnekdrzrea0!spotify"]
+        );
+    }
+}
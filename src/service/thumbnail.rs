@@ -0,0 +1,147 @@
+use std::{io::Cursor, time::Duration};
+
+use image::{ImageError, ImageReader, RgbaImage};
+
+#[derive(thiserror::Error, Debug)]
+pub enum ThumbnailError {
+    #[cfg(windows)]
+    #[error("Thumbnail is not readable")]
+    NotReadable,
+    #[error("Unsupported thumbnail format: {0}")]
+    UnsupportedFormat(String),
+    #[error("Could not decode thumbnail: {0}")]
+    Decode(ImageError),
+    #[error("Thumbnail is too large ({0} bytes)")]
+    TooLarge(u64),
+    #[error("Reading the thumbnail timed out")]
+    Timeout,
+    #[cfg(windows)]
+    #[error("WinRT error: {0}")]
+    WinRt(#[from] windows::core::Error),
+    #[cfg(target_os = "linux")]
+    #[error("HTTP error: {0}")]
+    Http(Box<ureq::Error>),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+/// Bounds for reading a thumbnail, so a misbehaving source
+/// (e.g. reporting a huge size or never finishing a read) can't stall the service.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ThumbnailLimits {
+    /// Bytes
+    pub max_size: u64,
+    pub timeout: Duration,
+}
+
+impl Default for ThumbnailLimits {
+    fn default() -> Self {
+        ThumbnailLimits {
+            max_size: 10 * 1024 * 1024,
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+impl ThumbnailLimits {
+    /// Creates limits from the settings, using the defaults for unset values.
+    /// [max_size_kib] is in KiB and [timeout_ms] in milliseconds.
+    pub fn from_settings(max_size_kib: Option<u64>, timeout_ms: Option<u64>) -> Self {
+        let default = ThumbnailLimits::default();
+        ThumbnailLimits {
+            max_size: max_size_kib.map_or(default.max_size, |kib| kib * 1024),
+            timeout: timeout_ms.map_or(default.timeout, Duration::from_millis),
+        }
+    }
+}
+
+/// Decodes an encoded thumbnail (PNG, JPEG, BMP or WebP).
+/// The format is guessed from the content of the [buffer].
+/// [content_type] as reported by the source is only used for error reporting.
+pub fn decode_thumbnail(buffer: &[u8], content_type: &str) -> Result<RgbaImage, ThumbnailError> {
+    let reader = ImageReader::new(Cursor::new(buffer)).with_guessed_format()?;
+    let format = reader.format();
+    match reader.decode() {
+        Ok(img) => Ok(img.to_rgba8()),
+        Err(ImageError::Unsupported(_)) => {
+            let format = match format {
+                Some(format) => format!("{:?} ({})", format, content_type),
+                None => content_type.to_string(),
+            };
+            Err(ThumbnailError::UnsupportedFormat(format))
+        }
+        Err(e) => Err(ThumbnailError::Decode(e)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use image::{DynamicImage, ImageFormat, Rgba};
+
+    use super::*;
+
+    fn encode(format: ImageFormat) -> Vec<u8> {
+        let img = RgbaImage::from_pixel(4, 4, Rgba([30, 215, 96, 255]));
+        let mut buffer = Cursor::new(Vec::new());
+        let res = match format {
+            // JPEG has no alpha channel
+            ImageFormat::Jpeg => DynamicImage::ImageRgba8(img)
+                .to_rgb8()
+                .write_to(&mut buffer, format),
+            _ => img.write_to(&mut buffer, format),
+        };
+        res.expect("Could not encode test image");
+        buffer.into_inner()
+    }
+
+    #[test]
+    fn decodes_supported_formats() {
+        for format in [
+            ImageFormat::Png,
+            ImageFormat::Jpeg,
+            ImageFormat::Bmp,
+            ImageFormat::WebP,
+        ] {
+            let img = decode_thumbnail(&encode(format), "image/test")
+                .unwrap_or_else(|e| panic!("Could not decode {:?}: {}", format, e));
+            assert_eq!(img.dimensions(), (4, 4));
+        }
+    }
+
+    #[test]
+    fn limits_from_settings() {
+        let limits = ThumbnailLimits::from_settings(Some(512), None);
+        assert_eq!(limits.max_size, 512 * 1024);
+        assert_eq!(limits.timeout, ThumbnailLimits::default().timeout);
+        assert_eq!(
+            ThumbnailLimits::from_settings(None, Some(250)).timeout,
+            Duration::from_millis(250)
+        );
+    }
+
+    #[test]
+    fn avif_is_unsupported() {
+        let avif = b"\0\0\0\x1cftypavif\0\0\0\0avifmif1miaf";
+        assert!(matches!(
+            decode_thumbnail(avif, "image/avif"),
+            Err(ThumbnailError::UnsupportedFormat(_))
+        ));
+    }
+
+    #[test]
+    fn unknown_format_is_unsupported() {
+        assert!(matches!(
+            decode_thumbnail(b"definitely not an image", "application/octet-stream"),
+            Err(ThumbnailError::UnsupportedFormat(_))
+        ));
+    }
+
+    #[test]
+    fn truncated_image_fails_to_decode() {
+        let png = encode(ImageFormat::Png);
+        assert!(matches!(
+            decode_thumbnail(&png[..png.len() / 2], "image/png"),
+            Err(ThumbnailError::Decode(_))
+        ));
+    }
+}
@@ -0,0 +1,156 @@
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+/// Titles at least this similar (0 - 1) after normalization are considered the same.
+const MIN_TITLE_SIMILARITY: f64 = 0.8;
+/// Seconds the lengths of the same track may differ by, as players round differently.
+const LENGTH_TOLERANCE: u64 = 3;
+
+/// Parts of titles which browsers (e.g. YouTube) add or update while the track plays.
+static TITLE_NOISE: LazyLock<[Regex; 4]> = LazyLock::new(|| {
+    [
+        // Unread notifications (e.g. "(3) Title")
+        Regex::new(r"^\(\d+\)\s*").unwrap(),
+        // Name of the site
+        Regex::new(r"\s*[-–|]\s*youtube(?: music)?\s*$").unwrap(),
+        // View counts (e.g. "1.2M views")
+        Regex::new(r"\b\d[\d.,]*\s*[kmb]?\s*views?\b").unwrap(),
+        // Kind of video (e.g. "(Official Music Video)" or "[Lyrics]")
+        Regex::new(concat!(
+            r"[(\[][^)\]]*",
+            r"\b(?:official|video|audio|lyrics?|visuali[sz]er|hd|hq|4k|mv)\b",
+            r"[^)\]]*[)\]]",
+        ))
+        .unwrap(),
+    ]
+});
+
+/// Tells tracks apart despite sloppy metadata, which is common for browser sources:
+/// The title of one video may change while it plays (e.g. its view count), but it is still
+/// the same track. Used wherever tracks are deduplicated (e.g. the history).
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrackIdentity {
+    title: String,
+    artist: String,
+    /// Seconds, 0 if unknown
+    length: u64,
+}
+
+impl TrackIdentity {
+    pub fn new(title: &str, artist: &str, length: u64) -> Self {
+        TrackIdentity {
+            title: normalize(title, true),
+            artist: normalize(artist, false),
+            length,
+        }
+    }
+
+    /// Whether both are likely the same track: The artists are the same, the titles are
+    /// similar apart from noise and contain the same numbers (e.g. "Part 2"),
+    /// and the lengths (if known) are about the same.
+    pub fn matches(&self, other: &TrackIdentity) -> bool {
+        if self.length > 0
+            && other.length > 0
+            && self.length.abs_diff(other.length) > LENGTH_TOLERANCE
+        {
+            return false;
+        }
+        self.artist == other.artist
+            && numbers(&self.title).eq(numbers(&other.title))
+            && similarity(&self.title, &other.title) >= MIN_TITLE_SIMILARITY
+    }
+
+    /// The normalized title and artist, for grouping tracks which match exactly.
+    pub fn key(&self) -> (&str, &str) {
+        (&self.title, &self.artist)
+    }
+}
+
+/// Lowercases [value] and reduces it to words (of any script) and numbers.
+/// Strips the [TITLE_NOISE] off [titles](TrackIdentity::title).
+fn normalize(value: &str, title: bool) -> String {
+    let mut value = value.to_lowercase();
+    if title {
+        for noise in TITLE_NOISE.iter() {
+            value = noise.replace_all(&value, " ").into_owned();
+        }
+    }
+    value
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn numbers(value: &str) -> impl Iterator<Item = &str> {
+    value
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|number| !number.is_empty())
+}
+
+/// Similarity (0 - 1) of [a] and [b] by their edit distance.
+fn similarity(a: &str, b: &str) -> f64 {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let max_len = a.len().max(b.len());
+    if max_len == 0 {
+        return 1.0;
+    }
+
+    // Levenshtein distance, keeping only the previous row
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != b_char);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    1.0 - previous[b.len()] as f64 / max_len as f64
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ignores_browser_noise() {
+        let video = TrackIdentity::new(
+            "Rick Astley - Never Gonna Give You Up (Official Music Video)",
+            "Rick Astley",
+            213,
+        );
+        for title in [
+            "(2) Rick Astley - Never Gonna Give You Up (Official Music Video) - YouTube",
+            "Rick Astley - Never Gonna Give You Up • 1.5B views",
+            "Rick Astley – Never Gonna Give You Up [HD]",
+            // Typo fixed mid-track
+            "Rick Astley - Never Gona Give You Up",
+        ] {
+            let other = TrackIdentity::new(title, "Rick Astley", 212);
+            assert!(video.matches(&other), "{:?} should match", title);
+        }
+        assert_eq!(
+            video.key(),
+            ("rick astley never gonna give you up", "rick astley")
+        );
+    }
+
+    #[test]
+    fn tells_different_tracks_apart() {
+        let track = TrackIdentity::new("Symphony No. 5 - Part 1", "Orchestra", 0);
+        assert!(!track.matches(&TrackIdentity::new(
+            "Symphony No. 5 - Part 2",
+            "Orchestra",
+            0
+        )));
+        assert!(!track.matches(&TrackIdentity::new("Symphony No. 5 - Part 1", "Other", 0)));
+        assert!(!track.matches(&TrackIdentity::new("Nocturne", "Orchestra", 0)));
+
+        let with_length = TrackIdentity::new("Intro", "", 90);
+        assert!(with_length.matches(&TrackIdentity::new("Intro", "", 0)));
+        assert!(!with_length.matches(&TrackIdentity::new("Intro", "", 240)));
+    }
+}
@@ -1,66 +1,160 @@
 use std::{
-    io::Cursor,
     num::NonZero,
     sync::{Arc, Weak},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
-use anyhow::{ensure, Result};
-use image::ImageReader;
-use tokio::sync::{
-    broadcast::{channel, Receiver, Sender},
-    RwLock,
+use anyhow::Result;
+use image::RgbaImage;
+use tokio::{
+    sync::{
+        broadcast::{channel, Receiver, Sender},
+        RwLock,
+    },
+    task::JoinHandle,
+    time::Instant,
 };
+use tokio_util::sync::CancellationToken;
 use windows::{
-    core::{Result as WinResult, HSTRING},
-    Foundation::TypedEventHandler,
-    Media::Control::{
-        GlobalSystemMediaTransportControlsSession, GlobalSystemMediaTransportControlsSessionManager,
-    },
+    core::{AgileReference, Result as WinResult, RuntimeType, HSTRING},
+    ApplicationModel::AppInfo,
+    Foundation::DateTime,
+    Media::Control::GlobalSystemMediaTransportControlsSessionManager,
     Storage::Streams::{DataReader, IRandomAccessStreamReference, InputStreamOptions},
 };
+use windows_future::IAsyncOperation;
 
 use crate::service::{
+    audio_session::{
+        find_app_process, get_app_volume, set_app_volume, watch_app_volume, AppVolumeWatcher,
+    },
+    decode_thumbnail,
     media_service::{
-        AlbumCover, MediaService, MediaServiceError, MediaTrack, PlaybackChangedEvent,
-        PlaybackState,
+        AlbumCover, CrashedApp, MediaService, MediaServiceError, MediaSessionInfo, MediaTrack,
+        PlaybackChangedEvent, PlaybackState, RepeatMode,
     },
-    BaseService,
+    smtc::{EventHandler, SessionEvent, SessionRequest, SmtcSession, SmtcSessionManager, Timeline},
+    source_fallback::select_source_session,
+    source_process::SourceProcess,
+    Activity, ActivityLog, BaseService, MetadataRules, ThumbnailError, ThumbnailLimits,
 };
 
 type WinRtHandle = Option<NonZero<i64>>;
+type Session = Arc<dyn SmtcSession>;
+
+/// Covers are read in chunks of this many bytes.
+const THUMBNAIL_CHUNK_SIZE: u32 = 64 * 1024;
 
 /// A media service observing one running application connected to
 /// the media controls of the windows runtime (winrt).
-/// NOTE: The winrt media API doesn't support individual media volume
-/// (i.e. getting or requesting the monitored app to change its volume).
-/// Seeking into and reporting the playback position is currently a Todo.
+/// With fallback apps, it follows whichever of these apps is playing.
+/// NOTE: The winrt media API doesn't support individual media volume,
+/// so the volume is read and changed through the app's audio session instead.
 pub struct WindowsMediaService {
     self_ref: Weak<RwLock<WindowsMediaService>>,
-    manager: GlobalSystemMediaTransportControlsSessionManager,
+    manager: Box<dyn SmtcSessionManager>,
     sessions_changed_handler: WinRtHandle,
     source_app_id: String,
-    media_properties_changed_handler: WinRtHandle,
-    media_playback_changed_handler: WinRtHandle,
-    source_session: Option<GlobalSystemMediaTransportControlsSession>,
+    fallback_app_ids: Vec<String>,
+    /// App of the [WindowsMediaService::source_session]
+    active_app_id: String,
+    /// Handlers of the events of the [WindowsMediaService::source_session]
+    source_session_handlers: Vec<(SessionEvent, i64)>,
+    source_session: Option<Session>,
+    /// Sessions of the source and fallback apps watched for starting to play,
+    /// see [WindowsMediaService::watch_fallback_sessions]
+    fallback_sessions: Vec<(Session, i64)>,
     current_track: Option<MediaTrack>,
     playback_state: PlaybackState,
+    timeline: Option<TimelineAnchor>,
+    progress_ticker: Option<JoinHandle<()>>,
     event_sender: Sender<PlaybackChangedEvent>,
+    activity_log: ActivityLog,
+    metadata_rules: MetadataRules,
+    thumbnail_limits: ThumbnailLimits,
+    /// Cancels loading the cover of the current track
+    thumbnail_loading: Option<CancellationToken>,
+    /// Process of the [WindowsMediaService::source_session], once found
+    source_process: Option<SourceProcess>,
+    /// Audio sessions of the [WindowsMediaService::source_session], once found
+    volume_watcher: Option<AppVolumeWatcher>,
 }
 
-fn unwrap_hstring(hstring: WinResult<HSTRING>, default: impl Into<String>) -> String {
-    hstring
-        .ok()
-        .and_then(|s| {
-            if !s.is_empty() {
-                Some(s.to_string())
-            } else {
-                None
-            }
-        })
-        .unwrap_or_else(|| {
-            log::warn!("Could not retrieve HSTRING");
-            default.into()
-        })
+/// Sessions leave out the properties their app doesn't know.
+fn non_empty(value: String, default: impl Into<String>) -> String {
+    if value.is_empty() {
+        log::warn!("Session left out a media property");
+        default.into()
+    } else {
+        value
+    }
+}
+
+/// The playback position reported by a session at a certain time.
+/// Sessions only update their timeline sporadically (e.g. on seeks or
+/// track changes), so we interpolate the position in between.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct TimelineAnchor {
+    position: Duration,
+    updated: SystemTime,
+    /// When the session last reported its timeline, see [TimelineAnchor::rebased]
+    reported: SystemTime,
+}
+
+impl TimelineAnchor {
+    /// Estimates the playback position at [now].
+    fn position_at(&self, now: SystemTime, is_playing: bool) -> Duration {
+        if !is_playing {
+            return self.position;
+        }
+        self.position + now.duration_since(self.updated).unwrap_or_default()
+    }
+
+    /// Moves the anchor to [now] when the playback starts or pauses,
+    /// since not every player updates its timeline then.
+    /// Prefers the timeline [reported] by a pausing player if it is newer,
+    /// as it has the exact position while the interpolated one drifts.
+    fn rebased(
+        &self,
+        now: SystemTime,
+        was_playing: bool,
+        reported: Option<TimelineAnchor>,
+    ) -> TimelineAnchor {
+        match reported {
+            Some(reported) if reported.reported > self.reported => reported,
+            _ => TimelineAnchor {
+                position: self.position_at(now, was_playing),
+                updated: now,
+                reported: self.reported,
+            },
+        }
+    }
+}
+
+fn read_timeline(session: &dyn SmtcSession) -> WinResult<TimelineAnchor> {
+    let timeline = session.timeline()?;
+    let updated = convert_date_time(timeline.last_updated);
+    Ok(TimelineAnchor {
+        position: convert_ticks_to_duration(timeline.position),
+        updated,
+        reported: updated,
+    })
+}
+
+/// Ticks (100ns) between the windows epoch (1601-01-01) and the unix epoch.
+const UNIX_EPOCH_TICKS: i64 = 116_444_736_000_000_000;
+
+fn convert_ticks_to_duration(ticks: i64) -> Duration {
+    Duration::from_nanos(ticks.max(0) as u64 * 100)
+}
+
+fn convert_date_time(date_time: DateTime) -> SystemTime {
+    let ticks = date_time.UniversalTime - UNIX_EPOCH_TICKS;
+    if ticks < 0 {
+        UNIX_EPOCH - convert_ticks_to_duration(-ticks)
+    } else {
+        UNIX_EPOCH + convert_ticks_to_duration(ticks)
+    }
 }
 
 fn convert_ticks_to_seconds(ticks: i64) -> u64 {
@@ -71,27 +165,142 @@ fn convert_ticks_to_seconds(ticks: i64) -> u64 {
     ticks as u64 / 10_000_000
 }
 
-macro_rules! register_winrt_event {
-    ($self:ident, $src:expr, $ev:ident, |$srv:ident|$handler:block) => {{
-        $src.$ev(&TypedEventHandler::new({
-            let srv = $self.clone();
-            let rt_handle = tokio::runtime::Handle::current();
-            move |_, __| {
-                let srv = srv.clone();
-                rt_handle.spawn(async move {
-                    log::info!(stringify!($ev));
-                    if let Some($srv) = srv.upgrade() {
-                        let res: Result<(), MediaServiceError> = $handler;
-                        if let Err(e) = res {
-                            log::error!("WinRt handler failed: {:?}", e);
-                        }
-                    } else {
-                        log::error!("Could not get service in winrt handler!");
+/// Determines the track length in seconds from the [timeline] of a session.
+/// Not every player populates MaxSeekTime (e.g. foobar2000 and browsers),
+/// so we fall back to EndTime - StartTime.
+fn get_track_length(timeline: &Timeline) -> u64 {
+    let max_seek_time = convert_ticks_to_seconds(timeline.max_seek_time);
+    if max_seek_time > 0 {
+        log::debug!("Track length from MaxSeekTime: {}s", max_seek_time);
+        return max_seek_time;
+    }
+
+    let length = convert_ticks_to_seconds(timeline.end_time - timeline.start_time);
+    log::debug!("Track length from EndTime - StartTime: {}s", length);
+    length
+}
+
+/// Converts [playback_percent] of the seekable range of a [timeline]
+/// into the position (in ticks) to seek to.
+/// Falls back to EndTime if the player doesn't report a MaxSeekTime.
+fn get_seek_position(timeline: &Timeline, playback_percent: u32) -> i64 {
+    let mut max_seek_time = timeline.max_seek_time;
+    if max_seek_time <= timeline.min_seek_time {
+        max_seek_time = timeline.end_time;
+    }
+    percent_to_ticks(timeline.min_seek_time, max_seek_time, playback_percent)
+}
+
+fn percent_to_ticks(min_ticks: i64, max_ticks: i64, percent: u32) -> i64 {
+    let range = (max_ticks - min_ticks).max(0);
+    min_ticks + range / 100 * percent.min(100) as i64
+}
+
+/// Resolves a human readable name for the [app_id] of a media session.
+/// Packaged apps (e.g. "SpotifyAB.SpotifyMusic_zpdnekdrzrea0!Spotify") are resolved
+/// using their package manifest. Any other app id is treated as an executable name.
+fn resolve_app_name(app_id: &str) -> String {
+    let display_name = AppInfo::GetFromAppUserModelId(&HSTRING::from(app_id))
+        .and_then(|info| info.DisplayInfo()?.DisplayName());
+    match display_name {
+        Ok(name) if !name.is_empty() => name.to_string(),
+        _ => exe_display_name(app_id),
+    }
+}
+
+/// Turns an executable name or path into a display name ("spotify.exe" -> "Spotify").
+fn exe_display_name(app_id: &str) -> String {
+    let name = app_id.rsplit(['\\', '/']).next().unwrap_or(app_id);
+    let name = if name.to_ascii_lowercase().ends_with(".exe") {
+        &name[..name.len() - 4]
+    } else {
+        name
+    };
+
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => app_id.to_string(),
+    }
+}
+
+/// Awaits the WinRT [op], cancelling it if the future is dropped before (e.g. on timeout).
+async fn cancel_on_drop<T: RuntimeType + 'static>(op: IAsyncOperation<T>) -> WinResult<T> {
+    struct CancelGuard<T: RuntimeType + 'static>(IAsyncOperation<T>);
+    impl<T: RuntimeType + 'static> Drop for CancelGuard<T> {
+        fn drop(&mut self) {
+            // Has no effect on completed operations
+            let _ = self.0.Cancel();
+        }
+    }
+
+    let _guard = CancelGuard(op.clone());
+    op.await
+}
+
+/// Reads and decodes the thumbnail [stream] chunk by chunk.
+/// Fails once the thumbnail exceeds [max_size] bytes, no matter the size reported by the source.
+async fn read_thumbnail(
+    stream: IRandomAccessStreamReference,
+    max_size: u64,
+) -> Result<RgbaImage, ThumbnailError> {
+    let stream_handle = cancel_on_drop(stream.OpenReadAsync()?).await?;
+    if !stream_handle.CanRead()? {
+        return Err(ThumbnailError::NotReadable);
+    }
+
+    let reported_size = stream_handle.Size()?;
+    let content_type = stream_handle.ContentType()?.to_string();
+    log::info!(
+        "Media thumbnail content-type: {}, Size: {}",
+        &content_type,
+        reported_size
+    );
+    if reported_size > max_size {
+        return Err(ThumbnailError::TooLarge(reported_size));
+    }
+
+    let buf_reader = DataReader::CreateDataReader(&stream_handle)?;
+    buf_reader.SetInputStreamOptions(InputStreamOptions::Partial)?;
+    let mut buffer: Vec<u8> = Vec::with_capacity(reported_size as usize);
+    loop {
+        let loaded = cancel_on_drop(buf_reader.LoadAsync(THUMBNAIL_CHUNK_SIZE)?).await?;
+        if loaded == 0 {
+            break;
+        }
+        let start = buffer.len();
+        buffer.resize(start + loaded as usize, 0); // DataReader needs length == loaded bytes
+        buf_reader.ReadBytes(&mut buffer[start..])?;
+        if buffer.len() as u64 > max_size {
+            return Err(ThumbnailError::TooLarge(buffer.len() as u64));
+        }
+    }
+
+    decode_thumbnail(&buffer, &content_type)
+}
+
+/// Creates an [EventHandler] running [handler] with the service on the runtime,
+/// as events are raised from threads of Windows.
+macro_rules! event_handler {
+    ($self:ident, $ev:expr, |$srv:ident|$handler:block) => {{
+        let srv = $self.clone();
+        let rt_handle = tokio::runtime::Handle::current();
+        let handler: EventHandler = Box::new(move || {
+            let srv = srv.clone();
+            rt_handle.spawn(async move {
+                log::info!("{:?}", $ev);
+                if let Some($srv) = srv.upgrade() {
+                    let res: Result<(), MediaServiceError> = $handler;
+                    if let Err(e) = res {
+                        log::error!("WinRt handler failed: {:?}", e);
+                        $srv.write().await.report_error(&e);
                     }
-                });
-                Ok(())
-            }
-        }))
+                } else {
+                    log::error!("Could not get service in winrt handler!");
+                }
+            });
+        });
+        handler
     }};
 }
 
@@ -108,39 +317,79 @@ impl WindowsMediaService {
     /// You have to call [WindowsMediaService::begin_monitor_sessions] to receive
     /// [PlaybackChangedEvent]s.
     pub fn new(source_app_id: impl Into<String>) -> Arc<RwLock<Self>> {
+        let manager = GlobalSystemMediaTransportControlsSessionManager::RequestAsync()
+            .unwrap()
+            .get()
+            .unwrap();
+        Self::with_manager(Box::new(manager), source_app_id)
+    }
+
+    /// Like [WindowsMediaService::new], but with the sessions of any [manager]
+    /// (e.g. a fake one in tests).
+    fn with_manager(
+        manager: Box<dyn SmtcSessionManager>,
+        source_app_id: impl Into<String>,
+    ) -> Arc<RwLock<Self>> {
+        let source_app_id = source_app_id.into().to_lowercase();
         Arc::new_cyclic(|weak| {
             let (tx, _) = channel(16);
             RwLock::new(WindowsMediaService {
                 self_ref: weak.clone(),
-                manager: GlobalSystemMediaTransportControlsSessionManager::RequestAsync()
-                    .unwrap()
-                    .get()
-                    .unwrap(),
+                manager,
                 sessions_changed_handler: None,
-                media_properties_changed_handler: None,
-                media_playback_changed_handler: None,
+                source_session_handlers: Vec::new(),
                 source_session: None,
+                fallback_sessions: Vec::new(),
                 current_track: None,
                 playback_state: PlaybackState::default(),
-                source_app_id: source_app_id.into().to_lowercase(),
+                timeline: None,
+                progress_ticker: None,
+                source_app_id: source_app_id.clone(),
+                fallback_app_ids: Vec::new(),
+                active_app_id: source_app_id,
                 event_sender: tx,
+                activity_log: ActivityLog::default(),
+                metadata_rules: MetadataRules::default(),
+                thumbnail_limits: ThumbnailLimits::default(),
+                thumbnail_loading: None,
+                source_process: None,
+                volume_watcher: None,
             })
         })
     }
 
-    fn send_event(&self, ev: PlaybackChangedEvent) {
-        match ev {
+    fn send_event(&mut self, ev: PlaybackChangedEvent) {
+        match &ev {
             PlaybackChangedEvent::TrackChanged => {
                 log::info!("{:?}: {:?}", ev, self.current_track);
+                let title = self.current_track.as_ref().map(|t| t.title.clone());
+                self.activity_log.record(Activity::TrackChanged(title));
+            }
+            PlaybackChangedEvent::Play => {
+                log::info!("{:?}: {:?}", ev, self.playback_state);
+                self.activity_log.record(Activity::Playing);
             }
-            PlaybackChangedEvent::Pause | PlaybackChangedEvent::Play => {
+            PlaybackChangedEvent::Pause => {
                 log::info!("{:?}: {:?}", ev, self.playback_state);
+                self.activity_log.record(Activity::Paused);
+            }
+            PlaybackChangedEvent::SourceCrashed(app) => {
+                log::warn!("{:?}", ev);
+                self.activity_log
+                    .record(Activity::SourceCrashed(app.app_id.clone(), app.exit_code));
             }
             _ => {}
         };
         let _ = self.event_sender.send(ev);
     }
 
+    /// Records the error [e] and notifies subscribers about it.
+    fn report_error(&mut self, e: &MediaServiceError) {
+        self.activity_log
+            .record(Activity::Error(format!("{:?}", e)));
+        self.send_event(PlaybackChangedEvent::ServiceError(e.to_string()));
+    }
+
     /// Starts monitoring for the media session identified by its source app id.
     /// We also monitor all media sessions in the background on availability.
     /// If the [self.source_session] becomes unavailable, we stop monitoring that session
@@ -152,97 +401,197 @@ impl WindowsMediaService {
         }
 
         self.update_sessions()?;
-        let handle = register_winrt_event!(self, self.manager, SessionsChanged, |srv| {
-            srv.write().await.update_sessions()
-        })?;
+        self.watch_fallback_sessions()?;
+        let handler = event_handler!(self, "SessionsChanged", |srv| {
+            let mut srv = srv.write().await;
+            srv.update_sessions()
+                .and_then(|()| srv.watch_fallback_sessions())
+        });
+        let handle = self.manager.add_sessions_changed(handler)?;
         self.sessions_changed_handler = NonZero::new(handle);
         Ok(())
     }
 
+    /// Periodically publishes the interpolated playback position
+    /// while playing, so subscribers can show a smooth progress.
+    /// Stops once the playback pauses or the timeline is gone (e.g. with the session).
+    fn update_progress_ticker(&mut self) {
+        if !self.playback_state.is_playing || self.timeline.is_none() {
+            if let Some(ticker) = self.progress_ticker.take() {
+                ticker.abort();
+            }
+            return;
+        }
+        if self.progress_ticker.is_some() {
+            return;
+        }
+
+        let srv = self.clone();
+        self.progress_ticker = Some(tokio::spawn(async move {
+            let period = Duration::from_secs(1);
+            let mut interval = tokio::time::interval_at(Instant::now() + period, period);
+            loop {
+                interval.tick().await;
+                let Some(srv) = srv.upgrade() else {
+                    break;
+                };
+                let mut srv = srv.write().await;
+                srv.update_progress();
+                srv.send_event(PlaybackChangedEvent::PlaybackProgress);
+            }
+        }));
+    }
+
     /// Actually begins monitoring the selected [self.source_session] for changes.
     /// Does nothing if already monitoring.
     fn begin_monitor_source_session(&mut self) -> Result<(), MediaServiceError> {
-        if self.media_properties_changed_handler.is_some()
-            || self.media_playback_changed_handler.is_some()
-        {
+        if !self.source_session_handlers.is_empty() {
             return Ok(());
         }
-        let Some(session) = &self.source_session else {
+        let Some(session) = self.source_session.clone() else {
             return Ok(());
         };
 
         log::info!(
             "Beginning to monitor source session: {}",
-            &self.source_app_id
+            &self.active_app_id
         );
+        self.activity_log
+            .record(Activity::SessionFound(self.active_app_id.clone()));
 
-        let handle = register_winrt_event!(self, session, MediaPropertiesChanged, |srv| {
+        let event = SessionEvent::MediaPropertiesChanged;
+        let handler = event_handler!(self, event, |srv| {
             srv.write().await.update_current_session_info()
-        })?;
-        self.media_properties_changed_handler = NonZero::new(handle);
+        });
+        self.source_session_handlers
+            .push((event, session.add_handler(event, handler)?));
 
-        let handle = register_winrt_event!(self, session, PlaybackInfoChanged, |srv| {
+        let event = SessionEvent::PlaybackInfoChanged;
+        let handler = event_handler!(self, event, |srv| {
             srv.write().await.update_playback_info()
-        })?;
-        self.media_playback_changed_handler = NonZero::new(handle);
+        });
+        self.source_session_handlers
+            .push((event, session.add_handler(event, handler)?));
+
+        let event = SessionEvent::TimelinePropertiesChanged;
+        let handler = event_handler!(self, event, |srv| { srv.write().await.update_timeline() });
+        self.source_session_handlers
+            .push((event, session.add_handler(event, handler)?));
 
         self.update_current_session_info()?;
         self.update_playback_info()?;
+        self.update_timeline()?;
+        self.watch_volume();
+        self.find_source_process();
 
         Ok(())
     }
 
     fn update_sessions(&mut self) -> Result<(), MediaServiceError> {
-        for session in self.manager.GetSessions()? {
-            let session_app_id = session.SourceAppUserModelId()?.to_string();
-            log::debug!("Found source with id: {}", &session_app_id);
-            if session_app_id.eq_ignore_ascii_case(&self.source_app_id) {
-                if self.source_session.is_none() {
-                    self.source_session = Some(session);
-                    self.begin_monitor_source_session()?;
-                }
-                return Ok(());
+        let sessions = self.manager.sessions()?;
+        let session_infos = sessions
+            .iter()
+            .map(|session| {
+                let app_id = session.app_id()?;
+                let is_playing = session
+                    .playback_info()
+                    .is_ok_and(|playback| playback.is_playing);
+                Ok((app_id, is_playing))
+            })
+            .collect::<WinResult<Vec<_>>>()?;
+        log::debug!("Found sources with ids: {:?}", &session_infos);
+
+        let priorities: Vec<_> = std::iter::once(&self.source_app_id)
+            .chain(&self.fallback_app_ids)
+            .map(String::as_str)
+            .collect();
+        let current = self
+            .source_session
+            .as_ref()
+            .map(|_| self.active_app_id.as_str());
+        if let Some(idx) = select_source_session(&session_infos, &priorities, current) {
+            let app_id = session_infos[idx].0.to_lowercase();
+            if self.source_session.is_none() || app_id != self.active_app_id {
+                self.end_monitor_source_session();
+                self.active_app_id = app_id;
+                self.source_session = Some(sessions[idx].clone());
+                self.begin_monitor_source_session()?;
             }
+            return Ok(());
         }
         self.end_monitor_source_session();
         self.source_session = None;
+        self.active_app_id = self.source_app_id.clone();
+        Ok(())
+    }
+
+    /// Follows another app as soon as it starts or stops playing, if there are fallback apps.
+    /// The source session only notifies about the app it belongs to,
+    /// so all sessions of the source and fallback apps are watched.
+    fn watch_fallback_sessions(&mut self) -> Result<(), MediaServiceError> {
+        self.end_watch_fallback_sessions();
+        if self.fallback_app_ids.is_empty() {
+            return Ok(());
+        }
+
+        for session in self.manager.sessions()? {
+            let app_id = session.app_id()?;
+            let is_candidate = std::iter::once(&self.source_app_id)
+                .chain(&self.fallback_app_ids)
+                .any(|candidate| candidate.eq_ignore_ascii_case(&app_id));
+            if !is_candidate {
+                continue;
+            }
+            let event = SessionEvent::PlaybackInfoChanged;
+            let handler =
+                event_handler!(self, event, |srv| { srv.write().await.update_sessions() });
+            let handle = session.add_handler(event, handler)?;
+            self.fallback_sessions.push((session, handle));
+        }
         Ok(())
     }
 
+    fn end_watch_fallback_sessions(&mut self) {
+        for (session, handle) in self.fallback_sessions.drain(..) {
+            session.remove_handler(SessionEvent::PlaybackInfoChanged, handle);
+        }
+    }
+
     fn update_current_session_info(&mut self) -> Result<(), MediaServiceError> {
         let Some(session) = &self.source_session else {
             return Ok(());
         };
 
-        let media_props = session.TryGetMediaPropertiesAsync()?.get()?;
-        let timeline_props = session.GetTimelineProperties()?;
+        let media_props = session.media_properties()?;
+        let timeline = session.timeline()?;
+        self.cancel_thumbnail_loading();
 
-        let title_length = convert_ticks_to_seconds(timeline_props.MaxSeekTime()?.Duration);
+        let title_length = get_track_length(&timeline);
         let track = if title_length > 0 {
-            let album_cover = match media_props.Thumbnail() {
-                Ok(s) => match WindowsMediaService::read_thumbnail(s) {
-                    Ok(cover) => cover,
-                    Err(e) => {
-                        log::error!("Unable to fetch thumbnail: {}", e);
-                        AlbumCover::None
-                    }
-                },
-                Err(_) => AlbumCover::None,
+            let album_cover = match media_props.thumbnail {
+                Some(stream) => {
+                    self.load_thumbnail(stream)?;
+                    AlbumCover::Pending
+                }
+                None => AlbumCover::None,
             };
 
-            Some(MediaTrack {
-                album_title: unwrap_hstring(media_props.AlbumTitle(), "No Title"),
-                artist: unwrap_hstring(media_props.Artist(), "No Artist"),
-                title: unwrap_hstring(media_props.Title(), "No Title"),
+            let mut track = MediaTrack {
+                album_title: non_empty(media_props.album_title, "No Title"),
+                artist: non_empty(media_props.artist, "No Artist"),
+                title: non_empty(media_props.title, "No Title"),
                 length: title_length,
                 album_cover,
-            })
+            };
+            self.metadata_rules.apply(&self.active_app_id, &mut track);
+            Some(track)
         } else {
             // We have no track
             None
         };
 
         self.current_track = track;
+        self.update_progress();
         self.send_event(PlaybackChangedEvent::TrackChanged);
         Ok(())
     }
@@ -252,10 +601,42 @@ impl WindowsMediaService {
             return Ok(());
         };
 
-        let playback = session.GetPlaybackInfo()?;
-        // See: https://learn.microsoft.com/en-US/uwp/api/windows.media.control.globalsystemmediatransportcontrolssessionplaybackstatus?view=winrt-22621
-        let playing = playback.PlaybackStatus()?.0 == 4;
+        let playback = session.playback_info()?;
+        let playing = playback.is_playing;
+
+        // Not every player updates its timeline when pausing or resuming,
+        // so we continue interpolating from the current position.
+        // When pausing, the position the player reports right away is more accurate though.
+        let started = playing && !self.playback_state.is_playing;
+        if playing != self.playback_state.is_playing {
+            if let Some(timeline) = &mut self.timeline {
+                let reported = if playing {
+                    None
+                } else {
+                    read_timeline(session.as_ref())
+                        .inspect_err(|e| log::debug!("Could not read paused timeline: {}", e))
+                        .ok()
+                };
+                *timeline =
+                    timeline.rebased(SystemTime::now(), self.playback_state.is_playing, reported);
+            }
+        }
         self.playback_state.is_playing = playing;
+        self.update_progress();
+        self.update_progress_ticker();
+        // Some players only create their audio session once they start playing
+        if started {
+            self.watch_volume();
+            self.find_source_process();
+        }
+
+        let (shuffle, repeat) = (playback.shuffle, playback.repeat);
+        if shuffle != self.playback_state.shuffle || repeat != self.playback_state.repeat {
+            self.playback_state.shuffle = shuffle;
+            self.playback_state.repeat = repeat;
+            self.send_event(PlaybackChangedEvent::PlaybackMode);
+        }
+
         self.send_event(if playing {
             PlaybackChangedEvent::Play
         } else {
@@ -264,29 +645,141 @@ impl WindowsMediaService {
         Ok(())
     }
 
-    fn read_thumbnail(stream: IRandomAccessStreamReference) -> Result<AlbumCover> {
-        let stream_handle = stream.OpenReadAsync()?.get()?;
-        ensure!(stream_handle.CanRead()?, "Thumbnail is not readable");
+    fn update_timeline(&mut self) -> Result<(), MediaServiceError> {
+        let Some(session) = &self.source_session else {
+            return Ok(());
+        };
 
-        let buffer_size = stream_handle.Size()? as u32;
-        log::info!(
-            "Media thumbnail content-type: {}, Size: {}",
-            stream_handle.ContentType()?,
-            buffer_size
-        );
+        self.timeline = Some(read_timeline(session.as_ref())?);
+        self.update_progress();
+        self.update_progress_ticker();
+        self.send_event(PlaybackChangedEvent::PlaybackProgress);
+        Ok(())
+    }
+
+    /// Updates the position and progress of the [self.playback_state]
+    /// from the last known timeline.
+    fn update_progress(&mut self) {
+        let length = self.current_track.as_ref().map_or(0, |t| t.length);
+        let position = self.timeline.map(|timeline| {
+            let position = timeline
+                .position_at(SystemTime::now(), self.playback_state.is_playing)
+                .as_secs();
+            if length > 0 {
+                position.min(length)
+            } else {
+                position
+            }
+        });
+
+        self.playback_state.position = position;
+        self.playback_state.progress = match position {
+            Some(position) if length > 0 => Some((position * 100 / length) as u32),
+            _ => None,
+        };
+    }
+
+    /// Reads the volume of the audio sessions of the source app and watches them for
+    /// changes made outside of Spotick (e.g. in the volume mixer).
+    /// Keeps the last known volume if the app currently has no audio session.
+    fn watch_volume(&mut self) {
+        // Apps may have replaced their audio sessions in the meantime
+        self.volume_watcher = None;
+        let srv = self.clone();
+        let rt_handle = tokio::runtime::Handle::current();
+        let watcher = watch_app_volume(&self.active_app_id, move |volume| {
+            let srv = srv.clone();
+            rt_handle.spawn(async move {
+                if let Some(srv) = srv.upgrade() {
+                    srv.write().await.update_volume(volume);
+                }
+            });
+        });
+        match watcher {
+            Ok(watcher) => self.volume_watcher = watcher,
+            Err(e) => log::debug!("Could not watch volume: {}", e),
+        }
+
+        match get_app_volume(&self.active_app_id) {
+            Ok(Some(volume)) => self.update_volume(volume),
+            Ok(None) => {}
+            Err(e) => log::debug!("Could not read volume: {}", e),
+        }
+    }
+
+    fn update_volume(&mut self, volume: u32) {
+        if volume != self.playback_state.volume {
+            self.playback_state.volume = volume;
+            self.send_event(PlaybackChangedEvent::Volume);
+        }
+    }
 
-        let buf_reader = DataReader::CreateDataReader(&stream_handle)?;
-        buf_reader.SetInputStreamOptions(InputStreamOptions(2))?;
-        buf_reader.LoadAsync(buffer_size)?.get()?;
+    /// Loads the cover of the current track in the background within the [self.thumbnail_limits].
+    fn load_thumbnail(&mut self, stream: IRandomAccessStreamReference) -> WinResult<()> {
+        self.cancel_thumbnail_loading();
+        let loading = CancellationToken::new();
+        self.thumbnail_loading = Some(loading.clone());
 
-        let mut buffer: Vec<u8> = Vec::with_capacity(buffer_size as usize);
-        buffer.resize(buffer.capacity(), 0); // DataReader needs length == capacity
-        buf_reader.ReadBytes(&mut buffer)?;
+        // WinRT streams can't be sent between threads, so the whole read stays on one thread
+        let stream = AgileReference::new(&stream)?;
+        let limits = self.thumbnail_limits;
+        let srv = self.clone();
+        let rt_handle = tokio::runtime::Handle::current();
+        tokio::task::spawn_blocking(move || {
+            let res = rt_handle.block_on(async {
+                let read = async { read_thumbnail(stream.resolve()?, limits.max_size).await };
+                tokio::select! {
+                    _ = loading.cancelled() => None,
+                    res = tokio::time::timeout(limits.timeout, read) => {
+                        Some(res.unwrap_or(Err(ThumbnailError::Timeout)))
+                    }
+                }
+            });
+            let Some(res) = res else {
+                return;
+            };
+            rt_handle.spawn(async move {
+                if let Some(srv) = srv.upgrade() {
+                    let mut srv = srv.write().await;
+                    // The track might have changed while waiting for the lock
+                    if !loading.is_cancelled() {
+                        srv.apply_thumbnail(res);
+                    }
+                }
+            });
+        });
+        Ok(())
+    }
 
-        let img_reader = ImageReader::new(Cursor::new(buffer)).with_guessed_format()?;
-        let img = img_reader.decode()?.to_rgba8();
+    /// Makes sure a cover still loading isn't applied to another track.
+    fn cancel_thumbnail_loading(&mut self) {
+        if let Some(loading) = self.thumbnail_loading.take() {
+            loading.cancel();
+        }
+    }
 
-        Ok(AlbumCover::Image(img))
+    fn apply_thumbnail(&mut self, res: Result<RgbaImage, ThumbnailError>) {
+        self.thumbnail_loading = None;
+        let cover = match res {
+            Ok(img) => AlbumCover::Image(img),
+            Err(e) => {
+                log::error!("Unable to fetch thumbnail: {}", e);
+                // Let the user know why there is no cover
+                if let ThumbnailError::UnsupportedFormat(_) = e {
+                    self.send_event(PlaybackChangedEvent::ServiceError(e.to_string()));
+                }
+                AlbumCover::None
+            }
+        };
+        if let Some(track) = &mut self.current_track {
+            track.album_cover = cover;
+            self.send_event(PlaybackChangedEvent::CoverChanged);
+        }
+    }
+
+    /// Overrides the default limits for reading the covers of tracks.
+    pub fn set_thumbnail_limits(&mut self, limits: ThumbnailLimits) {
+        self.thumbnail_limits = limits;
     }
 
     /// Stops monitoring for the source media session.
@@ -295,24 +788,81 @@ impl WindowsMediaService {
     pub fn end_monitor_sessions(&mut self) {
         log::info!("Stopping monitoring media sessions");
         if let Some(handle) = self.sessions_changed_handler.take() {
-            let _ = self.manager.RemoveSessionsChanged(handle.get());
+            self.manager.remove_sessions_changed(handle.get());
         }
+        self.end_watch_fallback_sessions();
     }
 
     fn end_monitor_source_session(&mut self) {
-        log::info!("Stopping monitoring source media session");
+        // A crash usually ends the session before the process is noticed to end
+        self.check_source_process();
+        self.source_process = None;
+        self.volume_watcher = None;
+        self.cancel_thumbnail_loading();
         if let Some(session) = self.source_session.take() {
-            if let Some(handle) = self.media_properties_changed_handler.take() {
-                let _ = session.RemoveMediaPropertiesChanged(handle.get());
-            }
-            if let Some(handle) = self.media_playback_changed_handler.take() {
-                let _ = session.RemovePlaybackInfoChanged(handle.get());
+            log::info!("Stopping monitoring source media session");
+            for (event, handle) in self.source_session_handlers.drain(..) {
+                session.remove_handler(event, handle);
             }
+            self.activity_log
+                .record(Activity::SessionLost(self.active_app_id.clone()));
         }
         self.current_track = None;
+        self.timeline = None;
+        self.update_progress();
+        self.update_progress_ticker();
         self.send_event(PlaybackChangedEvent::TrackChanged);
     }
 
+    /// Finds the process of the source app by its audio session once it plays,
+    /// as some players only create their audio session then.
+    /// Crashes are picked up by [WindowsMediaService::check_source_process] once it ended.
+    fn find_source_process(&mut self) {
+        if self.source_process.is_some() || !self.playback_state.is_playing {
+            return;
+        }
+        let srv = self.clone();
+        let rt_handle = tokio::runtime::Handle::current();
+        let on_exit = move || {
+            let srv = srv.clone();
+            rt_handle.spawn(async move {
+                if let Some(srv) = srv.upgrade() {
+                    srv.write().await.check_source_process();
+                }
+            });
+        };
+        match find_app_process(&self.active_app_id) {
+            Ok(Some((pid, image_path))) => match SourceProcess::open(pid, image_path, on_exit) {
+                Ok(process) => self.source_process = Some(process),
+                Err(e) => log::debug!("Could not open process {} of source app: {}", pid, e),
+            },
+            Ok(None) => {}
+            Err(e) => log::debug!("Could not find process of source app: {}", e),
+        }
+    }
+
+    /// Sends [PlaybackChangedEvent::SourceCrashed] if the process of the source app ended
+    /// while playing. Apps closed as usual stop playing first or exit with code 0.
+    fn check_source_process(&mut self) {
+        let Some(exit_code) = self
+            .source_process
+            .as_ref()
+            .and_then(SourceProcess::exit_code)
+        else {
+            return;
+        };
+        let Some(process) = self.source_process.take() else {
+            return;
+        };
+        if self.playback_state.is_playing && exit_code != 0 {
+            self.send_event(PlaybackChangedEvent::SourceCrashed(CrashedApp {
+                app_id: self.active_app_id.clone(),
+                image_path: process.image_path,
+                exit_code,
+            }));
+        }
+    }
+
     pub fn clone(&self) -> Weak<RwLock<Self>> {
         self.self_ref.clone()
     }
@@ -321,7 +871,9 @@ impl WindowsMediaService {
 impl Drop for WindowsMediaService {
     fn drop(&mut self) {
         self.end_monitor_sessions();
+        // Also stops the progress ticker
         self.end_monitor_source_session();
+        self.activity_log.dump();
     }
 }
 
@@ -331,56 +883,87 @@ impl BaseService<PlaybackChangedEvent> for WindowsMediaService {
     }
 }
 
-macro_rules! wait_async_op {
-    ($async_op:expr) => {
-        let x = $async_op;
-        tokio::task::spawn_blocking(move || x.get())
+/// Sends the [request] to the [session] (if any) without blocking the runtime.
+async fn send_request(
+    session: &Option<Session>,
+    request: SessionRequest,
+) -> Result<(), MediaServiceError> {
+    if let Some(session) = session.clone() {
+        tokio::task::spawn_blocking(move || session.request(request))
             .await
-            .unwrap()?
-    };
+            .unwrap()?;
+    }
+    Ok(())
 }
 
 #[async_trait::async_trait]
 impl MediaService for WindowsMediaService {
     async fn next_track(&mut self) -> Result<(), MediaServiceError> {
-        if let Some(session) = &self.source_session {
-            wait_async_op!(session.TrySkipNextAsync()?);
-        }
-        Ok(())
+        send_request(&self.source_session, SessionRequest::SkipNext).await
     }
 
     async fn previous_track(&mut self) -> Result<(), MediaServiceError> {
-        if let Some(session) = &self.source_session {
-            wait_async_op!(session.TrySkipPreviousAsync()?);
-        }
-        Ok(())
+        send_request(&self.source_session, SessionRequest::SkipPrevious).await
     }
 
     async fn play(&mut self) -> Result<(), MediaServiceError> {
-        if let Some(session) = &self.source_session {
-            wait_async_op!(session.TryPlayAsync()?);
-        }
-        Ok(())
+        send_request(&self.source_session, SessionRequest::Play).await
     }
 
     async fn pause(&mut self) -> Result<(), MediaServiceError> {
-        if let Some(session) = &self.source_session {
-            wait_async_op!(session.TryPauseAsync()?);
+        send_request(&self.source_session, SessionRequest::Pause).await
+    }
+
+    async fn seek(&mut self, playback_percent: u32) -> Result<(), MediaServiceError> {
+        let Some(session) = &self.source_session else {
+            return Ok(());
+        };
+        if self.current_track.is_none() {
+            return Ok(());
+        }
+
+        let position = get_seek_position(&session.timeline()?, playback_percent);
+        log::debug!("Seeking to {}% ({} ticks)", playback_percent, position);
+        let request = SessionRequest::ChangePlaybackPosition(position);
+        send_request(&self.source_session, request).await?;
+
+        if !self.playback_state.is_playing {
+            self.play().await?;
         }
         Ok(())
     }
 
-    async fn seek(&mut self, _playback_percent: u32) -> Result<(), MediaServiceError> {
+    async fn set_volume(&mut self, volume: u32) -> Result<(), MediaServiceError> {
+        let volume = volume.min(100);
+        if set_app_volume(&self.active_app_id, volume)? {
+            self.update_volume(volume);
+        }
         Ok(())
     }
 
-    async fn set_volume(&mut self, _volume: u32) -> Result<(), MediaServiceError> {
-        Ok(())
+    async fn set_shuffle(&mut self, shuffle: bool) -> Result<(), MediaServiceError> {
+        let request = SessionRequest::ChangeShuffleActive(shuffle);
+        send_request(&self.source_session, request).await
+    }
+
+    async fn set_repeat(&mut self, repeat: RepeatMode) -> Result<(), MediaServiceError> {
+        let request = SessionRequest::ChangeAutoRepeatMode(repeat);
+        send_request(&self.source_session, request).await
     }
 
     fn set_source_app_id(&mut self, app_id: String) -> Result<(), MediaServiceError> {
-        self.end_monitor_sessions();
+        let app_id = app_id.to_lowercase();
+        if app_id == self.source_app_id {
+            return Ok(());
+        }
         self.source_app_id = app_id;
+        // Picked up by begin_monitor_sessions otherwise, which is deferred on startup
+        if self.sessions_changed_handler.is_none() {
+            self.active_app_id = self.source_app_id.clone();
+            return Ok(());
+        }
+        self.end_monitor_sessions();
+        self.end_monitor_source_session();
         self.begin_monitor_sessions()?;
         Ok(())
     }
@@ -389,19 +972,58 @@ impl MediaService for WindowsMediaService {
         &self.source_app_id
     }
 
-    fn get_available_source_apps_ids(&self) -> Result<Vec<String>, MediaServiceError> {
-        let app_ids = self
+    fn set_fallback_app_ids(&mut self, app_ids: Vec<String>) -> Result<(), MediaServiceError> {
+        self.fallback_app_ids = app_ids.into_iter().map(|id| id.to_lowercase()).collect();
+        // Picked up by begin_monitor_sessions otherwise
+        if self.sessions_changed_handler.is_some() {
+            self.update_sessions()?;
+            self.watch_fallback_sessions()?;
+        }
+        Ok(())
+    }
+
+    fn get_active_app_id(&self) -> &str {
+        &self.active_app_id
+    }
+
+    fn has_source_session(&self) -> bool {
+        self.source_session.is_some()
+    }
+
+    fn set_metadata_rules(&mut self, rules: MetadataRules) -> Result<(), MediaServiceError> {
+        self.metadata_rules = rules;
+        self.update_current_session_info()
+    }
+
+    fn list_sessions(&self) -> Result<Vec<MediaSessionInfo>, MediaServiceError> {
+        let sessions = self
             .manager
-            .GetSessions()?
+            .sessions()?
             .into_iter()
             .map(|session| {
-                session
-                    .SourceAppUserModelId()
-                    .unwrap_or_default()
-                    .to_string()
+                let app_id = session.app_id().unwrap_or_default();
+                // Apps still starting up might not provide any properties yet
+                let media_props = session.media_properties().ok();
+                let is_playing = session
+                    .playback_info()
+                    .is_ok_and(|playback| playback.is_playing);
+                MediaSessionInfo {
+                    app_name: resolve_app_name(&app_id),
+                    track_title: media_props
+                        .as_ref()
+                        .map(|props| props.title.clone())
+                        .unwrap_or_default(),
+                    track_artist: media_props.map(|props| props.artist).unwrap_or_default(),
+                    is_playing,
+                    app_id,
+                }
             })
             .collect();
-        Ok(app_ids)
+        Ok(sessions)
+    }
+
+    fn get_source_app_name(&self, app_id: &str) -> String {
+        resolve_app_name(app_id)
     }
 
     fn current_track(&self) -> Option<&MediaTrack> {
@@ -411,4 +1033,234 @@ impl MediaService for WindowsMediaService {
     fn current_playback_state(&self) -> &PlaybackState {
         &self.playback_state
     }
+
+    fn activity_log(&self) -> &ActivityLog {
+        &self.activity_log
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::ensure;
+
+    use super::*;
+    use crate::service::smtc::fake::{FakeSession, FakeSessionManager};
+
+    fn app_ids(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    #[test]
+    fn display_names_from_exe() {
+        assert_eq!(exe_display_name("spotify.exe"), "Spotify");
+        assert_eq!(
+            exe_display_name("C:\\Programs\\foobar2000.EXE"),
+            "Foobar2000"
+        );
+        assert_eq!(exe_display_name("Chrome"), "Chrome");
+        assert_eq!(exe_display_name(".exe"), ".exe");
+    }
+
+    #[test]
+    fn interpolates_position_while_playing() {
+        let updated = UNIX_EPOCH + Duration::from_secs(1000);
+        let timeline = TimelineAnchor {
+            position: Duration::from_secs(30),
+            updated,
+            reported: updated,
+        };
+        let now = updated + Duration::from_secs(5);
+        assert_eq!(timeline.position_at(now, true), Duration::from_secs(35));
+        assert_eq!(timeline.position_at(now, false), Duration::from_secs(30));
+        // Clock skew between the player and us
+        let before = updated - Duration::from_secs(5);
+        assert_eq!(timeline.position_at(before, true), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn captures_position_when_pausing() {
+        let at = |secs| UNIX_EPOCH + Duration::from_secs(secs);
+        let timeline = TimelineAnchor {
+            position: Duration::from_secs(30),
+            updated: at(1000),
+            reported: at(1000),
+        };
+        // The player didn't report a new timeline, so it is interpolated
+        let paused = timeline.rebased(at(1130), true, Some(timeline));
+        assert_eq!(
+            paused.position_at(at(1200), false),
+            Duration::from_secs(160)
+        );
+        assert_eq!(paused.reported, at(1000));
+
+        // Interpolating drifted by a second
+        let reported = TimelineAnchor {
+            position: Duration::from_secs(161),
+            updated: at(1130),
+            reported: at(1130),
+        };
+        let paused = timeline.rebased(at(1130), true, Some(reported));
+        assert_eq!(
+            paused.position_at(at(1200), false),
+            Duration::from_secs(161)
+        );
+
+        // Resuming continues from the paused position
+        let resumed = paused.rebased(at(1300), false, None);
+        assert_eq!(
+            resumed.position_at(at(1310), true),
+            Duration::from_secs(171)
+        );
+    }
+
+    #[test]
+    fn converts_date_times() {
+        let date_time = DateTime {
+            UniversalTime: UNIX_EPOCH_TICKS + 15_000_000,
+        };
+        assert_eq!(
+            convert_date_time(date_time),
+            UNIX_EPOCH + Duration::from_millis(1500)
+        );
+        assert_eq!(convert_ticks_to_duration(-1), Duration::ZERO);
+    }
+
+    #[test]
+    fn seek_percent_to_ticks() {
+        assert_eq!(percent_to_ticks(0, 1_800_000_000, 50), 900_000_000);
+        assert_eq!(percent_to_ticks(100, 300, 0), 100);
+        assert_eq!(percent_to_ticks(100, 300, 100), 300);
+        assert_eq!(percent_to_ticks(0, 1_000, 250), 1_000);
+        assert_eq!(percent_to_ticks(500, 0, 50), 500);
+    }
+
+    async fn start_monitoring(
+        manager: &FakeSessionManager,
+        source_app_id: &str,
+    ) -> Result<Arc<RwLock<WindowsMediaService>>> {
+        let srv = WindowsMediaService::with_manager(Box::new(manager.clone()), source_app_id);
+        srv.write().await.begin_monitor_sessions()?;
+        Ok(srv)
+    }
+
+    /// Lets the handlers spawned for the events raised by the fakes run.
+    async fn settle() {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    fn sessions_found(srv: &WindowsMediaService) -> usize {
+        srv.activity_log
+            .entries()
+            .filter(|(_, activity)| matches!(activity, Activity::SessionFound(_)))
+            .count()
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn monitors_source_session() -> Result<()> {
+        let manager = FakeSessionManager::default();
+        manager.add_session(FakeSession::new("chrome.exe"));
+        let spotify = FakeSession::new("Spotify.exe");
+        spotify.set_track("Song", "Artist", 180);
+        manager.add_session(spotify.clone());
+
+        let srv = start_monitoring(&manager, "spotify.exe").await?;
+        {
+            let srv = srv.read().await;
+            ensure!(srv.has_source_session());
+            ensure!(srv.get_active_app_id() == "spotify.exe");
+            ensure!(spotify.handler_count() == 3);
+            ensure!(srv
+                .current_track()
+                .is_some_and(|track| track.title == "Song" && track.length == 180));
+        }
+
+        let mut events = srv.read().await.subscribe();
+        spotify.set_playing(true);
+        settle().await;
+        ensure!(matches!(events.try_recv(), Ok(PlaybackChangedEvent::Play)));
+        ensure!(srv.read().await.current_playback_state().is_playing);
+
+        srv.write().await.next_track().await?;
+        ensure!(*spotify.requests.lock().unwrap() == [SessionRequest::SkipNext]);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn reattaches_to_restarted_source() -> Result<()> {
+        let manager = FakeSessionManager::default();
+        let srv = start_monitoring(&manager, "spotify.exe").await?;
+        ensure!(!srv.read().await.has_source_session());
+
+        let spotify = FakeSession::new("spotify.exe");
+        manager.add_session(spotify.clone());
+        settle().await;
+        ensure!(srv.read().await.has_source_session());
+        ensure!(spotify.handler_count() == 3);
+
+        manager.remove_session("spotify.exe");
+        settle().await;
+        ensure!(!srv.read().await.has_source_session());
+        ensure!(spotify.handler_count() == 0);
+
+        let restarted = FakeSession::new("spotify.exe");
+        restarted.set_track("Song", "Artist", 180);
+        manager.add_session(restarted.clone());
+        settle().await;
+        let srv = srv.read().await;
+        ensure!(restarted.handler_count() == 3);
+        ensure!(srv.current_track().is_some());
+        ensure!(sessions_found(&srv) == 2);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn ignores_repeated_sessions_changed() -> Result<()> {
+        let manager = FakeSessionManager::default();
+        let spotify = FakeSession::new("spotify.exe");
+        manager.add_session(spotify.clone());
+        let srv = start_monitoring(&manager, "spotify.exe").await?;
+        let mut events = srv.read().await.subscribe();
+
+        for _ in 0..5 {
+            manager.sessions_changed();
+        }
+        settle().await;
+        ensure!(spotify.handler_count() == 3);
+        ensure!(sessions_found(&*srv.read().await) == 1);
+        ensure!(events.try_recv().is_err());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn follows_playing_fallback_app() -> Result<()> {
+        let manager = FakeSessionManager::default();
+        let spotify = FakeSession::new("spotify.exe");
+        let vlc = FakeSession::new("vlc.exe");
+        manager.add_session(spotify.clone());
+        manager.add_session(vlc.clone());
+        let srv = start_monitoring(&manager, "spotify.exe").await?;
+        srv.write()
+            .await
+            .set_fallback_app_ids(app_ids(&["VLC.exe"]))?;
+        ensure!(srv.read().await.get_active_app_id() == "spotify.exe");
+
+        vlc.set_playing(true);
+        settle().await;
+        ensure!(srv.read().await.get_active_app_id() == "vlc.exe");
+        // Source handlers of the followed session, plus the one watching for playback
+        ensure!(vlc.handler_count() == 4);
+        ensure!(spotify.handler_count() == 1);
+
+        // Pausing keeps the app
+        vlc.set_playing(false);
+        settle().await;
+        ensure!(srv.read().await.get_active_app_id() == "vlc.exe");
+
+        spotify.set_playing(true);
+        settle().await;
+        ensure!(srv.read().await.get_active_app_id() == "spotify.exe");
+        ensure!(spotify.handler_count() == 4);
+        ensure!(vlc.handler_count() == 1);
+        Ok(())
+    }
 }
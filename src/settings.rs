@@ -1,12 +1,21 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
 use serde::{Deserialize, Serialize};
 use slint::PhysicalPosition;
 use tokio::sync::RwLock;
 
+use crate::{
+    schedule::SourceScheduleRule,
+    service::{MetadataRule, NormalizationRule},
+    ui::{
+        announcer::AnnouncementVerbosity, placement::WindowLayout, theme::ThemeSetting,
+        toast::ToastMode,
+    },
+};
+
 mod app_settings;
 
-pub use crate::settings::app_settings::AppSettings;
+pub use crate::settings::app_settings::{get_data_dir, AppSettings};
 
 pub type SpotickAppSettings = Arc<RwLock<AppSettings<SpotickSettings>>>;
 
@@ -20,6 +29,64 @@ pub struct SpotickSettings {
     pub source_app: String,
     pub main_window_pos: PhysicalPosition,
     pub main_window_scale: f32,
+    /// Rules overriding [SpotickSettings::source_app] at certain times.
+    pub source_schedule: Option<Vec<SourceScheduleRule>>,
+    /// Rules rewriting track metadata (e.g. stripping "(Official Video)").
+    pub metadata_rules: Option<Vec<MetadataRule>>,
+    /// Normalization (e.g. title casing) applied after [SpotickSettings::metadata_rules].
+    pub metadata_normalization: Option<Vec<NormalizationRule>>,
+    /// Upscale covers smaller than the displayed thumbnail (default: true).
+    pub upscale_thumbnails: Option<bool>,
+    /// Ask before quitting while the source app is playing (default: false).
+    pub confirm_quit: Option<bool>,
+    /// Only log registry changes (e.g. autostart) instead of executing them (default: false).
+    /// Can also be enabled for a single run with `--dry-run`.
+    pub dry_run: Option<bool>,
+    /// Playback changes announced to screen readers (default: Tracks).
+    pub announcements: Option<AnnouncementVerbosity>,
+    /// Media applications followed while the source app isn't playing,
+    /// most preferred first (default: none).
+    pub fallback_apps: Option<Vec<String>>,
+    /// Minimize the main window while presenting (default: true).
+    pub presentation_hide_window: Option<bool>,
+    /// Hold back error messages in the main window and toasts while presenting
+    /// (default: true). Errors are still listed in the notifications.
+    pub presentation_quiet: Option<bool>,
+    /// Largest cover read from the media app in KiB (default: 10240).
+    pub thumbnail_max_size: Option<u64>,
+    /// Time to read a cover from the media app in milliseconds (default: 5000).
+    pub thumbnail_timeout: Option<u64>,
+    /// Count down the last seconds of each track prominently over the cover (default: false).
+    pub countdown: Option<bool>,
+    /// Colors, font and shape of the main window (default: Dark).
+    pub theme: Option<ThemeSetting>,
+    /// Tint the [SpotickSettings::theme] in the colors of the current cover (default: false).
+    pub cover_colors: Option<bool>,
+    /// Let clicks pass through the main window, e.g. to use it as overlay in a stream
+    /// (default: false). Toggled with Ctrl+Alt+O, since the window can't be clicked anymore.
+    pub click_through: Option<bool>,
+    /// File in the data directory shown instead of a cover, picked in the settings
+    /// (default: none, the built-in placeholder).
+    pub placeholder_image: Option<String>,
+    /// Snap the main window to the screen edges when dragged within this many pixels of them,
+    /// 0 to turn it off (default: 16).
+    pub snap_margin: Option<u32>,
+    /// Main window position per layout and monitor arrangement, so it returns to where it was
+    /// once e.g. a laptop is docked again (default: none, [SpotickSettings::main_window_pos]).
+    pub window_positions: Option<HashMap<String, PhysicalPosition>>,
+    /// Minimize the main window while the source app (or a fallback app) has no media session,
+    /// e.g. because it isn't running (default: false).
+    pub hide_when_idle: Option<bool>,
+    /// Show how loud the source app is next to the volume, e.g. to spot playback without sound
+    /// (default: false).
+    pub level_meter: Option<bool>,
+    /// Layout of the main window (default: Full).
+    pub layout: Option<WindowLayout>,
+    /// Show a Windows notification with the cover on track changes (default: Off).
+    pub track_toasts: Option<ToastMode>,
+    /// Show a Windows notification with a button to restart the source app
+    /// if it crashes while playing (default: true).
+    pub crash_toasts: Option<bool>,
 }
 
 impl Default for SpotickSettings {
@@ -30,6 +97,30 @@ impl Default for SpotickSettings {
             main_window_scale: 1.0,
             source_app: String::from("spotify.exe"),
             main_window_pos: PhysicalPosition::default(),
+            source_schedule: None,
+            metadata_rules: None,
+            metadata_normalization: None,
+            upscale_thumbnails: Some(true),
+            confirm_quit: Some(false),
+            dry_run: Some(false),
+            announcements: Some(AnnouncementVerbosity::Tracks),
+            fallback_apps: Some(Vec::new()),
+            presentation_hide_window: Some(true),
+            presentation_quiet: Some(true),
+            thumbnail_max_size: Some(10240),
+            thumbnail_timeout: Some(5000),
+            countdown: Some(false),
+            theme: Some(ThemeSetting::Dark),
+            cover_colors: Some(false),
+            click_through: Some(false),
+            placeholder_image: None,
+            snap_margin: Some(16),
+            window_positions: Some(HashMap::new()),
+            hide_when_idle: Some(false),
+            level_meter: Some(false),
+            layout: Some(WindowLayout::Full),
+            track_toasts: Some(ToastMode::Off),
+            crash_toasts: Some(true),
         }
     }
 }
@@ -3,31 +3,55 @@ use std::{
     sync::Arc,
 };
 
-use anyhow::{bail, Result};
+use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use tokio::sync::{
     broadcast::{channel, Receiver, Sender},
     RwLock,
 };
 
-use crate::service::BaseService;
+use crate::{
+    service::BaseService,
+    storage::{file_storage_of, SharedStorage},
+};
 
 pub struct AppSettings<S> {
-    save_path: PathBuf,
+    storage: SharedStorage,
+    /// Name of the settings in the [AppSettings::storage]
+    name: String,
     event_sender: Sender<()>,
     settings: S,
 }
 
+/// Gets the directory all app data (settings, caches,...) is stored in.
 #[cfg(windows)]
+pub fn get_data_dir() -> PathBuf {
+    let app_data = std::env::var("APPDATA").expect("APPDATA should be present");
+    Path::new(&app_data).join(env!("CARGO_PKG_NAME"))
+}
+
+/// Gets the directory all app data (settings, caches,...) is stored in.
+/// Like the roaming app data of Windows, it is the config dir of the XDG base directories.
+#[cfg(target_os = "linux")]
+pub fn get_data_dir() -> PathBuf {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| {
+            let home = std::env::var_os("HOME").expect("HOME should be present");
+            Path::new(&home).join(".config")
+        });
+    config_home.join(env!("CARGO_PKG_NAME"))
+}
+
 fn get_default_save_path() -> PathBuf {
     #[cfg(debug_assertions)]
-    static RELATIVE_SAVE_PATH: &str = concat!(env!("CARGO_PKG_NAME"), "/settings-dbg.json");
+    static SAVE_FILE_NAME: &str = "settings-dbg.json";
 
     #[cfg(not(debug_assertions))]
-    static RELATIVE_SAVE_PATH: &str = concat!(env!("CARGO_PKG_NAME"), "/settings.json");
+    static SAVE_FILE_NAME: &str = "settings.json";
 
-    let app_data = std::env::var("APPDATA").expect("APPDATA should be present");
-    Path::new(&app_data).join(RELATIVE_SAVE_PATH)
+    get_data_dir().join(SAVE_FILE_NAME)
 }
 
 impl<S> AppSettings<S>
@@ -39,22 +63,28 @@ where
         AppSettings::<S>::new(save_path)
     }
 
+    /// Creates settings stored as a file at [save_path].
     pub fn new(save_path: impl Into<PathBuf>) -> Result<Arc<RwLock<Self>>> {
-        let save_path = save_path.into();
-        std::fs::create_dir_all(&save_path.parent().unwrap())?;
+        let (storage, name) = file_storage_of(&save_path.into())?;
+        Ok(AppSettings::<S>::with_storage(storage, name))
+    }
+
+    /// Creates settings stored as [name] in the [storage].
+    pub fn with_storage(storage: SharedStorage, name: impl Into<String>) -> Arc<RwLock<Self>> {
         let (tx, _) = channel(16);
-        let settings = Arc::new(RwLock::new(AppSettings {
-            save_path,
+        Arc::new(RwLock::new(AppSettings {
+            storage,
+            name: name.into(),
             event_sender: tx,
             settings: S::default(),
-        }));
-        Ok(settings)
+        }))
     }
 
     pub fn get_settings(&self) -> &S {
         &self.settings
     }
 
+    #[cfg(any(windows, test))]
     pub fn get_settings_mut(&mut self) -> &mut S {
         &mut self.settings
     }
@@ -63,29 +93,101 @@ where
         let _ = self.event_sender.send(());
     }
 
-    /// Writes the current settings to disk
+    /// Writes the current settings to disk.
+    /// The storage replaces the previous settings atomically,
+    /// so a crash mid-write can't corrupt them. The previous settings are kept as a backup.
     pub async fn save(&self) -> Result<()> {
         let json = serde_json::to_string_pretty(&self.settings)?;
-        tokio::fs::write(&self.save_path, json).await?;
+        self.backup_current().await;
+        self.storage.write(&self.name, json.as_bytes()).await?;
         self.notify_settings_changed();
         Ok(())
     }
 
     /// Loads the settings from disk, overriding the currently loaded ones.
+    /// Falls back to the backup if the file can't be read (e.g. after being edited by hand)
+    /// and keeps the current settings if neither can be read.
     /// Does nothing if the file doesn't exist.
     pub async fn load(&mut self) -> Result<()> {
-        let file_contents = tokio::fs::read(&self.save_path).await;
-        let file_contents = match file_contents {
-            Ok(res) => res,
-            Err(e) => match e.kind() {
-                std::io::ErrorKind::NotFound => return Ok(()),
-                _ => bail!(e),
-            },
+        let e = match self.read_settings(&self.name).await {
+            Ok(Some(settings)) => {
+                self.settings = settings;
+                self.notify_settings_changed();
+                return Ok(());
+            }
+            Ok(None) => return Ok(()),
+            Err(e) => e,
         };
-        self.settings = serde_json::from_slice::<S>(&file_contents)?;
-        self.notify_settings_changed();
+
+        let backup_name = backup_name(&self.name);
+        log::error!(
+            "Could not load settings from {}, trying {}: {:?}",
+            self.name,
+            backup_name,
+            e
+        );
+        match self.read_settings(&backup_name).await {
+            Ok(Some(settings)) => {
+                self.settings = settings;
+                self.notify_settings_changed();
+            }
+            Ok(None) => log::warn!("No settings backup, keeping the current settings"),
+            Err(e) => log::error!(
+                "Could not load settings backup, keeping the current settings: {:?}",
+                e
+            ),
+        }
         Ok(())
     }
+
+    /// Loads the settings like [AppSettings::load].
+    /// Without any settings on disk (e.g. on a fresh install), the current (default) settings
+    /// are written instead, so the next run finds them.
+    /// Returns whether the settings didn't exist yet, i.e. this is the first run.
+    pub async fn load_or_default(&mut self) -> Result<bool> {
+        if self.storage.exists(&self.name).await? {
+            self.load().await?;
+            return Ok(false);
+        }
+
+        self.save().await?;
+        Ok(true)
+    }
+
+    /// Copies the settings on disk to the backup, unless they are unreadable.
+    /// The backup then always holds the last good settings.
+    async fn backup_current(&self) {
+        let contents = match self.storage.read(&self.name).await {
+            Ok(Some(contents)) => contents,
+            Ok(None) => return,
+            Err(e) => {
+                log::warn!("Could not read settings to back up: {}", e);
+                return;
+            }
+        };
+        if serde_json::from_slice::<S>(&contents).is_err() {
+            log::warn!("Not backing up unreadable settings of {}", self.name);
+            return;
+        }
+
+        let backup_name = backup_name(&self.name);
+        if let Err(e) = self.storage.write(&backup_name, &contents).await {
+            log::warn!("Could not back up settings to {}: {}", backup_name, e);
+        }
+    }
+
+    /// Reads the settings stored as [name]. Returns [None] if they don't exist.
+    async fn read_settings(&self, name: &str) -> Result<Option<S>> {
+        match self.storage.read(name).await? {
+            Some(contents) => Ok(Some(serde_json::from_slice::<S>(&contents)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Name of the backup of the settings [name] (e.g. settings.json.bak).
+fn backup_name(name: &str) -> String {
+    format!("{}.bak", name)
 }
 
 impl<S> BaseService<()> for AppSettings<S>
@@ -99,8 +201,8 @@ where
 
 #[cfg(test)]
 mod test {
-    use anyhow::ensure;
-    use rand::{rngs::StdRng, RngCore, SeedableRng};
+    use anyhow::{ensure, Context as _};
+    use rand::{rngs::StdRng, Rng, RngCore, SeedableRng};
     use test_context::{test_context, AsyncTestContext};
 
     #[derive(Serialize, Deserialize, Default, PartialEq)]
@@ -145,6 +247,7 @@ mod test {
         Ok(())
     }
 
+    #[cfg(windows)]
     #[test]
     fn correct_default_save_path() {
         std::env::set_var("APPDATA", "C:\\Users\\test\\AppData\\Roaming");
@@ -155,6 +258,29 @@ mod test {
         );
     }
 
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn data_dir_in_xdg_config_home() {
+        std::env::set_var("XDG_CONFIG_HOME", "/home/test/.config");
+        assert_eq!(get_data_dir(), PathBuf::from("/home/test/.config/spotick"));
+    }
+
+    #[test_context(Context)]
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn load_or_default_first_run(ctx: &mut Context) -> Result<()> {
+        let app_settings = AppSettings::<TestSettings>::new(&ctx.path)?;
+        ensure!(
+            app_settings.write().await.load_or_default().await?,
+            "Expected first run"
+        );
+        ensure!(ctx.path.exists(), "Default settings not written");
+        ensure!(
+            !app_settings.write().await.load_or_default().await?,
+            "Expected existing settings"
+        );
+        Ok(())
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
     async fn load_non_existing() -> Result<()> {
         let app_settings = AppSettings::<TestSettings>::new("test.json")?;
@@ -181,4 +307,108 @@ mod test {
         );
         Ok(())
     }
+
+    #[test_context(Context)]
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn load_truncated(ctx: &mut Context) -> Result<()> {
+        let expected = TestSettings {
+            int: 42,
+            hello: "world".into(),
+            nice: true,
+        };
+        let json = serde_json::to_vec_pretty(&expected)?;
+        let app_settings = AppSettings::<TestSettings>::new(&ctx.path)?;
+
+        // No prefix of a settings file is valid.
+        // Without a backup, a failed load must keep the previous settings
+        for len in 0..json.len() {
+            std::fs::write(&ctx.path, &json[..len])?;
+            app_settings.write().await.load().await?;
+            ensure!(
+                app_settings.read().await.get_settings() == &TestSettings::default(),
+                "Failed load changed the settings"
+            );
+        }
+
+        std::fs::write(&ctx.path, &json)?;
+        app_settings.write().await.load().await?;
+        ensure!(
+            app_settings.read().await.get_settings() == &expected,
+            "Complete settings not loaded"
+        );
+        Ok(())
+    }
+
+    #[test_context(Context)]
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn load_backup(ctx: &mut Context) -> Result<()> {
+        let backup_path = ctx.path.with_extension("json.bak");
+        let app_settings = AppSettings::<TestSettings>::new(&ctx.path)?;
+        app_settings.write().await.get_settings_mut().int = 1;
+        app_settings.write().await.save().await?;
+        ensure!(!backup_path.exists(), "Backed up missing settings");
+        app_settings.write().await.get_settings_mut().int = 2;
+        app_settings.write().await.save().await?;
+        let name = ctx.path.file_name().unwrap().to_string_lossy().to_string();
+        let tmp_files = std::fs::read_dir(ctx.path.parent().unwrap())?
+            .filter_map(|entry| Some(entry.ok()?.file_name().to_string_lossy().to_string()))
+            .filter(|file| file.starts_with(&name) && file.ends_with(".tmp"))
+            .count();
+        ensure!(tmp_files == 0, "Temporary file left behind");
+
+        // A corrupt file is never backed up, so the backup stays the last good one
+        std::fs::write(&ctx.path, b"{\"int\": 3")?;
+        app_settings.write().await.get_settings_mut().int = 3;
+        app_settings.write().await.save().await?;
+        std::fs::write(&ctx.path, b"{\"int\": 4")?;
+
+        let loaded = AppSettings::<TestSettings>::new(&ctx.path)?;
+        loaded.write().await.load().await?;
+        ensure!(
+            loaded.read().await.get_settings().int == 1,
+            "Backup not loaded"
+        );
+
+        let _ = std::fs::remove_file(backup_path);
+        Ok(())
+    }
+
+    #[test_context(Context)]
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn load_arbitrary(ctx: &mut Context) -> Result<()> {
+        // Set SPOTICK_TEST_SEED to replay the contents of a failed run
+        let seed = match std::env::var("SPOTICK_TEST_SEED") {
+            Ok(seed) => seed.parse()?,
+            Err(_) => StdRng::from_os_rng().next_u64(),
+        };
+        println!("Seed: {}", seed);
+        let mut rng = StdRng::seed_from_u64(seed);
+        let valid = serde_json::to_vec(&TestSettings::default())?;
+        let app_settings = AppSettings::<TestSettings>::new(&ctx.path)?;
+
+        for _ in 0..500 {
+            // Either garble a few bytes of a valid file or use plain noise
+            let mut contents = valid.clone();
+            if rng.random_bool(0.5) {
+                for _ in 0..=rng.next_u32() % 4 {
+                    let idx = rng.next_u32() as usize % contents.len();
+                    contents[idx] = rng.next_u32() as u8;
+                }
+            } else {
+                contents.resize(rng.next_u32() as usize % 256, 0);
+                rng.fill_bytes(&mut contents);
+            }
+            std::fs::write(&ctx.path, &contents)?;
+
+            // Loading may fail but must neither panic
+            // nor leave the settings in an unusable state
+            let _ = app_settings.write().await.load().await;
+            let sg = app_settings.read().await;
+            serde_json::to_vec(sg.get_settings())
+                .with_context(|| format!("Unusable settings loaded (seed {})", seed))?;
+        }
+
+        app_settings.write().await.save().await?;
+        Ok(())
+    }
 }
@@ -0,0 +1,93 @@
+use std::{path::PathBuf, sync::Arc};
+
+use anyhow::Result;
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::{
+    service::{AlbumCover, SharedMediaService},
+    settings::{get_data_dir, AppSettings},
+    storage::SharedStorage,
+};
+
+const TRACK_FILE: &str = "last-track.json";
+const COVER_FILE: &str = "last-cover.png";
+
+/// The last known track of a previous app run.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CachedTrack {
+    pub title: String,
+    pub artist: String,
+    pub album_title: String,
+    pub length: u64,
+    pub is_playing: bool,
+    pub has_cover: bool,
+}
+
+/// Persists the last known track and its cover between app runs,
+/// so it can be shown right away while the media session attaches.
+pub struct StateCache {
+    track: Arc<RwLock<AppSettings<Option<CachedTrack>>>>,
+    cover_path: PathBuf,
+}
+
+impl StateCache {
+    /// Keeps the track in the [storage] and its cover in the data directory.
+    pub fn new(storage: SharedStorage) -> Self {
+        StateCache {
+            track: AppSettings::with_storage(storage, TRACK_FILE),
+            cover_path: get_data_dir().join(COVER_FILE),
+        }
+    }
+
+    /// Loads the track of the last app run and its cover, if any.
+    pub async fn load(&self) -> Result<Option<(CachedTrack, Option<RgbaImage>)>> {
+        self.track.write().await.load().await?;
+        let Some(track) = self.track.read().await.get_settings().clone() else {
+            return Ok(None);
+        };
+
+        let cover = if track.has_cover {
+            match image::open(&self.cover_path) {
+                Ok(img) => Some(img.to_rgba8()),
+                Err(e) => {
+                    log::warn!("Could not load cached cover: {}", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        Ok(Some((track, cover)))
+    }
+
+    /// Saves the current track of the [media_service].
+    pub async fn save(&self, media_service: SharedMediaService) -> Result<()> {
+        let srv = media_service.read().await;
+        let track = match srv.current_track() {
+            Some(track) => {
+                let has_cover = if let AlbumCover::Image(img) = &track.album_cover {
+                    img.save(&self.cover_path)?;
+                    true
+                } else {
+                    false
+                };
+
+                Some(CachedTrack {
+                    title: track.title.clone(),
+                    artist: track.artist.clone(),
+                    album_title: track.album_title.clone(),
+                    length: track.length,
+                    is_playing: srv.current_playback_state().is_playing,
+                    has_cover,
+                })
+            }
+            None => None,
+        };
+
+        let mut sg = self.track.write().await;
+        *sg.get_settings_mut() = track;
+        sg.save().await
+    }
+}
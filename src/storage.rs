@@ -0,0 +1,166 @@
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use tokio::io::AsyncWriteExt;
+
+#[cfg(windows)]
+use crate::settings::get_data_dir;
+
+pub type SharedStorage = Arc<dyn Storage>;
+
+/// Numbers the temporary files of [FileStorage::write], see there.
+static TMP_FILE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Persists the data of the app (settings, history,...) by name (e.g. "settings.json").
+/// Implementations must make [Storage::write] atomic, so a crash never leaves a mix of
+/// old and new contents behind.
+/// The contents aren't checksummed: Every owner parses what it reads anyway and already
+/// handles damaged contents (e.g. edited by hand) - settings fall back to their backup
+/// and unreadable history records are skipped.
+#[async_trait]
+pub trait Storage: Send + Sync {
+    /// Reads the whole contents of [name]. Returns [None] if it doesn't exist.
+    async fn read(&self, name: &str) -> Result<Option<Vec<u8>>>;
+
+    /// Replaces the contents of [name] as a whole.
+    async fn write(&self, name: &str, contents: &[u8]) -> Result<()>;
+
+    /// Appends a [record] to [name] (e.g. a line of a log), creating it if needed.
+    /// A crash may cut off the last record.
+    #[cfg(windows)]
+    async fn append(&self, name: &str, record: &[u8]) -> Result<()>;
+
+    /// Whether [name] exists.
+    async fn exists(&self, name: &str) -> Result<bool> {
+        Ok(self.read(name).await?.is_some())
+    }
+}
+
+/// Stores everything as files in one directory.
+pub struct FileStorage {
+    dir: PathBuf,
+}
+
+impl FileStorage {
+    pub fn new(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        Ok(FileStorage { dir })
+    }
+
+    /// Storage in the data directory of the app, see [get_data_dir].
+    #[cfg(windows)]
+    pub fn data_dir() -> Result<SharedStorage> {
+        Ok(Arc::new(FileStorage::new(get_data_dir())?))
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(name)
+    }
+}
+
+#[async_trait]
+impl Storage for FileStorage {
+    async fn read(&self, name: &str) -> Result<Option<Vec<u8>>> {
+        match tokio::fs::read(self.path(name)).await {
+            Ok(contents) => Ok(Some(contents)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Writes to a temporary file first which then replaces the previous one.
+    /// Every write has its own temporary file (by process and counter),
+    /// so concurrent writes of [name] can't clobber each other's contents.
+    async fn write(&self, name: &str, contents: &[u8]) -> Result<()> {
+        let path = self.path(name);
+        let tmp_path = self.path(&format!(
+            "{}.{}-{}.tmp",
+            name,
+            std::process::id(),
+            TMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let res = async {
+            let mut file = tokio::fs::File::create(&tmp_path).await?;
+            file.write_all(contents).await?;
+            file.sync_all().await?;
+            drop(file);
+            tokio::fs::rename(&tmp_path, &path).await
+        }
+        .await;
+        if res.is_err() {
+            let _ = tokio::fs::remove_file(&tmp_path).await;
+        }
+        Ok(res?)
+    }
+
+    #[cfg(windows)]
+    async fn append(&self, name: &str, record: &[u8]) -> Result<()> {
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path(name))
+            .await?;
+        file.write_all(record).await?;
+        Ok(())
+    }
+
+    async fn exists(&self, name: &str) -> Result<bool> {
+        Ok(tokio::fs::try_exists(self.path(name)).await?)
+    }
+}
+
+/// Splits [path] into a [FileStorage] of its directory and the file name within.
+pub fn file_storage_of(path: &Path) -> Result<(SharedStorage, String)> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .ok_or_else(|| anyhow::anyhow!("{:?} is no file path", path))?;
+    Ok((Arc::new(FileStorage::new(dir)?), name))
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::ensure;
+
+    use super::*;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn concurrent_writes_keep_whole_contents() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("spotick-test/storage-{}", std::process::id()));
+        let storage = Arc::new(FileStorage::new(&dir)?);
+        let writes = (0..16u8).map(|i| {
+            let storage = storage.clone();
+            tokio::spawn(async move { storage.write("data", &[i; 4096]).await })
+        });
+        for write in writes.collect::<Vec<_>>() {
+            write.await??;
+        }
+
+        let contents = storage.read("data").await?.unwrap_or_default();
+        ensure!(contents.len() == 4096, "Contents cut off");
+        ensure!(
+            contents.iter().all(|b| *b == contents[0]),
+            "Contents of different writes mixed"
+        );
+        let names = std::fs::read_dir(&dir)?
+            .map(|entry| Ok(entry?.file_name().to_string_lossy().to_string()))
+            .collect::<Result<Vec<_>>>()?;
+        ensure!(
+            names == ["data"],
+            "Temporary files left behind: {:?}",
+            names
+        );
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
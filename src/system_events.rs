@@ -0,0 +1,354 @@
+use std::{cell::RefCell, sync::Arc, time::Duration};
+
+use tokio::sync::{
+    mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+    watch::{channel, Receiver},
+};
+use windows::{
+    core::{w, BOOL, PCWSTR},
+    Foundation::TypedEventHandler,
+    Win32::{
+        Devices::Display::{
+            GetDisplayConfigBufferSizes, QueryDisplayConfig, DISPLAYCONFIG_MODE_INFO,
+            DISPLAYCONFIG_PATH_INFO, DISPLAYCONFIG_TOPOLOGY_CLONE, DISPLAYCONFIG_TOPOLOGY_ID,
+            QDC_DATABASE_CURRENT,
+        },
+        Foundation::{HINSTANCE, HWND, LPARAM, LRESULT, RECT, WPARAM},
+        Graphics::Gdi::{EnumDisplayMonitors, GetMonitorInfoW, HDC, HMONITOR, MONITORINFO},
+        System::LibraryLoader::GetModuleHandleW,
+        UI::{
+            Input::KeyboardAndMouse::{
+                RegisterHotKey, UnregisterHotKey, HOT_KEY_MODIFIERS, MOD_NOREPEAT,
+            },
+            Shell::{
+                SHQueryUserNotificationState, QUNS_BUSY, QUNS_PRESENTATION_MODE,
+                QUNS_RUNNING_D3D_FULL_SCREEN,
+            },
+            WindowsAndMessaging::{
+                CreateWindowExW, DefWindowProcW, DestroyWindow, DispatchMessageW, GetMessageW,
+                PostQuitMessage, RegisterClassW, MSG, WM_DISPLAYCHANGE, WM_HOTKEY, WNDCLASSW,
+                WS_EX_TOOLWINDOW, WS_OVERLAPPED,
+            },
+        },
+    },
+    UI::ViewManagement::{UIColorType, UISettings},
+};
+
+use crate::ui::{
+    placement::ScreenArea,
+    theme::{HexColor, SystemColors},
+};
+
+/// Windows doesn't notify about changes of the notification state, so it is polled.
+const POLL_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Watches whether the user is presenting while [enabled] holds true, otherwise it is
+/// always false: A full screen app (e.g. a PowerPoint slideshow) is running, the presentation
+/// settings of Windows are on, or the screen is duplicated (e.g. to a projector).
+pub fn watch_presenting(mut enabled: Receiver<bool>) -> Receiver<bool> {
+    let (tx, rx) = channel(false);
+    tokio::spawn(async move {
+        let mut display_changes = match watch_display_changes() {
+            Ok(display_changes) => Some(display_changes),
+            Err(e) => {
+                log::warn!("Could not watch display changes: {}", e);
+                None
+            }
+        };
+        // Only checked again after the displays changed, if that can be watched
+        let mut duplicated = None;
+        loop {
+            let presenting = *enabled.borrow_and_update() && {
+                if display_changes.is_none() {
+                    duplicated = None;
+                }
+                is_notification_state_busy()
+                    || *duplicated.get_or_insert_with(is_display_duplicated)
+            };
+            let changed = tx.send_if_modified(|current| {
+                let changed = *current != presenting;
+                *current = presenting;
+                changed
+            });
+            if changed {
+                log::info!("Presenting: {}", presenting);
+            }
+
+            let poll = *enabled.borrow();
+            let display_changed = async {
+                match display_changes.as_mut() {
+                    Some(display_changes) => display_changes.recv().await,
+                    None => std::future::pending().await,
+                }
+            };
+            tokio::select! {
+                res = enabled.changed() => if res.is_err() {
+                    break;
+                },
+                Some(()) = display_changed => duplicated = None,
+                _ = tokio::time::sleep(POLL_INTERVAL), if poll => {}
+                _ = tx.closed() => break,
+            }
+        }
+    });
+    rx
+}
+
+/// Whether Windows itself holds back notifications for a full screen app or presentation.
+fn is_notification_state_busy() -> bool {
+    match unsafe { SHQueryUserNotificationState() } {
+        Ok(state) => [
+            QUNS_BUSY,
+            QUNS_RUNNING_D3D_FULL_SCREEN,
+            QUNS_PRESENTATION_MODE,
+        ]
+        .contains(&state),
+        Err(e) => {
+            log::debug!("Could not query notification state: {}", e);
+            false
+        }
+    }
+}
+
+fn is_display_duplicated() -> bool {
+    let mut num_paths = 0;
+    let mut num_modes = 0;
+    unsafe {
+        if GetDisplayConfigBufferSizes(QDC_DATABASE_CURRENT, &mut num_paths, &mut num_modes)
+            .is_err()
+        {
+            return false;
+        }
+
+        let mut paths = vec![DISPLAYCONFIG_PATH_INFO::default(); num_paths as usize];
+        let mut modes = vec![DISPLAYCONFIG_MODE_INFO::default(); num_modes as usize];
+        let mut topology = DISPLAYCONFIG_TOPOLOGY_ID::default();
+        let res = QueryDisplayConfig(
+            QDC_DATABASE_CURRENT,
+            &mut num_paths,
+            paths.as_mut_ptr(),
+            &mut num_modes,
+            modes.as_mut_ptr(),
+            Some(&mut topology),
+        );
+        res.is_ok() && topology == DISPLAYCONFIG_TOPOLOGY_CLONE
+    }
+}
+
+/// Yields every time the displays change (e.g. the screen is duplicated to a projector).
+/// Windows only broadcasts this to top-level windows, so a hidden one is created for it.
+fn watch_display_changes() -> windows::core::Result<UnboundedReceiver<()>> {
+    thread_local! {
+        static DISPLAY_CHANGED: RefCell<Option<UnboundedSender<()>>> = const { RefCell::new(None) };
+    }
+
+    unsafe extern "system" fn window_proc(
+        window: HWND,
+        msg: u32,
+        wparam: WPARAM,
+        lparam: LPARAM,
+    ) -> LRESULT {
+        if msg == WM_DISPLAYCHANGE {
+            let closed = DISPLAY_CHANGED
+                .with_borrow(|tx| tx.as_ref().is_some_and(|tx| tx.send(()).is_err()));
+            if closed {
+                PostQuitMessage(0);
+            }
+        }
+        DefWindowProcW(window, msg, wparam, lparam)
+    }
+
+    unsafe fn create_window() -> windows::core::Result<HWND> {
+        let instance: HINSTANCE = GetModuleHandleW(PCWSTR::null())?.into();
+        let class = WNDCLASSW {
+            lpfnWndProc: Some(window_proc),
+            hInstance: instance,
+            lpszClassName: w!("SpotickDisplayChanges"),
+            ..Default::default()
+        };
+        if RegisterClassW(&class) == 0 {
+            return Err(windows::core::Error::from_win32());
+        }
+        // Never shown
+        CreateWindowExW(
+            WS_EX_TOOLWINDOW,
+            class.lpszClassName,
+            PCWSTR::null(),
+            WS_OVERLAPPED,
+            0,
+            0,
+            0,
+            0,
+            None,
+            None,
+            Some(instance),
+            None,
+        )
+    }
+
+    let (tx, rx) = unbounded_channel();
+    let (created_tx, created_rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || unsafe {
+        DISPLAY_CHANGED.set(Some(tx));
+        let window = match create_window() {
+            Ok(window) => {
+                let _ = created_tx.send(Ok(()));
+                window
+            }
+            Err(e) => {
+                let _ = created_tx.send(Err(e));
+                return;
+            }
+        };
+
+        let mut msg = MSG::default();
+        // Fails with -1 and ends with 0 (WM_QUIT)
+        while GetMessageW(&mut msg, None, 0, 0).0 > 0 {
+            DispatchMessageW(&msg);
+        }
+        let _ = DestroyWindow(window);
+    });
+
+    created_rx
+        .recv()
+        .unwrap_or_else(|_| Err(windows::core::Error::from_win32()))?;
+    Ok(rx)
+}
+
+pub fn read_system_colors() -> windows::core::Result<SystemColors> {
+    let settings = UISettings::new()?;
+    let background = settings.GetColorValue(UIColorType::Background)?;
+    let mut accent = [HexColor::rgb(0); 7];
+    let shades = [
+        UIColorType::AccentDark3,
+        UIColorType::AccentDark2,
+        UIColorType::AccentDark1,
+        UIColorType::Accent,
+        UIColorType::AccentLight1,
+        UIColorType::AccentLight2,
+        UIColorType::AccentLight3,
+    ];
+    for (color, shade) in accent.iter_mut().zip(shades) {
+        *color = settings.GetColorValue(shade)?.into();
+    }
+    Ok(SystemColors {
+        // The app background is black in dark mode and white otherwise
+        dark_mode: (background.R as u32 + background.G as u32 + background.B as u32) < 384,
+        accent,
+    })
+}
+
+/// Watches the colors of Windows (e.g. switching to dark mode).
+/// [None] if they can't be read.
+pub fn watch_system_colors() -> Receiver<Option<SystemColors>> {
+    let read_colors = || match read_system_colors() {
+        Ok(colors) => Some(colors),
+        Err(e) => {
+            log::warn!("Could not read system colors: {}", e);
+            None
+        }
+    };
+
+    let (tx, rx) = channel(read_colors());
+    let tx = Arc::new(tx);
+    tokio::spawn(async move {
+        // Windows only notifies as long as the settings are kept
+        let settings = match UISettings::new() {
+            Ok(settings) => settings,
+            Err(e) => {
+                log::warn!("Could not watch system colors: {}", e);
+                return;
+            }
+        };
+        let token = settings.ColorValuesChanged(&TypedEventHandler::new({
+            let tx = tx.clone();
+            move |_, _| {
+                let colors = read_colors();
+                tx.send_if_modified(|current| {
+                    let changed = *current != colors;
+                    *current = colors;
+                    changed
+                });
+                Ok(())
+            }
+        }));
+
+        tx.closed().await;
+        if let Ok(token) = token {
+            let _ = settings.RemoveColorValuesChanged(token);
+        }
+    });
+    rx
+}
+
+/// Registers a global hotkey (e.g. Ctrl+Alt+O) and yields every time it is pressed,
+/// no matter which window has the focus. Fails if another app already took the hotkey.
+pub fn watch_hotkey(
+    modifiers: HOT_KEY_MODIFIERS,
+    key: u32,
+) -> windows::core::Result<UnboundedReceiver<()>> {
+    const HOTKEY_ID: i32 = 1;
+    let (tx, rx) = unbounded_channel();
+    let (registered_tx, registered_rx) = std::sync::mpsc::channel();
+    // Hotkeys without a window are posted to the message queue of the registering thread
+    std::thread::spawn(move || unsafe {
+        let registered = RegisterHotKey(None, HOTKEY_ID, modifiers | MOD_NOREPEAT, key);
+        let failed = registered.is_err();
+        let _ = registered_tx.send(registered);
+        if failed {
+            return;
+        }
+
+        let mut msg = MSG::default();
+        // Fails with -1 and ends with 0 (WM_QUIT)
+        while GetMessageW(&mut msg, None, 0, 0).0 > 0 {
+            if msg.message == WM_HOTKEY && tx.send(()).is_err() {
+                break;
+            }
+        }
+        let _ = UnregisterHotKey(None, HOTKEY_ID);
+    });
+
+    registered_rx
+        .recv()
+        .unwrap_or_else(|_| Err(windows::core::Error::from_win32()))?;
+    Ok(rx)
+}
+
+/// The areas of all connected monitors.
+pub fn screen_areas() -> Vec<ScreenArea> {
+    unsafe extern "system" fn add_area(
+        monitor: HMONITOR,
+        _: HDC,
+        _: *mut RECT,
+        areas: LPARAM,
+    ) -> BOOL {
+        let areas = &mut *(areas.0 as *mut Vec<ScreenArea>);
+        let mut info = MONITORINFO {
+            cbSize: std::mem::size_of::<MONITORINFO>() as u32,
+            ..Default::default()
+        };
+        if GetMonitorInfoW(monitor, &mut info).as_bool() {
+            let work = info.rcWork;
+            areas.push(ScreenArea {
+                x: work.left,
+                y: work.top,
+                width: work.right - work.left,
+                height: work.bottom - work.top,
+            });
+        }
+        // Continue with the next monitor
+        true.into()
+    }
+
+    let mut areas: Vec<ScreenArea> = Vec::new();
+    unsafe {
+        let _ = EnumDisplayMonitors(
+            None,
+            None,
+            Some(add_area),
+            LPARAM(&mut areas as *mut _ as isize),
+        );
+    }
+    areas
+}
@@ -1,10 +1,32 @@
+pub mod announcer;
+pub mod placement;
+#[cfg(windows)]
+pub mod romanization;
+pub mod theme;
+pub mod toast;
+#[cfg(windows)]
 pub mod window;
 
+#[cfg(windows)]
+use std::path::Path;
+
+#[cfg(windows)]
 use anyhow::Result;
-use image::RgbaImage;
+#[cfg(windows)]
+use chrono::Local;
+#[cfg(windows)]
+use image::{
+    imageops::{self, FilterType},
+    RgbaImage,
+};
 
+#[cfg(windows)]
 use crate::ui::window::get_window_creation_settings;
 
+/// Larger placeholder images are scaled down when cached
+#[cfg(windows)]
+const PLACEHOLDER_MAX_SIZE: u32 = 512;
+
 #[macro_export]
 macro_rules! callback {
     ($prop:ident, |$app_ref:ident $(,)? $( $params:ident ),*| $handler:block) => {{
@@ -18,6 +40,7 @@ macro_rules! callback {
     }};
 }
 
+#[cfg(windows)]
 pub fn init_backend() -> Result<()> {
     let window_backend = i_slint_backend_winit::Backend::builder()
         .with_window_attributes_hook(|_| get_window_creation_settings().get_settings())
@@ -26,9 +49,38 @@ pub fn init_backend() -> Result<()> {
     Ok(())
 }
 
+/// Formats a duration given in [seconds] like a media player would (e.g. 3:07 or 1:02:07).
+#[cfg(windows)]
+pub fn format_duration(seconds: u64) -> String {
+    let (hours, minutes, seconds) = (seconds / 3600, (seconds / 60) % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
+}
+
+/// Upscales [img] so that its smaller side is at least [min_size] pixels
+/// using bicubic interpolation followed by a light sharpening.
+/// Returns [None] if [img] is already large enough.
+#[cfg(windows)]
+pub fn upscale_image(img: &RgbaImage, min_size: u32) -> Option<RgbaImage> {
+    let smaller_side = img.width().min(img.height());
+    if smaller_side == 0 || smaller_side >= min_size {
+        return None;
+    }
+
+    let factor = min_size as f32 / smaller_side as f32;
+    let width = (img.width() as f32 * factor).round() as u32;
+    let height = (img.height() as f32 * factor).round() as u32;
+    let resized = imageops::resize(img, width, height, FilterType::CatmullRom);
+    Some(imageops::unsharpen(&resized, 1.0, 2))
+}
+
 /// Rounds the corners of [img] with the given [radius].
 /// This is a naive implementation running on the CPU and not quite efficient.
 /// Don't call it frequently.
+#[cfg(windows)]
 pub fn apply_border_radius(img: &mut RgbaImage, radius: u32) {
     let nearest_corner_distance = |coord, axis_length| {
         if coord < radius {
@@ -59,3 +111,17 @@ pub fn apply_border_radius(img: &mut RgbaImage, radius: u32) {
         }
     }
 }
+
+/// Checks that [source] is an image and caches it in the [data_dir]
+/// to be shown instead of a cover, so it stays even if [source] is moved.
+/// Returns the name of the cached file, which is new for every call.
+#[cfg(windows)]
+pub fn cache_placeholder(source: &Path, data_dir: &Path) -> Result<String> {
+    let mut img = image::open(source)?;
+    if img.width() > PLACEHOLDER_MAX_SIZE || img.height() > PLACEHOLDER_MAX_SIZE {
+        img = img.thumbnail(PLACEHOLDER_MAX_SIZE, PLACEHOLDER_MAX_SIZE);
+    }
+    let name = format!("placeholder-{}.png", Local::now().timestamp_millis());
+    img.to_rgba8().save(data_dir.join(&name))?;
+    Ok(name)
+}
@@ -0,0 +1,118 @@
+#[cfg(windows)]
+use anyhow::Result;
+#[cfg(windows)]
+use i_slint_backend_winit::winit::raw_window_handle::{HasWindowHandle, RawWindowHandle};
+use serde::{Deserialize, Serialize};
+#[cfg(windows)]
+use windows::{
+    core::BSTR,
+    Win32::{
+        Foundation::HWND,
+        UI::Accessibility::{
+            NotificationKind_Other, NotificationProcessing_ImportantMostRecent,
+            UiaClientsAreListening, UiaHostProviderFromHwnd, UiaRaiseNotificationEvent,
+        },
+    },
+};
+
+#[cfg(windows)]
+use crate::service::TrackIdentity;
+
+/// Lets screen readers group (and drop outdated) announcements of Spotick.
+#[cfg(windows)]
+const ACTIVITY_ID: &str = "SpotickPlayback";
+
+/// How much of the playback is announced to screen readers.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+pub enum AnnouncementVerbosity {
+    Off,
+    /// New tracks (e.g. "Song by Artist")
+    #[default]
+    Tracks,
+    /// New tracks and play state changes ("Playing" or "Paused")
+    All,
+}
+
+/// Turns playback changes into messages for screen readers.
+/// Media sessions often report the same track or play state multiple times,
+/// so only actual changes are announced.
+#[cfg(windows)]
+#[derive(Default)]
+pub struct PlaybackAnnouncer {
+    last_track: Option<TrackIdentity>,
+    last_playing: Option<bool>,
+}
+
+#[cfg(windows)]
+impl PlaybackAnnouncer {
+    /// Gets the message announcing the current track, if it changed.
+    /// [track] is the title, artist and length of the current track.
+    pub fn track_changed(
+        &mut self,
+        verbosity: AnnouncementVerbosity,
+        track: Option<(&str, &str, u64)>,
+    ) -> Option<String> {
+        let identity =
+            track.map(|(title, artist, length)| TrackIdentity::new(title, artist, length));
+        let same_track = match (&self.last_track, &identity) {
+            (Some(last), Some(identity)) => last.matches(identity),
+            (None, None) => true,
+            _ => false,
+        };
+        if same_track {
+            return None;
+        }
+        self.last_track = identity;
+
+        let (title, artist, _) = track?;
+        match verbosity {
+            AnnouncementVerbosity::Off => None,
+            _ if artist.is_empty() => Some(title.to_string()),
+            _ => Some(format!("{} by {}", title, artist)),
+        }
+    }
+
+    /// Gets the message announcing the play state, if it changed.
+    pub fn playback_changed(
+        &mut self,
+        verbosity: AnnouncementVerbosity,
+        is_playing: bool,
+    ) -> Option<String> {
+        // The initial state isn't a change
+        let previous = self.last_playing.replace(is_playing);
+        if previous.is_none() || previous == Some(is_playing) {
+            return None;
+        }
+
+        match verbosity {
+            AnnouncementVerbosity::All if is_playing => Some("Playing".into()),
+            AnnouncementVerbosity::All => Some("Paused".into()),
+            _ => None,
+        }
+    }
+}
+
+/// Lets screen readers read out [msg] without the [window] having the focus,
+/// similar to a live region on the web.
+/// Does nothing if no screen reader (or other UI Automation client) is running.
+#[cfg(windows)]
+pub fn announce(window: &slint::Window, msg: &str) -> Result<()> {
+    if !unsafe { UiaClientsAreListening() }.as_bool() {
+        return Ok(());
+    }
+
+    let RawWindowHandle::Win32(handle) = window.window_handle().window_handle()?.as_raw() else {
+        return Ok(());
+    };
+    unsafe {
+        let provider = UiaHostProviderFromHwnd(HWND(handle.hwnd.get() as *mut _))?;
+        UiaRaiseNotificationEvent(
+            &provider,
+            NotificationKind_Other,
+            NotificationProcessing_ImportantMostRecent,
+            &BSTR::from(msg),
+            &BSTR::from(ACTIVITY_ID),
+        )?;
+    }
+    Ok(())
+}
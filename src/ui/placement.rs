@@ -0,0 +1,180 @@
+use serde::{Deserialize, Serialize};
+#[cfg(windows)]
+use slint::{PhysicalPosition, PhysicalSize};
+
+/// Layout of the main window, switched by double-clicking it.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum WindowLayout {
+    /// Card with the cover, the progress and all controls
+    #[default]
+    Full,
+    /// Bar with only the title and the playback controls
+    Compact,
+}
+
+/// Area of a monitor in physical pixels, without the taskbar.
+#[cfg(windows)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScreenArea {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+/// Squared distance of the point ([x], [y]) to [area], 0 if it is inside.
+#[cfg(windows)]
+fn distance(area: &ScreenArea, x: i32, y: i32) -> i64 {
+    let dx = (area.x - x).max(x - (area.x + area.width)).max(0) as i64;
+    let dy = (area.y - y).max(y - (area.y + area.height)).max(0) as i64;
+    dx * dx + dy * dy
+}
+
+/// The area the center of the window is on, or the nearest one if it is on none.
+#[cfg(windows)]
+fn area_of(
+    pos: PhysicalPosition,
+    size: PhysicalSize,
+    areas: &[ScreenArea],
+) -> Option<(&ScreenArea, bool)> {
+    let x = pos.x + size.width as i32 / 2;
+    let y = pos.y + size.height as i32 / 2;
+    areas
+        .iter()
+        .map(|area| (area, distance(area, x, y)))
+        .min_by_key(|(_, distance)| *distance)
+        .map(|(area, distance)| (area, distance == 0))
+}
+
+/// Moves the window at [pos] flush to the edges (or corner) of its screen area
+/// it is within [margin] pixels of.
+#[cfg(windows)]
+pub fn snap_to_edges(
+    pos: PhysicalPosition,
+    size: PhysicalSize,
+    areas: &[ScreenArea],
+    margin: u32,
+) -> PhysicalPosition {
+    let Some((area, _)) = area_of(pos, size, areas) else {
+        return pos;
+    };
+    let margin = margin as i32;
+    let snap = |pos: i32, len: u32, start: i32, area_len: i32| {
+        let end = start + area_len - len as i32;
+        if (pos - start).abs() <= margin {
+            start
+        } else if (pos - end).abs() <= margin {
+            end
+        } else {
+            pos
+        }
+    };
+    PhysicalPosition::new(
+        snap(pos.x, size.width, area.x, area.width),
+        snap(pos.y, size.height, area.y, area.height),
+    )
+}
+
+/// Moves the window at [pos] onto the nearest screen area if its center is on none,
+/// e.g. because the monitor it was on is disconnected.
+#[cfg(windows)]
+pub fn clamp_to_screens(
+    pos: PhysicalPosition,
+    size: PhysicalSize,
+    areas: &[ScreenArea],
+) -> PhysicalPosition {
+    let Some((area, false)) = area_of(pos, size, areas) else {
+        return pos;
+    };
+    let clamp = |pos: i32, len: u32, start: i32, area_len: i32| {
+        pos.min(start + area_len - len as i32).max(start)
+    };
+    PhysicalPosition::new(
+        clamp(pos.x, size.width, area.x, area.width),
+        clamp(pos.y, size.height, area.y, area.height),
+    )
+}
+
+/// Identifies the arrangement of the monitors (e.g. "0,0,1920x1040;1920,0,2560x1400"),
+/// regardless of the order they are listed in.
+#[cfg(windows)]
+pub fn monitor_setup_key(areas: &[ScreenArea]) -> String {
+    let mut areas: Vec<String> = areas
+        .iter()
+        .map(|area| format!("{},{},{}x{}", area.x, area.y, area.width, area.height))
+        .collect();
+    areas.sort();
+    areas.join(";")
+}
+
+/// Key of the position saved for the main window in [layout] on the monitors of [areas].
+/// The full layout keeps the plain [monitor_setup_key] it had before there were layouts.
+#[cfg(windows)]
+pub fn position_key(layout: WindowLayout, areas: &[ScreenArea]) -> String {
+    match layout {
+        WindowLayout::Full => monitor_setup_key(areas),
+        WindowLayout::Compact => format!("compact:{}", monitor_setup_key(areas)),
+    }
+}
+
+#[cfg(all(test, windows))]
+mod test {
+    use super::*;
+
+    const AREAS: [ScreenArea; 2] = [
+        ScreenArea {
+            x: 0,
+            y: 0,
+            width: 1920,
+            height: 1040,
+        },
+        ScreenArea {
+            x: 1920,
+            y: -200,
+            width: 1080,
+            height: 1880,
+        },
+    ];
+    const SIZE: PhysicalSize = PhysicalSize {
+        width: 400,
+        height: 200,
+    };
+
+    #[test]
+    fn snaps_to_edges_of_current_screen() {
+        let snap = |x, y| snap_to_edges(PhysicalPosition::new(x, y), SIZE, &AREAS, 16);
+        assert_eq!(snap(10, -12), PhysicalPosition::new(0, 0));
+        assert_eq!(snap(1510, 500), PhysicalPosition::new(1520, 500));
+        assert_eq!(snap(700, 500), PhysicalPosition::new(700, 500));
+        // Bottom right corner of the second screen
+        assert_eq!(snap(2590, 1470), PhysicalPosition::new(2600, 1480));
+        assert_eq!(
+            snap_to_edges(PhysicalPosition::new(10, 10), SIZE, &[], 16),
+            PhysicalPosition::new(10, 10)
+        );
+    }
+
+    #[test]
+    fn clamps_to_connected_screens() {
+        let clamp = |x, y| clamp_to_screens(PhysicalPosition::new(x, y), SIZE, &AREAS);
+        // Partly off screen is fine as long as the center is on one
+        assert_eq!(clamp(-100, 900), PhysicalPosition::new(-100, 900));
+        // A third monitor right of the second got disconnected
+        assert_eq!(clamp(3500, 300), PhysicalPosition::new(2600, 300));
+        assert_eq!(clamp(-3000, 2000), PhysicalPosition::new(0, 840));
+    }
+
+    #[test]
+    fn monitor_setup_key_ignores_order() {
+        let reversed = [AREAS[1], AREAS[0]];
+        assert_eq!(monitor_setup_key(&AREAS), monitor_setup_key(&reversed));
+        assert_eq!(
+            monitor_setup_key(&AREAS),
+            "0,0,1920x1040;1920,-200,1080x1880"
+        );
+        assert_eq!(
+            position_key(WindowLayout::Compact, &AREAS[..1]),
+            "compact:0,0,1920x1040"
+        );
+    }
+}
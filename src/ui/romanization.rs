@@ -0,0 +1,89 @@
+/// Whether [text] contains letters of a non-Latin script (e.g. CJK or Cyrillic).
+/// Latin letters with diacritics (e.g. "é") don't count.
+fn has_non_latin_letters(text: &str) -> bool {
+    text.chars().any(|c| c.is_alphabetic() && !is_latin(c))
+}
+
+fn is_latin(c: char) -> bool {
+    matches!(
+        c as u32,
+        // Basic Latin up to Latin Extended-B
+        0..=0x024F
+            // IPA extensions
+            | 0x0250..=0x02AF
+            // Latin Extended Additional
+            | 0x1E00..=0x1EFF
+            // Latin Extended-C and D
+            | 0x2C60..=0x2C7F
+            | 0xA720..=0xA7FF
+            // Fullwidth Latin letters
+            | 0xFF21..=0xFF3A
+            | 0xFF41..=0xFF5A
+    )
+}
+
+/// Romanizes [text] if it contains letters of a non-Latin script (e.g. "Кино" becomes "Kino").
+/// Always [None] without the `transliteration` feature.
+pub fn romanize(text: &str) -> Option<String> {
+    if !has_non_latin_letters(text) {
+        return None;
+    }
+    transliterate(text)
+}
+
+/// NOTE: Kanji are transliterated by their Chinese reading.
+#[cfg(feature = "transliteration")]
+fn transliterate(text: &str) -> Option<String> {
+    // Most CJK characters are transliterated as separate words
+    let romanized = deunicode::deunicode(text)
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    (!romanized.is_empty()).then_some(romanized)
+}
+
+#[cfg(not(feature = "transliteration"))]
+fn transliterate(_text: &str) -> Option<String> {
+    None
+}
+
+/// The romanized [title] and [artist] of a track (e.g. "Gruppa krovi · Kino"),
+/// or an empty string if neither needs to be romanized.
+pub fn romanize_track(title: &str, artist: &str) -> String {
+    match (romanize(title), romanize(artist)) {
+        (None, None) => String::new(),
+        (romanized_title, romanized_artist) => format!(
+            "{} · {}",
+            romanized_title.as_deref().unwrap_or(title),
+            romanized_artist.as_deref().unwrap_or(artist)
+        ),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn detects_non_latin_letters() {
+        assert!(!has_non_latin_letters("Café del Mar (2024 Remaster)"));
+        assert!(!has_non_latin_letters("Ærøskøbing · Łódź"));
+        assert!(!has_non_latin_letters("♪ 123 !?"));
+        assert!(has_non_latin_letters("Группа крови"));
+        assert!(has_non_latin_letters("Mix: 夜に駆ける"));
+        assert!(has_non_latin_letters("Ελλάδα"));
+    }
+
+    #[cfg(feature = "transliteration")]
+    #[test]
+    fn romanizes_tracks() {
+        assert_eq!(romanize("Кино").as_deref(), Some("Kino"));
+        assert_eq!(romanize("Café"), None);
+        assert_eq!(
+            romanize_track("Группа крови", "Кино"),
+            "Gruppa krovi · Kino"
+        );
+        assert_eq!(romanize_track("Kino", "Кино"), "Kino · Kino");
+        assert_eq!(romanize_track("Yesterday", "The Beatles"), "");
+    }
+}
@@ -0,0 +1,360 @@
+use std::fmt::Display;
+
+#[cfg(windows)]
+use image::{imageops, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+#[cfg(windows)]
+const BLACK: HexColor = HexColor::rgb(0x000000);
+#[cfg(windows)]
+const WHITE: HexColor = HexColor::rgb(0xffffff);
+
+/// Color written as hex code in the settings (e.g. "#0b4cbc" or "#001b50e8").
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(try_from = "String", into = "String")]
+pub struct HexColor {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+    pub alpha: u8,
+}
+
+impl HexColor {
+    pub const fn rgb(rgb: u32) -> Self {
+        HexColor {
+            red: (rgb >> 16) as u8,
+            green: (rgb >> 8) as u8,
+            blue: rgb as u8,
+            alpha: 0xff,
+        }
+    }
+
+    /// Mixes this color with [other], [weight] being the share of this color (0 - 1).
+    /// Keeps the alpha of this color.
+    pub fn mix(self, other: HexColor, weight: f32) -> Self {
+        let mix = |a: u8, b: u8| (a as f32 * weight + b as f32 * (1.0 - weight)).round() as u8;
+        HexColor {
+            red: mix(self.red, other.red),
+            green: mix(self.green, other.green),
+            blue: mix(self.blue, other.blue),
+            alpha: self.alpha,
+        }
+    }
+
+    /// Perceived brightness from 0 (black) to 1 (white).
+    pub fn luminance(self) -> f32 {
+        let linear = |channel: u8| (channel as f32 / 255.0).powf(2.2);
+        0.2126 * linear(self.red) + 0.7152 * linear(self.green) + 0.0722 * linear(self.blue)
+    }
+}
+
+impl TryFrom<String> for HexColor {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let invalid = || format!("{:?} is no color like #rrggbb or #rrggbbaa", value);
+        let hex = value.strip_prefix('#').ok_or_else(invalid)?;
+        if !(hex.len() == 6 || hex.len() == 8) || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+        let channel = |idx: usize| u8::from_str_radix(&hex[idx..idx + 2], 16);
+        let channels = || -> Result<_, std::num::ParseIntError> {
+            let alpha = if hex.len() == 8 { channel(6)? } else { 0xff };
+            Ok(HexColor {
+                red: channel(0)?,
+                green: channel(2)?,
+                blue: channel(4)?,
+                alpha,
+            })
+        };
+        channels().map_err(|_| invalid())
+    }
+}
+
+impl Display for HexColor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.red, self.green, self.blue)?;
+        if self.alpha != 0xff {
+            write!(f, "{:02x}", self.alpha)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<HexColor> for String {
+    fn from(value: HexColor) -> Self {
+        value.to_string()
+    }
+}
+
+impl From<HexColor> for slint::Color {
+    fn from(value: HexColor) -> Self {
+        slint::Color::from_argb_u8(value.alpha, value.red, value.green, value.blue)
+    }
+}
+
+#[cfg(windows)]
+impl From<windows::UI::Color> for HexColor {
+    fn from(value: windows::UI::Color) -> Self {
+        HexColor {
+            red: value.R,
+            green: value.G,
+            blue: value.B,
+            alpha: value.A,
+        }
+    }
+}
+
+/// Colors picked in the personalization settings of Windows.
+#[cfg(windows)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SystemColors {
+    /// Apps are shown in dark mode
+    pub dark_mode: bool,
+    /// The accent color in the middle of its shades, from the darkest to the lightest
+    pub accent: [HexColor; 7],
+}
+
+/// Colors, font and shape of the main window.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Theme {
+    /// Gradient of the background from the top left to the bottom right
+    pub background: [HexColor; 3],
+    /// Popups and overlays (e.g. the track details)
+    pub surface: HexColor,
+    pub foreground: HexColor,
+    /// Less important text (e.g. the album)
+    pub foreground_dim: HexColor,
+    /// Highlights (e.g. hovered menu entries)
+    pub accent: HexColor,
+    /// Font of all text, the default font of Slint if empty
+    #[serde(default)]
+    pub font_family: String,
+    /// Logical pixels
+    pub corner_radius: f32,
+    /// Opacity of the background (0 - 1)
+    pub opacity: f32,
+}
+
+#[cfg(windows)]
+impl Theme {
+    pub fn dark() -> Self {
+        Theme {
+            background: [
+                HexColor::rgb(0x001b50),
+                HexColor::rgb(0x2a467c),
+                HexColor::rgb(0x0c58db),
+            ],
+            surface: HexColor::rgb(0x383838),
+            foreground: HexColor::rgb(0xffffff),
+            foreground_dim: HexColor::rgb(0xd3d3d3),
+            accent: HexColor::rgb(0x0b4cbc),
+            font_family: String::new(),
+            corner_radius: 16.0,
+            opacity: 1.0,
+        }
+    }
+
+    pub fn light() -> Self {
+        Theme {
+            background: [
+                HexColor::rgb(0xe3ecfb),
+                HexColor::rgb(0xc9daf6),
+                HexColor::rgb(0x9dbcf2),
+            ],
+            surface: HexColor::rgb(0xf7f7f7),
+            foreground: HexColor::rgb(0x1c1c1c),
+            foreground_dim: HexColor::rgb(0x4a4a4a),
+            accent: HexColor::rgb(0x9ec1f7),
+            font_family: String::new(),
+            corner_radius: 16.0,
+            opacity: 1.0,
+        }
+    }
+
+    /// Dark or light theme in the accent color of Windows.
+    pub fn system(colors: &SystemColors) -> Self {
+        let [dark3, dark2, dark1, accent, light1, light2, light3] = colors.accent;
+        if colors.dark_mode {
+            Theme {
+                background: [dark3, dark2, dark1],
+                accent,
+                ..Theme::dark()
+            }
+        } else {
+            Theme {
+                background: [light3, light2, light1],
+                accent: light2,
+                ..Theme::light()
+            }
+        }
+    }
+
+    /// Tints the background and highlights in the [color] (e.g. of the cover).
+    /// Keeps a dark theme dark and a light theme light,
+    /// but switches the text color if it would be hard to read.
+    pub fn tinted(self, color: HexColor) -> Self {
+        let dark = self.foreground.luminance() > 0.5;
+        let (background, accent) = if dark {
+            (
+                [
+                    color.mix(BLACK, 0.25),
+                    color.mix(BLACK, 0.45),
+                    color.mix(BLACK, 0.7),
+                ],
+                color.mix(BLACK, 0.6),
+            )
+        } else {
+            (
+                [
+                    color.mix(WHITE, 0.15),
+                    color.mix(WHITE, 0.3),
+                    color.mix(WHITE, 0.5),
+                ],
+                color.mix(WHITE, 0.4),
+            )
+        };
+        // The text is in front of the center and the end of the gradient
+        let text_background = (background[1].luminance() + background[2].luminance()) / 2.0;
+        let (foreground, foreground_dim) = if text_background > 0.25 {
+            (HexColor::rgb(0x1c1c1c), color.mix(BLACK, 0.3))
+        } else {
+            (WHITE, color.mix(WHITE, 0.3))
+        };
+        Theme {
+            background,
+            foreground,
+            foreground_dim,
+            accent,
+            ..self
+        }
+    }
+}
+
+/// The most prominent color of [img], preferring colorful over gray pixels.
+/// [None] if the image is fully transparent.
+#[cfg(windows)]
+pub fn dominant_color(img: &RgbaImage) -> Option<HexColor> {
+    let small = imageops::thumbnail(img, 16, 16);
+    // Similar colors (same upper 3 bits per channel) are summed up in a bucket
+    let mut buckets = vec![(0f32, [0f32; 3]); 512];
+    for pixel in small.pixels() {
+        let [red, green, blue, alpha] = pixel.0;
+        if alpha < 128 {
+            continue;
+        }
+        let max = red.max(green).max(blue) as f32;
+        let min = red.min(green).min(blue) as f32;
+        let saturation = if max > 0.0 { (max - min) / max } else { 0.0 };
+        // Dark pixels are never that colorful, even if saturated
+        let weight = 0.1 + saturation * max / 255.0;
+
+        let idx = (red as usize >> 5) << 6 | (green as usize >> 5) << 3 | (blue as usize >> 5);
+        let (bucket_weight, sums) = &mut buckets[idx];
+        *bucket_weight += weight;
+        for (sum, channel) in sums.iter_mut().zip([red, green, blue]) {
+            *sum += channel as f32 * weight;
+        }
+    }
+
+    let (weight, sums) = buckets.into_iter().max_by(|a, b| a.0.total_cmp(&b.0))?;
+    if weight == 0.0 {
+        return None;
+    }
+    let [red, green, blue] = sums.map(|sum| (sum / weight).round() as u8);
+    Some(HexColor {
+        red,
+        green,
+        blue,
+        alpha: 0xff,
+    })
+}
+
+/// The theme picked in the settings.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub enum ThemeSetting {
+    #[default]
+    Dark,
+    Light,
+    /// Follows the dark mode and the accent color of Windows
+    Auto,
+    /// Edited in the settings file
+    Custom(Theme),
+}
+
+#[cfg(windows)]
+impl ThemeSetting {
+    /// The actual theme given the current [system_colors].
+    /// [ThemeSetting::Auto] falls back to [ThemeSetting::Dark] if they are unknown.
+    pub fn resolve(&self, system_colors: Option<&SystemColors>) -> Theme {
+        match self {
+            ThemeSetting::Dark => Theme::dark(),
+            ThemeSetting::Light => Theme::light(),
+            ThemeSetting::Auto => system_colors.map(Theme::system).unwrap_or_else(Theme::dark),
+            ThemeSetting::Custom(theme) => theme.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_hex_colors() {
+        let color = HexColor::try_from("#0B4cbc".to_string()).unwrap();
+        assert_eq!(color, HexColor::rgb(0x0b4cbc));
+        assert_eq!(String::from(color), "#0b4cbc");
+
+        let transparent = HexColor::try_from("#001b50e8".to_string()).unwrap();
+        assert_eq!(transparent.alpha, 0xe8);
+        assert_eq!(transparent.to_string(), "#001b50e8");
+
+        for invalid in ["0b4cbc", "#0b4cb", "#0b4cbcx", "#+b4cbc", "#ü0b4cbc"] {
+            assert!(HexColor::try_from(invalid.to_string()).is_err());
+        }
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn custom_theme_from_settings() {
+        let json = r##"{"Custom": {
+            "background": ["#000000", "#101010", "#202020"],
+            "surface": "#303030",
+            "foreground": "#ffffff",
+            "foreground_dim": "#c0c0c0",
+            "accent": "#ff000080",
+            "corner_radius": 0,
+            "opacity": 0.8
+        }}"##;
+        let setting: ThemeSetting = serde_json::from_str(json).unwrap();
+        let theme = setting.resolve(None);
+        assert_eq!(theme.accent.alpha, 0x80);
+        assert_eq!(theme.font_family, "");
+        assert_eq!(ThemeSetting::Auto.resolve(None), Theme::dark());
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn tints_in_cover_color() {
+        // Mostly gray with a red stripe
+        let cover = RgbaImage::from_fn(64, 64, |x, _| {
+            if x < 16 {
+                image::Rgba([200, 20, 20, 255])
+            } else {
+                image::Rgba([128, 128, 128, 255])
+            }
+        });
+        assert_eq!(dominant_color(&cover), Some(HexColor::rgb(0xc81414)));
+        assert_eq!(dominant_color(&RgbaImage::new(8, 8)), None);
+
+        let dark = Theme::dark().tinted(HexColor::rgb(0xc81414));
+        assert_eq!(dark.foreground, WHITE);
+        assert!(dark.background[1].luminance() < 0.1);
+        // Bright colors need dark text
+        let yellow = Theme::dark().tinted(HexColor::rgb(0xffff00));
+        assert_eq!(yellow.foreground, HexColor::rgb(0x1c1c1c));
+        let light = Theme::light().tinted(HexColor::rgb(0xc81414));
+        assert_eq!(light.foreground, HexColor::rgb(0x1c1c1c));
+    }
+}
@@ -0,0 +1,291 @@
+#[cfg(windows)]
+use std::path::Path;
+
+#[cfg(windows)]
+use anyhow::Result;
+#[cfg(windows)]
+use image::{imageops, RgbaImage};
+use serde::{Deserialize, Serialize};
+#[cfg(windows)]
+use windows::{
+    core::{Interface, HSTRING},
+    Data::Xml::Dom::XmlDocument,
+    Foundation::TypedEventHandler,
+    UI::Notifications::{ToastActivatedEventArgs, ToastNotification, ToastNotificationManager},
+};
+#[cfg(windows)]
+use winreg::{enums::HKEY_CURRENT_USER, RegKey};
+
+#[cfg(windows)]
+use crate::service::TrackIdentity;
+#[cfg(windows)]
+use crate::{
+    service::{restart_app, CrashedApp},
+    settings::get_data_dir,
+};
+
+/// App User Model ID the toasts are sent as.
+/// Windows only shows toasts of unpackaged apps once it is registered, see [register_app_id].
+#[cfg(windows)]
+const APP_ID: &str = "Kaaeveth.Spotick";
+#[cfg(windows)]
+const APP_ID_KEY: &str = "Software\\Classes\\AppUserModelId\\Kaaeveth.Spotick";
+/// Toasts can only show images from files, so the cover is written here first.
+#[cfg(windows)]
+const TOAST_COVER_FILE: &str = "toast-cover.png";
+#[cfg(windows)]
+const TOAST_COVER_SIZE: u32 = 256;
+/// Arguments of the button restarting a crashed app
+#[cfg(windows)]
+const RESTART_ARGUMENTS: &str = "restart";
+
+/// When a toast is shown for a new track.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+pub enum ToastMode {
+    #[default]
+    Off,
+    /// Only while the main window is minimized (e.g. by the user)
+    WhenHidden,
+    Always,
+}
+
+#[cfg(windows)]
+impl ToastMode {
+    /// Never while the window is kept [quiet] for a presentation, no matter the mode.
+    pub fn shows_toast(self, window_hidden: bool, quiet: bool) -> bool {
+        if quiet {
+            return false;
+        }
+        match self {
+            ToastMode::Off => false,
+            ToastMode::WhenHidden => window_hidden,
+            ToastMode::Always => true,
+        }
+    }
+}
+
+/// Decides when to toast a new track. Media sessions often report the same track
+/// multiple times (see [TrackIdentity]), and its cover only arrives a bit later,
+/// which the toast waits for.
+#[cfg(windows)]
+#[derive(Default)]
+pub struct TrackToaster {
+    last_track: Option<TrackIdentity>,
+    waiting_for_cover: bool,
+}
+
+#[cfg(windows)]
+impl TrackToaster {
+    /// Whether to toast the current [track] (title, artist and length) now.
+    /// Returns false until the [cover_pending] cover arrived, see [TrackToaster::cover_changed].
+    pub fn track_changed(&mut self, track: Option<(&str, &str, u64)>, cover_pending: bool) -> bool {
+        let track = track.map(|(title, artist, length)| TrackIdentity::new(title, artist, length));
+        let same_track = match (&self.last_track, &track) {
+            (Some(last), Some(track)) => last.matches(track),
+            (None, None) => true,
+            _ => false,
+        };
+        if same_track {
+            return false;
+        }
+        self.last_track = track;
+        self.waiting_for_cover = self.last_track.is_some() && cover_pending;
+        self.last_track.is_some() && !cover_pending
+    }
+
+    /// Whether to toast the current track now that its cover finished loading.
+    pub fn cover_changed(&mut self) -> bool {
+        std::mem::take(&mut self.waiting_for_cover)
+    }
+}
+
+#[cfg(windows)]
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Content of a toast about a track, see
+/// https://learn.microsoft.com/en-us/windows/apps/design/shell/tiles-and-notifications/adaptive-interactive-toasts
+#[cfg(windows)]
+fn toast_xml(title: &str, artist: &str, album: &str, cover: Option<&Path>) -> String {
+    let cover = cover
+        .map(|path| {
+            let src = escape_xml(&format!("file:///{}", path.display()));
+            format!(r#"<image placement="appLogoOverride" src="{}"/>"#, src)
+        })
+        .unwrap_or_default();
+    let album = if album.is_empty() {
+        String::new()
+    } else {
+        format!(
+            r#"<text placement="attribution">{}</text>"#,
+            escape_xml(album)
+        )
+    };
+    // The music is already playing, no need for a sound
+    format!(
+        concat!(
+            r#"<toast><visual><binding template="ToastGeneric">"#,
+            "<text>{}</text><text>{}</text>{}{}",
+            r#"</binding></visual><audio silent="true"/></toast>"#,
+        ),
+        escape_xml(title),
+        escape_xml(artist),
+        album,
+        cover
+    )
+}
+
+/// Content of a toast about the source app having crashed, with a button to restart it.
+#[cfg(windows)]
+fn crash_toast_xml(app_name: &str, exit_code: u32) -> String {
+    format!(
+        concat!(
+            r#"<toast><visual><binding template="ToastGeneric">"#,
+            "<text>{} closed unexpectedly</text>",
+            "<text>Playback stopped (exit code {:#x})</text>",
+            r#"</binding></visual><actions><action content="Restart" arguments="{}"/>"#,
+            "</actions></toast>",
+        ),
+        escape_xml(app_name),
+        exit_code,
+        RESTART_ARGUMENTS
+    )
+}
+
+/// Registers [APP_ID], so Windows shows toasts of Spotick under its name.
+/// In a [dry_run], the registry change is only logged.
+#[cfg(windows)]
+fn register_app_id(dry_run: bool) -> Result<()> {
+    let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+    if hkcu.open_subkey(APP_ID_KEY).is_ok() {
+        return Ok(());
+    }
+    if dry_run {
+        log::info!("Would create HKCU\\{} to show toasts", APP_ID_KEY);
+        return Ok(());
+    }
+
+    let (key, _) = hkcu.create_subkey(APP_ID_KEY)?;
+    key.set_value("DisplayName", &"Spotick")?;
+    log::info!("Registered {} to show toasts", APP_ID);
+    Ok(())
+}
+
+/// Shows a native toast about a track, replacing the one of the previous track.
+/// Blocks while writing the [cover].
+#[cfg(windows)]
+pub fn show_track_toast(
+    title: &str,
+    artist: &str,
+    album: &str,
+    cover: Option<&RgbaImage>,
+    dry_run: bool,
+) -> Result<()> {
+    register_app_id(dry_run)?;
+
+    let cover_path = get_data_dir().join(TOAST_COVER_FILE);
+    let cover = match cover {
+        Some(cover) => {
+            imageops::thumbnail(cover, TOAST_COVER_SIZE, TOAST_COVER_SIZE).save(&cover_path)?;
+            Some(cover_path.as_path())
+        }
+        None => None,
+    };
+
+    show_toast(&toast_xml(title, artist, album, cover), "track", |_| Ok(()))?;
+    Ok(())
+}
+
+/// Shows a native toast about the [app] having crashed.
+/// Its button restarts the app as long as Spotick is running, so keep the returned toast around.
+#[cfg(windows)]
+pub fn show_crash_toast(
+    app: CrashedApp,
+    app_name: &str,
+    dry_run: bool,
+) -> Result<ToastNotification> {
+    register_app_id(dry_run)?;
+    show_toast(
+        &crash_toast_xml(app_name, app.exit_code),
+        "crash",
+        move |toast| {
+            toast.Activated(&TypedEventHandler::new(move |_, args| {
+                let args = args.ok()?.cast::<ToastActivatedEventArgs>()?;
+                if args.Arguments()?.to_string() == RESTART_ARGUMENTS {
+                    if let Err(e) = restart_app(&app, dry_run) {
+                        log::error!("Could not restart {}: {:?}", app.app_id, e);
+                    }
+                }
+                Ok(())
+            }))?;
+            Ok(())
+        },
+    )
+}
+
+/// Shows a toast with the [xml] content, replacing the previous one with the same [tag].
+/// [prepare] is called before it shows (e.g. to handle its activation).
+#[cfg(windows)]
+fn show_toast(
+    xml: &str,
+    tag: &str,
+    prepare: impl FnOnce(&ToastNotification) -> windows::core::Result<()>,
+) -> Result<ToastNotification> {
+    let doc = XmlDocument::new()?;
+    doc.LoadXml(&HSTRING::from(xml))?;
+    let toast = ToastNotification::CreateToastNotification(&doc)?;
+    // Keeps the action center from filling up with old toasts
+    toast.SetTag(&HSTRING::from(tag))?;
+    toast.SetGroup(&HSTRING::from("playback"))?;
+    prepare(&toast)?;
+    ToastNotificationManager::CreateToastNotifierWithId(&HSTRING::from(APP_ID))?.Show(&toast)?;
+    Ok(toast)
+}
+
+#[cfg(all(test, windows))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn toasts_new_tracks_with_cover() {
+        let mut toaster = TrackToaster::default();
+        assert!(toaster.track_changed(Some(("A", "X", 180)), false));
+        // Same track reported again, with a title updated by the browser
+        assert!(!toaster.track_changed(Some(("(1) A - YouTube", "X", 180)), false));
+        assert!(!toaster.cover_changed());
+
+        // Waits for the cover
+        assert!(!toaster.track_changed(Some(("B", "X", 0)), true));
+        assert!(toaster.cover_changed());
+        assert!(!toaster.cover_changed());
+
+        assert!(!toaster.track_changed(None, false));
+        assert!(!ToastMode::WhenHidden.shows_toast(false, false));
+        assert!(ToastMode::WhenHidden.shows_toast(true, false));
+        assert!(!ToastMode::WhenHidden.shows_toast(true, true));
+        assert!(ToastMode::Always.shows_toast(false, false));
+        assert!(!ToastMode::Always.shows_toast(false, true));
+    }
+
+    #[test]
+    fn escapes_toast_content() {
+        let xml = toast_xml("Rock & Roll", "<Artist>", "", None);
+        assert!(xml.contains("<text>Rock &amp; Roll</text><text>&lt;Artist&gt;</text>"));
+        assert!(!xml.contains("attribution"));
+        assert!(!xml.contains("<image"));
+
+        let xml = toast_xml("A", "B", "Album", Some(Path::new("C:\\data\\cover.png")));
+        assert!(xml.contains(r#"<text placement="attribution">Album</text>"#));
+        assert!(xml.contains(r#"src="file:///C:\data\cover.png""#));
+
+        let xml = crash_toast_xml("Tom & Jerry's Player", 0xc0000005);
+        assert!(xml.contains("<text>Tom &amp; Jerry&apos;s Player closed unexpectedly</text>"));
+        assert!(xml.contains("(exit code 0xc0000005)"));
+        assert!(xml.contains(r#"<action content="Restart" arguments="restart"/>"#));
+    }
+}
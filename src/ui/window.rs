@@ -1,5 +1,7 @@
 pub mod dialog_window;
+pub mod history_window;
 pub mod main_window;
+pub mod notifications_window;
 pub mod settings_window;
 pub mod window_creation_settings;
 
@@ -8,7 +10,9 @@ slint::include_modules!();
 use slint::{ComponentHandle, Weak};
 
 pub use crate::ui::window::dialog_window::DialogWindow;
+pub use crate::ui::window::history_window::HistoryWindow;
 pub use crate::ui::window::main_window::MainWindow;
+pub use crate::ui::window::notifications_window::{NotificationsWindow, Notifier};
 pub use crate::ui::window::settings_window::SettingsWindow;
 pub use crate::ui::window::window_creation_settings::get_window_creation_settings;
 
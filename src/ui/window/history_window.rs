@@ -0,0 +1,113 @@
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use i_slint_backend_winit::winit::window::WindowButtons;
+use slint::{ComponentHandle, ModelRc, VecModel};
+
+use crate::{
+    callback,
+    history::{most_listened, HistoryEntry, TrackHistory},
+    ui::{
+        format_duration, get_window_creation_settings,
+        window::{HistoryItem, SlintHistoryWindow, Window},
+    },
+};
+
+/// Only the most recent matching tracks are listed.
+const MAX_LISTED_TRACKS: usize = 500;
+const MOST_LISTENED_COUNT: usize = 3;
+
+/// Lists the tracks listened to and which ones the most.
+pub struct HistoryWindow {
+    ui: SlintHistoryWindow,
+    history: TrackHistory,
+    /// All entries of the [TrackHistory] as of the last refresh, oldest first
+    entries: Arc<Mutex<Vec<HistoryEntry>>>,
+}
+
+impl HistoryWindow {
+    pub fn new(history: TrackHistory) -> Result<Self> {
+        let _settings_guard = get_window_creation_settings()
+            .change(|attr| attr.with_enabled_buttons(WindowButtons::CLOSE));
+        let win = HistoryWindow {
+            ui: SlintHistoryWindow::new()?,
+            history,
+            entries: Arc::default(),
+        };
+
+        win.setup_callbacks();
+
+        Ok(win)
+    }
+
+    fn setup_callbacks(&self) {
+        let ui = &self.ui;
+
+        let entries = self.entries.clone();
+        callback!(on_search, |ui, text| {
+            show_entries(&ui, &entries.lock().unwrap(), &text);
+        });
+
+        let entries = self.entries.clone();
+        let history = self.history.clone();
+        callback!(on_refresh, |ui| {
+            let wui = ui.as_weak();
+            let entries = entries.clone();
+            let history = history.clone();
+            tokio::spawn(async move {
+                let loaded = match history.load().await {
+                    Ok(loaded) => loaded,
+                    Err(e) => {
+                        log::error!("Could not load history: {:?}", e);
+                        return;
+                    }
+                };
+                *entries.lock().unwrap() = loaded;
+                let _ = wui.upgrade_in_event_loop(move |ui| {
+                    show_entries(&ui, &entries.lock().unwrap(), &ui.get_search_text());
+                });
+            });
+        });
+    }
+}
+
+/// Shows the [entries] matching the [query] (all for an empty one), newest first.
+fn show_entries(ui: &SlintHistoryWindow, entries: &[HistoryEntry], query: &str) {
+    let query = query.trim();
+    let matching: Vec<&HistoryEntry> = entries
+        .iter()
+        .filter(|entry| query.is_empty() || entry.matches(query))
+        .collect();
+
+    let tracks: Vec<HistoryItem> = matching
+        .iter()
+        .rev()
+        .take(MAX_LISTED_TRACKS)
+        .map(|entry| HistoryItem {
+            title: entry.title.clone().into(),
+            artist: entry.artist.clone().into(),
+            detail: entry.started_at.format("%Y-%m-%d %H:%M").to_string().into(),
+            duration: format_duration(entry.listened).into(),
+        })
+        .collect();
+
+    let stats: Vec<HistoryItem> = most_listened(matching.iter().copied(), MOST_LISTENED_COUNT)
+        .into_iter()
+        .map(|stats| HistoryItem {
+            title: stats.title.into(),
+            artist: stats.artist.into(),
+            detail: format!("{}×", stats.plays).into(),
+            duration: format_duration(stats.listened).into(),
+        })
+        .collect();
+
+    ui.set_track_count(matching.len() as i32);
+    ui.set_tracks(ModelRc::new(VecModel::from(tracks)));
+    ui.set_most_listened(ModelRc::new(VecModel::from(stats)));
+}
+
+impl Window<SlintHistoryWindow> for HistoryWindow {
+    fn component(&self) -> &SlintHistoryWindow {
+        &self.ui
+    }
+}
@@ -1,45 +1,97 @@
-use std::{sync::Arc, time::Duration};
+use std::{collections::HashMap, path::PathBuf, sync::Arc, time::Duration};
 
 use anyhow::Result;
-use i_slint_backend_winit::winit::platform::windows::WindowAttributesExtWindows;
+use i_slint_backend_winit::{
+    winit::{platform::windows::WindowAttributesExtWindows, window::WindowButtons},
+    WinitWindowAccessor,
+};
 use image::RgbaImage;
 use slint::{
-    ComponentHandle, Image, LogicalSize, PhysicalPosition, Rgba8Pixel, SharedPixelBuffer,
-    ToSharedString, Weak,
+    ComponentHandle, Image, LogicalSize, ModelRc, PhysicalPosition, PhysicalSize, Rgba8Pixel,
+    SharedPixelBuffer, SharedString, ToSharedString, VecModel, Weak,
+};
+use tokio::{
+    sync::{
+        broadcast::{error::RecvError, Receiver},
+        mpsc::unbounded_channel,
+        watch::{channel, Sender},
+    },
+    task::JoinHandle,
+    time::Instant,
 };
-use tokio::sync::watch::channel;
+use windows::Win32::UI::Input::KeyboardAndMouse::{MOD_ALT, MOD_CONTROL, VK_O};
 
 use crate::{
-    callback, save_changes_in_settings,
-    service::{AlbumCover, BaseService, PlaybackChangedEvent, SharedMediaService},
+    callback, close_dialog, on_settings_changed, save_changes_in_settings,
+    service::{
+        watch_app_level, AlbumCover, BaseService, Lyrics, LyricsProvider, LyricsQuery,
+        PlaybackChangedEvent, RepeatMode, SharedMediaService,
+    },
+    settings::{get_data_dir, SpotickSettings},
+    state_cache::CachedTrack,
+    system_events::{screen_areas, watch_hotkey, watch_presenting, watch_system_colors},
     ui::{
-        apply_border_radius, get_window_creation_settings,
-        window::{SettingsWindow, SlintMainWindow, Window},
+        announcer::{announce, PlaybackAnnouncer},
+        apply_border_radius, format_duration, get_window_creation_settings,
+        placement::{clamp_to_screens, position_key, snap_to_edges, WindowLayout},
+        romanization::romanize_track,
+        theme::{dominant_color, Theme, ThemeSetting},
+        toast::{show_crash_toast, show_track_toast, ToastMode, TrackToaster},
+        upscale_image,
+        window::{
+            DialogWindow, HistoryWindow, MsgType, NotificationsWindow, Notifier, Repeat,
+            SettingsWindow, SlintConfirmQuitWindow, SlintMainWindow, SlintWelcomeWindow,
+            WidgetTheme, Window,
+        },
     },
 };
 
+/// How long the media session has to be gone before the window hides,
+/// see [MainWindow::enable_auto_hide].
+const IDLE_HIDE_DELAY: Duration = Duration::from_secs(5);
+
 pub struct MainWindow {
     ui: SlintMainWindow,
     settings_window: SettingsWindow,
+    notifications_window: NotificationsWindow,
+    history_window: HistoryWindow,
     media_service: SharedMediaService,
+    /// Whether to hold back toasts while presenting, see [MainWindow::enable_auto_hide]
+    quiet: Sender<bool>,
 }
 
 impl MainWindow {
-    pub async fn new(media_service: SharedMediaService, settings: SettingsWindow) -> Result<Self> {
+    pub async fn new(
+        media_service: SharedMediaService,
+        settings: SettingsWindow,
+        notifications: NotificationsWindow,
+        history: HistoryWindow,
+        dry_run: bool,
+    ) -> Result<Self> {
         let _guard_settings =
             get_window_creation_settings().change(|attr| attr.with_skip_taskbar(true));
         let app = MainWindow {
             ui: SlintMainWindow::new()?,
             settings_window: settings,
+            notifications_window: notifications,
+            history_window: history,
             media_service,
+            quiet: channel(false).0,
         };
 
         app.ui.set_initial_thumbnail();
         app.connect_settings();
-        app.connect_media_info().await;
+        app.connect_media_info(dry_run).await;
         app.enable_app_quit();
+        app.enable_layouts().await;
         app.enable_window_positioning().await;
         app.enable_window_scaling().await;
+        app.enable_auto_hide().await;
+        app.enable_level_meter().await;
+        app.enable_theme().await;
+        app.enable_click_through().await;
+        app.enable_custom_placeholder().await;
+        app.enable_lyrics().await;
         app.setup_ui_callbacks();
 
         Ok(app)
@@ -54,6 +106,72 @@ impl MainWindow {
         Ok(())
     }
 
+    /// Shows the [track] of a previous app run as stale until
+    /// the media service reports the actual track.
+    pub fn show_cached_track(&self, track: CachedTrack, cover: Option<RgbaImage>) {
+        let srv = self.media_service.clone();
+        let _ = self.ui.as_weak().upgrade_in_event_loop(move |ui| {
+            // The media service might have been faster
+            if let Ok(srv) = srv.try_read() {
+                if srv.current_track().is_some() {
+                    return;
+                }
+            }
+
+            ui.set_stale(true);
+            ui.set_track_title(track.title.into());
+            ui.set_track_subtitle(track.artist.into());
+            ui.set_track_album(track.album_title.into());
+            ui.set_track_romanized(romanize_track(&track.title, &track.artist).into());
+            ui.set_track_length(format_duration(track.length).into());
+            ui.set_playing(track.is_playing);
+            if let Some(cover) = cover {
+                ui.set_thumbnail(cover);
+            }
+        });
+    }
+
+    /// Greets the user on the first run and offers to select the media application.
+    pub async fn show_welcome(&self) {
+        let source_app_name = {
+            let srv = self.media_service.read().await;
+            srv.get_source_app_name(srv.get_source_app_id())
+        };
+        let ui = self.ui.as_weak();
+        let settings_window = self.settings_window.as_weak();
+        // The main window must be shown to own the dialog
+        let _ = slint::invoke_from_event_loop(move || {
+            let Some(ui) = ui.upgrade() else {
+                return;
+            };
+            let dialog = DialogWindow::new(
+                ui,
+                |dialog_res| {
+                    let win = SlintWelcomeWindow::new()?;
+                    win.set_source_app_name(source_app_name.into());
+                    callback!(on_answered, |win, open_settings| {
+                        *dialog_res.borrow_mut() = Some(open_settings);
+                        close_dialog!(win);
+                    });
+                    Ok(win)
+                },
+                |attr| attr.with_enabled_buttons(WindowButtons::CLOSE),
+            );
+            let res = dialog.and_then(|dialog| {
+                dialog.show_dialog(move |open_settings| {
+                    if open_settings == Some(true) {
+                        if let Some(settings_window) = settings_window.upgrade() {
+                            let _ = settings_window.show();
+                        }
+                    }
+                })
+            });
+            if let Err(e) = res {
+                log::error!("Could not show welcome: {}", e);
+            }
+        });
+    }
+
     fn setup_ui_callbacks(&self) {
         let _app = &self.ui;
         let settings_window = self.settings_window.as_weak();
@@ -63,16 +181,36 @@ impl MainWindow {
             let _ = settings_window.show();
         });
 
+        let notifications_window = self.notifications_window.as_weak();
+        callback!(on_show_notifications, |_app| {
+            let notifications_window = notifications_window.unwrap();
+            let _ = notifications_window.show();
+        });
+
+        let history_window = self.history_window.as_weak();
+        callback!(on_show_history, |_app| {
+            let history_window = history_window.unwrap();
+            history_window.invoke_refresh();
+            let _ = history_window.show();
+        });
+
+        let notifier = self.notifications_window.notifier();
+
         macro_rules! connect_to_media_service {
             ($srv:expr, $media_method:ident, $ui_callback:ident) => {
                 let srv = Arc::downgrade($srv);
+                let notifier = notifier.clone();
                 callback!($ui_callback, |_app| {
+                    let wui = _app.as_weak();
                     tokio::spawn({
                         let srv = srv.clone();
+                        let notifier = notifier.clone();
                         async move {
                             if let Some(srv) = srv.upgrade() {
                                 if let Err(e) = srv.write().await.$media_method().await {
-                                    log::error!("Error in {}: {}", stringify!($media_method), e);
+                                    let msg =
+                                        format!("Error in {}: {}", stringify!($media_method), e);
+                                    show_error(&wui, &notifier, msg);
                                 }
                             }
                         }
@@ -84,20 +222,104 @@ impl MainWindow {
         connect_to_media_service!(&self.media_service, toggle_playback, on_toggle_play);
         connect_to_media_service!(&self.media_service, next_track, on_next_track);
         connect_to_media_service!(&self.media_service, previous_track, on_previous_track);
+
+        let srv = Arc::downgrade(&self.media_service);
+        callback!(on_seek, |_app, progress| {
+            let wui = _app.as_weak();
+            let srv = srv.clone();
+            let notifier = notifier.clone();
+            let playback_percent = (progress * 100.0).round() as u32;
+            tokio::spawn(async move {
+                if let Some(srv) = srv.upgrade() {
+                    if let Err(e) = srv.write().await.seek(playback_percent).await {
+                        show_error(&wui, &notifier, format!("Error in seek: {}", e));
+                    }
+                }
+            });
+        });
+
+        let srv = Arc::downgrade(&self.media_service);
+        callback!(on_volume_changed, |_app, volume| {
+            let wui = _app.as_weak();
+            let srv = srv.clone();
+            let notifier = notifier.clone();
+            let volume = (volume * 100.0).round() as u32;
+            tokio::spawn(async move {
+                if let Some(srv) = srv.upgrade() {
+                    if let Err(e) = srv.write().await.set_volume(volume).await {
+                        show_error(&wui, &notifier, format!("Error in set_volume: {}", e));
+                    }
+                }
+            });
+        });
+
+        let srv = Arc::downgrade(&self.media_service);
+        callback!(on_toggle_shuffle, |_app| {
+            let wui = _app.as_weak();
+            let srv = srv.clone();
+            let notifier = notifier.clone();
+            tokio::spawn(async move {
+                if let Some(srv) = srv.upgrade() {
+                    let mut srv = srv.write().await;
+                    let shuffle = !srv.current_playback_state().shuffle;
+                    if let Err(e) = srv.set_shuffle(shuffle).await {
+                        show_error(&wui, &notifier, format!("Error in set_shuffle: {}", e));
+                    }
+                }
+            });
+        });
+
+        let srv = Arc::downgrade(&self.media_service);
+        callback!(on_cycle_repeat, |_app| {
+            let wui = _app.as_weak();
+            let srv = srv.clone();
+            let notifier = notifier.clone();
+            tokio::spawn(async move {
+                if let Some(srv) = srv.upgrade() {
+                    let mut srv = srv.write().await;
+                    let repeat = srv.current_playback_state().repeat.next();
+                    if let Err(e) = srv.set_repeat(repeat).await {
+                        show_error(&wui, &notifier, format!("Error in set_repeat: {}", e));
+                    }
+                }
+            });
+        });
     }
 
     async fn update_track(srv: &SharedMediaService, wui: &Weak<SlintMainWindow>) {
         let srv_lock = srv.clone().read_owned().await;
+        let source_app_name = srv_lock.get_source_app_name(srv_lock.get_active_app_id());
         let _ = wui.upgrade_in_event_loop(move |ui| {
+            ui.set_stale(false);
+            ui.set_source_app_name(source_app_name.into());
             if let Some(current_media_track) = srv_lock.current_track() {
                 ui.set_track_title(current_media_track.title.to_shared_string());
                 ui.set_track_subtitle(current_media_track.artist.to_shared_string());
-                if let AlbumCover::Image(img) = &current_media_track.album_cover {
-                    ui.set_thumbnail(img.clone());
+                ui.set_track_album(current_media_track.album_title.to_shared_string());
+                ui.set_track_romanized(
+                    romanize_track(&current_media_track.title, &current_media_track.artist).into(),
+                );
+                ui.set_track_length(format_duration(current_media_track.length).into());
+                match &current_media_track.album_cover {
+                    AlbumCover::Image(img) => ui.set_thumbnail(img.clone()),
+                    // Keep the current cover until the new one is loaded
+                    AlbumCover::Pending => {}
+                    _ => ui.set_initial_thumbnail(),
                 }
+            } else if !srv_lock.has_source_session() {
+                // The app is usually still starting (e.g. right after login)
+                ui.set_track_title(ui.get_source_app_name());
+                ui.set_track_subtitle("Waiting for the app to start...".into());
+                ui.set_track_album("".into());
+                ui.set_track_romanized("".into());
+                ui.set_track_length("".into());
+                ui.set_initial_thumbnail();
             } else {
                 ui.set_track_title("No Title".into());
                 ui.set_track_subtitle("...".into());
+                ui.set_track_album("".into());
+                ui.set_track_romanized("".into());
+                ui.set_track_length("".into());
                 ui.set_initial_thumbnail();
             }
         });
@@ -108,28 +330,128 @@ impl MainWindow {
         let _ = wui.upgrade_in_event_loop(move |ui| {
             let playback_state = srv_lock.current_playback_state();
             ui.set_playing(playback_state.is_playing);
+            ui.set_volume(playback_state.volume as f32 / 100.0);
+            ui.set_shuffle(playback_state.shuffle);
+            ui.set_repeat(match playback_state.repeat {
+                RepeatMode::Off => Repeat::Off,
+                RepeatMode::Track => Repeat::Track,
+                RepeatMode::List => Repeat::List,
+            });
+            ui.set_progress(playback_state.progress.unwrap_or(0) as f32 / 100.0);
+            let remaining = srv_lock
+                .current_track()
+                .filter(|track| track.length > 0)
+                .zip(playback_state.position)
+                .map_or(-1, |(track, position)| {
+                    track.length.saturating_sub(position) as i32
+                });
+            ui.set_remaining(remaining);
+            let position = playback_state.position.map(format_duration);
+            ui.set_track_position(position.unwrap_or_default().into());
         });
     }
 
-    async fn connect_media_info(&self) {
+    /// [dry_run] is set on the command line, the settings may enable it as well.
+    async fn connect_media_info(&self, dry_run: bool) {
         let srv = self.media_service.clone();
         let wui = self.ui.as_weak();
+        let notifier = self.notifications_window.notifier();
+        let settings = self.settings_window.get_settings();
+        let quiet_rv = self.quiet.subscribe();
         MainWindow::update_track(&srv, &wui).await;
         MainWindow::update_playback(&srv, &wui).await;
 
+        // Read once per change of the settings instead of for every media event
+        let feedback_settings = move |spotick_settings: &SpotickSettings| {
+            (
+                spotick_settings.announcements.unwrap_or_default(),
+                spotick_settings.track_toasts.unwrap_or_default(),
+                spotick_settings.crash_toasts.unwrap_or(true),
+                dry_run || spotick_settings.dry_run.unwrap_or_default(),
+            )
+        };
+        let (feedback_tx, feedback_rv) =
+            channel(feedback_settings(settings.read().await.get_settings()));
+        on_settings_changed!(settings, |spotick_settings| {
+            if feedback_tx
+                .send(feedback_settings(spotick_settings))
+                .is_err()
+            {
+                break;
+            }
+        });
+
+        // Subscribe right away to not miss any events sent before the task below runs
+        let mut media_events = srv.read().await.subscribe();
         tokio::spawn(async move {
-            let mut media_events = srv.read().await.subscribe();
+            let mut announcer = PlaybackAnnouncer::default();
+            let mut toaster = TrackToaster::default();
+            // The restart button of a crash toast only works as long as it is around
+            let mut _crash_toast = None;
             loop {
                 let Ok(e) = media_events.recv().await else {
                     break;
                 };
 
+                let (verbosity, toast_mode, crash_toasts, dry_run) = *feedback_rv.borrow();
+                let quiet = *quiet_rv.borrow();
                 match e {
                     PlaybackChangedEvent::TrackChanged => {
                         MainWindow::update_track(&srv, &wui).await;
+                        let (msg, toast) = {
+                            let srv = srv.read().await;
+                            let current_track = srv.current_track();
+                            let track = current_track
+                                .map(|t| (t.title.as_str(), t.artist.as_str(), t.length));
+                            let cover_pending = current_track
+                                .is_some_and(|t| matches!(t.album_cover, AlbumCover::Pending));
+                            (
+                                announcer.track_changed(verbosity, track),
+                                toaster.track_changed(track, cover_pending),
+                            )
+                        };
+                        announce_playback(&wui, msg);
+                        if toast {
+                            toast_track(&srv, &wui, toast_mode, quiet, dry_run).await;
+                        }
+                    }
+                    PlaybackChangedEvent::CoverChanged => {
+                        MainWindow::update_track(&srv, &wui).await;
+                        if toaster.cover_changed() {
+                            toast_track(&srv, &wui, toast_mode, quiet, dry_run).await;
+                        }
                     }
                     PlaybackChangedEvent::Play | PlaybackChangedEvent::Pause => {
                         MainWindow::update_playback(&srv, &wui).await;
+                        let is_playing = srv.read().await.current_playback_state().is_playing;
+                        announce_playback(&wui, announcer.playback_changed(verbosity, is_playing));
+                    }
+                    PlaybackChangedEvent::Volume
+                    | PlaybackChangedEvent::PlaybackMode
+                    | PlaybackChangedEvent::PlaybackProgress => {
+                        MainWindow::update_playback(&srv, &wui).await;
+                    }
+                    PlaybackChangedEvent::ServiceError(msg) => {
+                        notifier.notify(msg.clone(), MsgType::Error);
+                        let _ = wui.upgrade_in_event_loop(move |ui| {
+                            ui.set_error_msg(msg.into());
+                        });
+                    }
+                    PlaybackChangedEvent::SourceCrashed(app) => {
+                        let app_name = srv.read().await.get_source_app_name(&app.app_id);
+                        notifier
+                            .notify(format!("{} closed unexpectedly", app_name), MsgType::Error);
+                        if crash_toasts && !quiet {
+                            _crash_toast = tokio::task::spawn_blocking(move || {
+                                show_crash_toast(app, &app_name, dry_run)
+                                    .inspect_err(|e| {
+                                        log::warn!("Could not show crash toast: {:?}", e)
+                                    })
+                                    .ok()
+                            })
+                            .await
+                            .unwrap_or_default();
+                        }
                     }
                     _ => {}
                 }
@@ -143,12 +465,38 @@ impl MainWindow {
         tokio::spawn(async move {
             let settings = settings.clone();
             let mut settings_recv = settings.read().await.subscribe();
+            let mut current_settings = None;
             loop {
-                let always_on_top = settings.read().await.get_settings().always_on_top;
+                let (always_on_top, upscale_thumbnails, confirm_quit, countdown, snap_margin) = {
+                    let sg = settings.read().await;
+                    let spotick_settings = sg.get_settings();
+                    (
+                        spotick_settings.always_on_top,
+                        spotick_settings.upscale_thumbnails.unwrap_or(true),
+                        spotick_settings.confirm_quit.unwrap_or_default(),
+                        spotick_settings.countdown.unwrap_or_default(),
+                        spotick_settings.snap_margin.unwrap_or(16),
+                    )
+                };
 
-                let _ = wui.upgrade_in_event_loop(move |ui| {
-                    ui.set_on_top(always_on_top);
-                });
+                // Avoid waking up the event loop for unrelated settings changes
+                let new_settings = (
+                    always_on_top,
+                    upscale_thumbnails,
+                    confirm_quit,
+                    countdown,
+                    snap_margin,
+                );
+                if current_settings != Some(new_settings) {
+                    current_settings = Some(new_settings);
+                    let _ = wui.upgrade_in_event_loop(move |ui| {
+                        ui.set_on_top(always_on_top);
+                        ui.set_upscale_thumbnail(upscale_thumbnails);
+                        ui.set_confirm_quit(confirm_quit);
+                        ui.set_countdown(countdown);
+                        ui.set_snap_margin(snap_margin.min(i32::MAX as u32) as i32);
+                    });
+                }
                 if let Err(_) = settings_recv.recv().await {
                     break;
                 }
@@ -156,6 +504,391 @@ impl MainWindow {
         });
     }
 
+    /// Steps aside while the user is presenting (e.g. a slideshow),
+    /// unless turned off in the settings.
+    /// Also minimizes the window while there is no media session to show, if enabled.
+    async fn enable_auto_hide(&self) {
+        let settings = self.settings_window.get_settings();
+        let mut settings_rv = settings.read().await.subscribe();
+        // Only watched while any of the presentation settings is on
+        let (presentation_tx, presentation_rv) = channel(false);
+        let mut presenting_rv = watch_presenting(presentation_rv);
+        let srv = self.media_service.clone();
+        let mut media_events = srv.read().await.subscribe();
+        let wui = self.ui.as_weak();
+        let quiet_tx = self.quiet.clone();
+        tokio::spawn(async move {
+            // The window starts out shown and loud
+            let mut applied = (false, false);
+            let mut session_lost: Option<Instant> = None;
+            let mut settings_changed = true;
+            let (mut hide_setting, mut quiet_setting, mut hide_when_idle) = (true, true, false);
+            loop {
+                if settings_changed {
+                    let sg = settings.read().await;
+                    let spotick_settings = sg.get_settings();
+                    hide_setting = spotick_settings.presentation_hide_window.unwrap_or(true);
+                    quiet_setting = spotick_settings.presentation_quiet.unwrap_or(true);
+                    hide_when_idle = spotick_settings.hide_when_idle.unwrap_or_default();
+                }
+                let (has_session, playing) = {
+                    let srv = srv.read().await;
+                    let playing = srv.current_playback_state().is_playing;
+                    (srv.has_source_session(), playing)
+                };
+                session_lost = match (has_session, session_lost) {
+                    (true, _) => None,
+                    (false, None) => Some(Instant::now()),
+                    (false, since) => since,
+                };
+                // Apps may drop their session briefly, e.g. when switching tracks
+                let idle_at = session_lost
+                    .map(|since| since + IDLE_HIDE_DELAY)
+                    .filter(|_| hide_when_idle);
+                let idle = idle_at.is_some_and(|at| at <= Instant::now());
+
+                // Presenting only matters while the window is shown or toasts may pop up,
+                // so it isn't polled at all while idle
+                let watch_presenting = (hide_setting && !idle) || (quiet_setting && playing);
+                presentation_tx.send_if_modified(|enabled| {
+                    let changed = *enabled != watch_presenting;
+                    *enabled = watch_presenting;
+                    changed
+                });
+                let presenting = *presenting_rv.borrow_and_update();
+                let quiet = presenting && quiet_setting;
+                let hide = (presenting && hide_setting) || idle;
+                if applied != (hide, quiet) {
+                    applied = (hide, quiet);
+                    quiet_tx.send_replace(quiet);
+                    let _ = wui.upgrade_in_event_loop(move |ui| {
+                        // Hiding the window would end the event loop
+                        ui.window().set_minimized(hide);
+                        ui.set_quiet(quiet);
+                    });
+                }
+
+                settings_changed = false;
+                tokio::select! {
+                    res = presenting_rv.changed() => if res.is_err() {
+                        break;
+                    },
+                    res = settings_rv.recv() => match res {
+                        Err(RecvError::Closed) => break,
+                        _ => settings_changed = true,
+                    },
+                    res = media_events.recv() => if let Err(RecvError::Closed) = res {
+                        break;
+                    },
+                    _ = tokio::time::sleep_until(idle_at.unwrap_or_else(Instant::now)),
+                        if idle_at.is_some() && !idle => {}
+                }
+            }
+        });
+    }
+
+    /// Shows how loud the source app is while playing, if enabled.
+    async fn enable_level_meter(&self) {
+        let settings = self.settings_window.get_settings();
+        let mut settings_rv = settings.read().await.subscribe();
+        let srv = self.media_service.clone();
+        let mut media_events = srv.read().await.subscribe();
+        let wui = self.ui.as_weak();
+
+        // Only meter while shown and playing, which is what the meter is there to verify
+        let (metered_app_tx, metered_app_rv) = channel(None);
+        let mut level_rv = watch_app_level(metered_app_rv);
+        tokio::spawn({
+            let wui = wui.clone();
+            async move {
+                while level_rv.changed().await.is_ok() {
+                    let level = *level_rv.borrow_and_update();
+                    if wui
+                        .upgrade_in_event_loop(move |ui| ui.set_output_level(level))
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+            }
+        });
+
+        tokio::spawn(async move {
+            let mut shown = false;
+            let mut settings_changed = true;
+            let mut enabled = false;
+            loop {
+                if settings_changed {
+                    let sg = settings.read().await;
+                    enabled = sg.get_settings().level_meter.unwrap_or_default();
+                }
+                let metered_app = {
+                    let srv = srv.read().await;
+                    let playing = srv.current_playback_state().is_playing;
+                    (enabled && srv.has_source_session() && playing)
+                        .then(|| srv.get_active_app_id().to_string())
+                };
+                metered_app_tx.send_if_modified(|app| {
+                    let changed = *app != metered_app;
+                    *app = metered_app;
+                    changed
+                });
+                if shown != enabled {
+                    shown = enabled;
+                    let _ = wui.upgrade_in_event_loop(move |ui| ui.set_level_meter(enabled));
+                }
+
+                settings_changed = false;
+                tokio::select! {
+                    res = settings_rv.recv() => match res {
+                        Err(RecvError::Closed) => break,
+                        _ => settings_changed = true,
+                    },
+                    res = next_meter_change(&mut media_events) => if res.is_err() {
+                        break;
+                    },
+                }
+            }
+        });
+    }
+
+    /// Applies the theme picked in the settings, tinted in the colors of the cover if enabled.
+    /// The Auto theme follows changes of the Windows colors right away.
+    async fn enable_theme(&self) {
+        let settings = self.settings_window.get_settings();
+        let mut settings_rv = settings.read().await.subscribe();
+        let mut colors_rv = watch_system_colors();
+        let srv = self.media_service.clone();
+        let mut media_events = srv.read().await.subscribe();
+        let wui = self.ui.as_weak();
+        tokio::spawn(async move {
+            let mut watching_colors = true;
+            let mut cover_color = None;
+            let mut applied = None;
+            let mut settings_changed = true;
+            let (mut theme_setting, mut cover_colors) = (ThemeSetting::default(), false);
+            loop {
+                if settings_changed {
+                    let sg = settings.read().await;
+                    let spotick_settings = sg.get_settings();
+                    theme_setting = spotick_settings.theme.clone().unwrap_or_default();
+                    cover_colors = spotick_settings.cover_colors.unwrap_or_default();
+                }
+                if cover_colors {
+                    let srv = srv.read().await;
+                    match srv.current_track().map(|track| &track.album_cover) {
+                        Some(AlbumCover::Image(img)) => cover_color = dominant_color(img),
+                        // Keep the colors of the previous cover until the new one is loaded
+                        Some(AlbumCover::Pending) => {}
+                        _ => cover_color = None,
+                    }
+                } else {
+                    cover_color = None;
+                }
+
+                let mut theme = theme_setting.resolve(colors_rv.borrow_and_update().as_ref());
+                if let Some(color) = cover_color {
+                    theme = theme.tinted(color);
+                }
+                if applied.as_ref() != Some(&theme) {
+                    applied = Some(theme.clone());
+                    let _ = wui.upgrade_in_event_loop(move |ui| apply_theme(&ui, theme));
+                }
+
+                settings_changed = false;
+                tokio::select! {
+                    res = colors_rv.changed(), if watching_colors => if res.is_err() {
+                        watching_colors = false;
+                    },
+                    res = settings_rv.recv() => match res {
+                        Err(RecvError::Closed) => break,
+                        _ => settings_changed = true,
+                    },
+                    res = next_cover(&mut media_events) => if res.is_err() {
+                        break;
+                    },
+                }
+            }
+        });
+    }
+
+    /// Lets clicks pass through the window if enabled in the settings.
+    /// A global hotkey toggles the setting, since the window itself can't be clicked anymore.
+    async fn enable_click_through(&self) {
+        let settings = self.settings_window.get_settings();
+        let mut settings_rv = settings.read().await.subscribe();
+        let (mut hotkey_rv, hotkey_registered) =
+            match watch_hotkey(MOD_CONTROL | MOD_ALT, VK_O.0 as u32) {
+                Ok(hotkey_rv) => (hotkey_rv, true),
+                // Closed right away, which disables the hotkey below
+                Err(e) => {
+                    log::error!("Could not register the click-through hotkey: {}", e);
+                    (unbounded_channel().1, false)
+                }
+            };
+        let wui = self.ui.as_weak();
+        let notifier = self.notifications_window.notifier();
+        tokio::spawn(async move {
+            let mut applied = false;
+            let mut warned = false;
+            loop {
+                let enabled = settings
+                    .read()
+                    .await
+                    .get_settings()
+                    .click_through
+                    .unwrap_or_default();
+                // Without the hotkey there would be no way back
+                if enabled && !hotkey_registered && !warned {
+                    let msg = "Click-through needs Ctrl+Alt+O, which is taken by another app";
+                    show_error(&wui, &notifier, msg);
+                }
+                warned = enabled && !hotkey_registered;
+                let click_through = enabled && hotkey_registered;
+                if applied != click_through {
+                    applied = click_through;
+                    let _ = wui.upgrade_in_event_loop(move |ui| {
+                        ui.window().with_winit_window(|win| {
+                            if let Err(e) = win.set_cursor_hittest(!click_through) {
+                                log::error!("Could not change click-through: {}", e);
+                            }
+                        });
+                    });
+                }
+
+                tokio::select! {
+                    Some(_) = hotkey_rv.recv() => {
+                        let mut sg = settings.write().await;
+                        let spotick_settings = sg.get_settings_mut();
+                        spotick_settings.click_through = Some(!applied);
+                        if let Err(e) = sg.save().await {
+                            log::error!("Could not save click-through: {}", e);
+                        }
+                    },
+                    res = settings_rv.recv() => if let Err(RecvError::Closed) = res {
+                        break;
+                    },
+                }
+            }
+        });
+    }
+
+    /// Shows the placeholder image picked in the settings instead of the built-in one.
+    async fn enable_custom_placeholder(&self) {
+        let settings = self.settings_window.get_settings();
+        let mut settings_rv = settings.read().await.subscribe();
+        let wui = self.ui.as_weak();
+        tokio::spawn(async move {
+            let mut applied = None;
+            loop {
+                let name = settings
+                    .read()
+                    .await
+                    .get_settings()
+                    .placeholder_image
+                    .clone();
+                if applied != name {
+                    applied = name.clone();
+                    let img = match name {
+                        Some(name) => load_placeholder(get_data_dir().join(name)).await,
+                        None => None,
+                    };
+                    let _ = wui.upgrade_in_event_loop(move |ui| ui.set_custom_placeholder(img));
+                }
+
+                if let Err(RecvError::Closed) = settings_rv.recv().await {
+                    break;
+                }
+            }
+        });
+    }
+
+    /// Shows the lyrics of the current track while the lyrics panel is expanded.
+    /// Lyrics are only fetched while the panel is expanded.
+    async fn enable_lyrics(&self) {
+        let (expanded_tx, mut expanded_rv) = channel(false);
+        let _app = &self.ui;
+        callback!(on_lyrics_toggled, |_app, expanded| {
+            let _ = expanded_tx.send_replace(expanded);
+        });
+
+        let srv = self.media_service.clone();
+        let mut media_events = srv.read().await.subscribe();
+        let wui = self.ui.as_weak();
+        tokio::spawn(async move {
+            let provider = LyricsProvider::default();
+            // Track the shown lyrics belong to
+            let mut shown_query = None;
+            let mut lyrics: Option<Lyrics> = None;
+            let mut shown_line = None;
+            let mut fetch: Option<JoinHandle<_>> = None;
+            loop {
+                if *expanded_rv.borrow_and_update() {
+                    let (query, position) = {
+                        let srv = srv.read().await;
+                        (
+                            srv.current_track().map(LyricsQuery::from),
+                            srv.current_playback_state().position,
+                        )
+                    };
+                    if query != shown_query {
+                        if let Some(fetch) = fetch.take() {
+                            fetch.abort();
+                        }
+                        lyrics = None;
+                        shown_line = None;
+                        let status = if query.is_some() {
+                            "Loading lyrics..."
+                        } else {
+                            "No track"
+                        };
+                        show_lyrics(&wui, Vec::new(), status);
+                        if let Some(query) = query.clone() {
+                            let provider = provider.clone();
+                            fetch = Some(tokio::spawn(async move { provider.get(&query).await }));
+                        }
+                        shown_query = query;
+                    }
+
+                    let line = lyrics.as_ref().and_then(|lyrics| {
+                        lyrics.line_at(Duration::from_secs(position.unwrap_or_default()))
+                    });
+                    if lyrics.is_some() && shown_line != Some(line) {
+                        shown_line = Some(line);
+                        let line = line.map_or(-1, |line| line as i32);
+                        let _ = wui.upgrade_in_event_loop(move |ui| ui.set_lyrics_line(line));
+                    }
+                }
+
+                tokio::select! {
+                    res = expanded_rv.changed() => if res.is_err() {
+                        break;
+                    },
+                    res = media_events.recv() => if let Err(RecvError::Closed) = res {
+                        break;
+                    },
+                    res = async { fetch.as_mut().unwrap().await }, if fetch.is_some() => {
+                        fetch = None;
+                        match res {
+                            Ok(Ok(Some(fetched))) => {
+                                let lines = fetched.lines.iter().map(|l| l.text.clone()).collect();
+                                show_lyrics(&wui, lines, "");
+                                lyrics = Some(fetched);
+                            }
+                            Ok(Ok(None)) => show_lyrics(&wui, Vec::new(), "No lyrics found"),
+                            Ok(Err(e)) => {
+                                log::warn!("Could not get lyrics: {}", e);
+                                show_lyrics(&wui, Vec::new(), "Could not load lyrics");
+                            }
+                            // Aborted
+                            Err(_) => {}
+                        }
+                    },
+                }
+            }
+        });
+    }
+
     async fn enable_window_scaling(&self) {
         let app = &self.ui;
         let mut scale_change_rv = self.settings_window.subscribe_scale_changed();
@@ -189,6 +922,8 @@ impl MainWindow {
         });
     }
 
+    /// Restores the position of the main window for the current layout and monitor arrangement,
+    /// snaps it to the screen edges while dragged and remembers where it was dropped.
     async fn enable_window_positioning(&self) {
         let app = &self.ui;
         let settings = self.settings_window.get_settings();
@@ -196,38 +931,299 @@ impl MainWindow {
         // Set initial position
         {
             let spotick_settings = settings.read().await;
-            let initial_pos = spotick_settings.get_settings().main_window_pos.clone();
+            let spotick_settings = spotick_settings.get_settings();
+            let areas = screen_areas();
+            let layout = spotick_settings.layout.unwrap_or_default();
+            // The compact layout starts where the full one was until it has been moved
+            let saved_pos = spotick_settings
+                .window_positions
+                .as_ref()
+                .and_then(|positions| {
+                    positions
+                        .get(&position_key(layout, &areas))
+                        .or_else(|| positions.get(&position_key(WindowLayout::Full, &areas)))
+                })
+                .unwrap_or(&spotick_settings.main_window_pos);
+            // The window isn't created yet, so its size is estimated
+            let scale = spotick_settings.main_window_scale * app.window().scale_factor();
+            let size = PhysicalSize::new(
+                (app.get_original_window_width() * scale) as u32,
+                (app.get_original_window_height() * scale) as u32,
+            );
+            let initial_pos = clamp_to_screens(*saved_pos, size, &areas);
             app.set_window_x(initial_pos.x as f32);
             app.set_window_y(initial_pos.y as f32);
             app.window().set_position(initial_pos);
         }
 
         // Channel for sending notifications of window position changes
-        let (pos_tx, mut pos_rv) = channel(PhysicalPosition::new(-1, -1));
+        let (pos_tx, mut pos_rv) = channel((PhysicalPosition::new(-1, -1), WindowLayout::Full));
         pos_rv.mark_unchanged();
 
         callback!(on_position_window, |app, x, y| {
-            let pos = PhysicalPosition::new(x as i32, y as i32);
+            // Only the shown position snaps, so the window can be dragged away again
+            let pos = snap_to_edges(
+                PhysicalPosition::new(x as i32, y as i32),
+                app.window().size(),
+                &screen_areas(),
+                app.get_snap_margin().max(0) as u32,
+            );
             app.window().set_position(pos);
-            let _ = pos_tx.send_replace(pos);
+            let _ = pos_tx.send_replace((pos, app.layout()));
         });
 
         save_changes_in_settings!(pos_rv, settings, |sg| {
             let spotick_settings = sg.get_settings_mut();
-            spotick_settings.main_window_pos = pos_rv.borrow().clone();
+            let (pos, layout) = *pos_rv.borrow();
+            if layout == WindowLayout::Full {
+                spotick_settings.main_window_pos = pos;
+            }
+            spotick_settings
+                .window_positions
+                .get_or_insert_with(HashMap::new)
+                .insert(position_key(layout, &screen_areas()), pos);
+        });
+    }
+
+    /// Switches between the full and the compact layout,
+    /// either by double-clicking the main window or in the settings.
+    /// Moves the window to where it was last left in the new layout.
+    async fn enable_layouts(&self) {
+        let app = &self.ui;
+        let settings = self.settings_window.get_settings();
+        let mut settings_rv = settings.read().await.subscribe();
+
+        let mut applied = settings
+            .read()
+            .await
+            .get_settings()
+            .layout
+            .unwrap_or_default();
+        app.set_compact(applied == WindowLayout::Compact);
+
+        let save_settings = settings.clone();
+        callback!(on_layout_toggled, |app, _compact| {
+            let settings = save_settings.clone();
+            let layout = app.layout();
+            tokio::spawn(async move {
+                let mut sg = settings.write().await;
+                sg.get_settings_mut().layout = Some(layout);
+                if let Err(e) = sg.save().await {
+                    log::error!("Could not save layout: {:?}", e);
+                }
+            });
+        });
+
+        let wui = app.as_weak();
+        tokio::spawn(async move {
+            loop {
+                if let Err(RecvError::Closed) = settings_rv.recv().await {
+                    break;
+                }
+                let (layout, scale, saved_pos) = {
+                    let sg = settings.read().await;
+                    let spotick_settings = sg.get_settings();
+                    let layout = spotick_settings.layout.unwrap_or_default();
+                    let saved_pos = spotick_settings
+                        .window_positions
+                        .as_ref()
+                        .and_then(|positions| positions.get(&position_key(layout, &screen_areas())))
+                        .copied();
+                    (layout, spotick_settings.main_window_scale, saved_pos)
+                };
+                if layout == applied {
+                    continue;
+                }
+                applied = layout;
+
+                let _ = wui.upgrade_in_event_loop(move |app| {
+                    app.set_compact(layout == WindowLayout::Compact);
+                    app.rescale(scale);
+                    if let Some(pos) = saved_pos {
+                        let pos = clamp_to_screens(pos, app.window().size(), &screen_areas());
+                        app.set_window_x(pos.x as f32);
+                        app.set_window_y(pos.y as f32);
+                        app.window().set_position(pos);
+                    }
+                });
+            }
         });
     }
 
     fn enable_app_quit(&self) {
         let _app = &self.ui;
         callback!(on_quit, |_app| {
-            let _ = slint::quit_event_loop();
+            if !(_app.get_confirm_quit() && _app.get_playing()) {
+                let _ = slint::quit_event_loop();
+                return;
+            }
+
+            let dialog = DialogWindow::new(
+                _app.clone_strong(),
+                |dialog_res| {
+                    let win = SlintConfirmQuitWindow::new()?;
+                    callback!(on_answered, |win, quit| {
+                        *dialog_res.borrow_mut() = Some(quit);
+                        close_dialog!(win);
+                    });
+                    Ok(win)
+                },
+                |attr| attr.with_enabled_buttons(WindowButtons::CLOSE),
+            );
+            let res = dialog.and_then(|dialog| {
+                dialog.show_dialog(|quit| {
+                    if quit == Some(true) {
+                        let _ = slint::quit_event_loop();
+                    }
+                })
+            });
+            if let Err(e) = res {
+                log::error!("Could not show quit confirmation: {}", e);
+                let _ = slint::quit_event_loop();
+            }
         });
     }
 }
 
+/// Logs the error [msg] and shows it in the main window until dismissed.
+fn show_error(ui: &Weak<SlintMainWindow>, notifier: &Notifier, msg: impl Into<SharedString>) {
+    let msg = msg.into();
+    log::error!("{msg}");
+    notifier.notify(msg.clone(), MsgType::Error);
+    let _ = ui.upgrade_in_event_loop(move |ui| {
+        ui.set_error_msg(msg);
+    });
+}
+
+/// Loads the placeholder image at [path], [None] if it can't be read.
+async fn load_placeholder(path: PathBuf) -> Option<RgbaImage> {
+    match tokio::task::spawn_blocking(move || image::open(path)).await {
+        Ok(Ok(img)) => Some(img.to_rgba8()),
+        Ok(Err(e)) => {
+            log::error!("Could not load placeholder: {}", e);
+            None
+        }
+        Err(e) => {
+            log::error!("Could not load placeholder: {}", e);
+            None
+        }
+    }
+}
+
+/// Waits for a media event which may change what the level meter meters.
+/// Skips progress and volume updates, which are frequent and change neither.
+async fn next_meter_change(
+    media_events: &mut Receiver<PlaybackChangedEvent>,
+) -> Result<(), RecvError> {
+    loop {
+        match media_events.recv().await {
+            Ok(PlaybackChangedEvent::PlaybackProgress | PlaybackChangedEvent::Volume) => {}
+            Ok(_) | Err(RecvError::Lagged(_)) => return Ok(()),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Waits for the next track or cover of the media service.
+async fn next_cover(media_events: &mut Receiver<PlaybackChangedEvent>) -> Result<(), RecvError> {
+    loop {
+        match media_events.recv().await {
+            Ok(PlaybackChangedEvent::TrackChanged | PlaybackChangedEvent::CoverChanged)
+            | Err(RecvError::Lagged(_)) => return Ok(()),
+            Ok(_) => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn apply_theme(ui: &SlintMainWindow, theme: Theme) {
+    let globals = ui.global::<WidgetTheme>();
+    let [start, center, end] = theme.background;
+    globals.set_background_start(start.into());
+    globals.set_background_center(center.into());
+    globals.set_background_end(end.into());
+    globals.set_surface(theme.surface.into());
+    globals.set_foreground(theme.foreground.into());
+    globals.set_foreground_dim(theme.foreground_dim.into());
+    globals.set_accent(theme.accent.into());
+    globals.set_font_family(theme.font_family.into());
+    globals.set_corner_radius(theme.corner_radius);
+    globals.set_opacity(theme.opacity.clamp(0.0, 1.0));
+}
+
+/// Shows the [lines] of lyrics from the start, or the [status] if there are none.
+fn show_lyrics(ui: &Weak<SlintMainWindow>, lines: Vec<String>, status: &'static str) {
+    let _ = ui.upgrade_in_event_loop(move |ui| {
+        let lines: Vec<SharedString> = lines.into_iter().map(Into::into).collect();
+        ui.set_lyrics(ModelRc::new(VecModel::from(lines)));
+        ui.set_lyrics_line(-1);
+        ui.set_lyrics_status(status.into());
+    });
+}
+
+/// Lets screen readers read out [msg], if any.
+fn announce_playback(ui: &Weak<SlintMainWindow>, msg: Option<String>) {
+    let Some(msg) = msg else {
+        return;
+    };
+    let _ = ui.upgrade_in_event_loop(move |ui| {
+        if let Err(e) = announce(ui.window(), &msg) {
+            log::warn!("Could not announce \"{}\": {}", msg, e);
+        }
+    });
+}
+
+/// Shows a toast about the current track, if the [mode] allows it
+/// given whether the main window is minimized or kept [quiet].
+async fn toast_track(
+    srv: &SharedMediaService,
+    ui: &Weak<SlintMainWindow>,
+    mode: ToastMode,
+    quiet: bool,
+    dry_run: bool,
+) {
+    if mode == ToastMode::Off || quiet {
+        return;
+    }
+    let track = srv.read().await.current_track().map(|track| {
+        let cover = match &track.album_cover {
+            AlbumCover::Image(img) => Some(img.clone()),
+            _ => None,
+        };
+        (
+            track.title.clone(),
+            track.artist.clone(),
+            track.album_title.clone(),
+            cover,
+        )
+    });
+    let Some((title, artist, album, cover)) = track else {
+        return;
+    };
+
+    let _ = ui.upgrade_in_event_loop(move |ui| {
+        if !mode.shows_toast(ui.window().is_minimized(), quiet) {
+            return;
+        }
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = show_track_toast(&title, &artist, &album, cover.as_ref(), dry_run) {
+                log::warn!("Could not show toast for \"{}\": {:?}", title, e);
+            }
+        });
+    });
+}
+
 impl SlintMainWindow {
     fn set_thumbnail(&self, mut img: RgbaImage) {
+        self.set_placeholder_shown(false);
+        // Small covers look blurry when scaled up by the renderer
+        if self.get_upscale_thumbnail() {
+            let display_size = self.get_thumbnail_size() * self.window().scale_factor();
+            if let Some(upscaled) = upscale_image(&img, display_size as u32) {
+                img = upscaled;
+            }
+        }
+
         // Apply image decorations
         apply_border_radius(&mut img, self.get_thumbnail_border_radius() as u32);
 
@@ -253,6 +1249,31 @@ impl SlintMainWindow {
             .expect("Invalid placeholder image format");
 
         self.set_thumbnail(buffer);
+        self.set_placeholder_shown(true);
+    }
+
+    /// Shows [img] instead of the built-in placeholder, or the built-in one again for [None].
+    fn set_custom_placeholder(&self, img: Option<RgbaImage>) {
+        let placeholder = match img {
+            Some(img) => Image::from_rgba8(SharedPixelBuffer::<Rgba8Pixel>::clone_from_slice(
+                img.as_raw(),
+                img.width(),
+                img.height(),
+            )),
+            None => self.get_default_thumbnail_placeholder(),
+        };
+        self.set_thumbnail_placeholder(placeholder);
+        if self.get_placeholder_shown() {
+            self.set_initial_thumbnail();
+        }
+    }
+
+    fn layout(&self) -> WindowLayout {
+        if self.get_compact() {
+            WindowLayout::Compact
+        } else {
+            WindowLayout::Full
+        }
     }
 
     fn rescale(&self, scale: f32) {
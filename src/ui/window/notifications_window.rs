@@ -0,0 +1,126 @@
+use std::rc::Rc;
+
+use anyhow::Result;
+use chrono::Local;
+use i_slint_backend_winit::winit::window::WindowButtons;
+use slint::{ComponentHandle, Model, ModelRc, SharedString, VecModel, Weak};
+
+use crate::{
+    callback,
+    ui::{
+        get_window_creation_settings,
+        window::{MsgType, Notification, SlintNotificationsWindow, Window},
+    },
+};
+
+/// Only the most recent notifications are kept.
+const MAX_NOTIFICATIONS: usize = 50;
+
+/// Collects non-critical messages (e.g. saved settings or service errors)
+/// so they can still be read after their transient display is gone.
+pub struct NotificationsWindow {
+    ui: SlintNotificationsWindow,
+}
+
+/// Posts notifications to the [NotificationsWindow] from any thread.
+#[derive(Clone)]
+pub struct Notifier {
+    ui: Weak<SlintNotificationsWindow>,
+}
+
+impl NotificationsWindow {
+    pub fn new() -> Result<Self> {
+        let _settings_guard = get_window_creation_settings()
+            .change(|attr| attr.with_enabled_buttons(WindowButtons::CLOSE));
+        let win = NotificationsWindow {
+            ui: SlintNotificationsWindow::new()?,
+        };
+
+        win.ui
+            .set_notifications(ModelRc::from(Rc::new(VecModel::<Notification>::default())));
+        win.setup_callbacks();
+
+        Ok(win)
+    }
+
+    pub fn notifier(&self) -> Notifier {
+        Notifier {
+            ui: self.ui.as_weak(),
+        }
+    }
+
+    fn setup_callbacks(&self) {
+        let ui = &self.ui;
+
+        callback!(on_dismiss, |ui, idx| {
+            ui.with_notifications(|model| {
+                if idx >= 0 && (idx as usize) < model.row_count() {
+                    model.remove(idx as usize);
+                }
+            });
+        });
+
+        callback!(on_clear, |ui| {
+            ui.with_notifications(|model| model.clear());
+        });
+
+        // Everything shown until now has been seen by the user
+        self.ui.window().on_close_requested({
+            let ui = self.ui.as_weak();
+            move || {
+                if let Some(ui) = ui.upgrade() {
+                    ui.with_notifications(|model| {
+                        for idx in 0..model.row_count() {
+                            if let Some(mut notification) = model.row_data(idx) {
+                                if !notification.read {
+                                    notification.read = true;
+                                    model.set_row_data(idx, notification);
+                                }
+                            }
+                        }
+                    });
+                }
+                slint::CloseRequestResponse::HideWindow
+            }
+        });
+    }
+}
+
+impl Notifier {
+    /// Adds the [message] to the notifications.
+    pub fn notify(&self, message: impl Into<SharedString>, kind: MsgType) {
+        let message = message.into();
+        let time = Local::now().format("%H:%M:%S").to_string();
+        let _ = self.ui.upgrade_in_event_loop(move |ui| {
+            ui.with_notifications(|model| {
+                if model.row_count() >= MAX_NOTIFICATIONS {
+                    model.remove(model.row_count() - 1);
+                }
+                model.insert(
+                    0,
+                    Notification {
+                        message,
+                        time: time.into(),
+                        kind,
+                        read: false,
+                    },
+                );
+            });
+        });
+    }
+}
+
+impl SlintNotificationsWindow {
+    fn with_notifications(&self, f: impl FnOnce(&VecModel<Notification>)) {
+        let model = self.get_notifications();
+        if let Some(model) = model.as_any().downcast_ref::<VecModel<Notification>>() {
+            f(model);
+        }
+    }
+}
+
+impl Window<SlintNotificationsWindow> for NotificationsWindow {
+    fn component(&self) -> &SlintNotificationsWindow {
+        &self.ui
+    }
+}
@@ -1,17 +1,26 @@
 use crate::{
+    backup::{create_backup, default_backup_name, restore_backup},
     callback, close_dialog, save_changes_in_settings,
-    service::{BaseService, SharedMediaService},
-    settings::SpotickAppSettings,
+    schedule::{format_days, parse_days, SourceScheduleRule},
+    service::{BaseService, MediaSessionInfo, MetadataField, MetadataRule, SharedMediaService},
+    settings::{get_data_dir, SpotickAppSettings},
     ui::{
-        get_window_creation_settings,
+        announcer::AnnouncementVerbosity,
+        cache_placeholder, get_window_creation_settings,
+        placement::WindowLayout,
+        theme::ThemeSetting,
+        toast::ToastMode,
         window::{
-            DialogWindow, MsgType, SlintAvailableSessionsWindow, SlintSettingsWindow, Window,
+            DialogWindow, MetadataRuleRow, MsgType, Notifier, ScheduleRule, SessionInfo,
+            SlintAvailableSessionsWindow, SlintMetadataRulesWindow, SlintScheduleWindow,
+            SlintSettingsWindow, Window,
         },
     },
 };
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use i_slint_backend_winit::winit::window::WindowButtons;
-use slint::{ComponentHandle, ModelRc, SharedString, ToSharedString, VecModel, Weak};
+use regex::Regex;
+use slint::{ComponentHandle, Model, ModelRc, SharedString, ToSharedString, VecModel, Weak};
 use std::{rc::Rc, sync::Arc, time::Duration};
 use tokio::sync::watch::{channel, Receiver, Sender};
 
@@ -19,6 +28,7 @@ pub struct SettingsWindow {
     ui: SlintSettingsWindow,
     app_settings: SpotickAppSettings,
     media_service: SharedMediaService,
+    notifier: Notifier,
     scale_changed_tx: Sender<f32>,
 }
 
@@ -26,12 +36,14 @@ impl SettingsWindow {
     pub fn new(
         app_settings: SpotickAppSettings,
         media_service: SharedMediaService,
+        notifier: Notifier,
     ) -> Result<Self> {
         let _settings_guard = get_window_creation_settings()
             .change(|attr| attr.with_enabled_buttons(WindowButtons::CLOSE));
         let win = SettingsWindow {
             ui: SlintSettingsWindow::new()?,
             media_service,
+            notifier,
             app_settings,
             scale_changed_tx: channel(1f32).0,
         };
@@ -57,6 +69,20 @@ impl SettingsWindow {
                     ui.set_always_top(settings.always_on_top);
                     ui.set_media_application_id(settings.source_app.to_shared_string());
                     ui.set_window_scale(settings.main_window_scale);
+                    ui.set_upscale_thumbnails(settings.upscale_thumbnails.unwrap_or(true));
+                    ui.set_confirm_quit(settings.confirm_quit.unwrap_or_default());
+                    ui.set_countdown(settings.countdown.unwrap_or_default());
+                    ui.set_announcements(announcements_to_index(
+                        settings.announcements.unwrap_or_default(),
+                    ));
+                    ui.set_theme(theme_to_index(&settings.theme.clone().unwrap_or_default()));
+                    ui.set_cover_colors(settings.cover_colors.unwrap_or_default());
+                    ui.set_click_through(settings.click_through.unwrap_or_default());
+                    ui.set_hide_when_idle(settings.hide_when_idle.unwrap_or_default());
+                    ui.set_level_meter(settings.level_meter.unwrap_or_default());
+                    ui.set_layout(layout_to_index(settings.layout.unwrap_or_default()));
+                    ui.set_track_toasts(toasts_to_index(settings.track_toasts.unwrap_or_default()));
+                    ui.set_crash_toasts(settings.crash_toasts.unwrap_or(true));
                 }) {
                     break;
                 }
@@ -96,14 +122,28 @@ impl SettingsWindow {
 
         let settings = self.app_settings.clone();
         let media_service = Arc::downgrade(&self.media_service);
+        let notifier = self.notifier.clone();
         callback!(on_settings_changed, |ui| {
             let settings = settings.clone();
             let media_service = media_service.clone();
+            let notifier = notifier.clone();
 
             let auto_start = ui.get_auto_start();
             let always_on_top = ui.get_always_top();
             let source_id = ui.get_media_application_id().to_string();
             let scale_factor = ui.get_window_scale();
+            let upscale_thumbnails = ui.get_upscale_thumbnails();
+            let confirm_quit = ui.get_confirm_quit();
+            let countdown = ui.get_countdown();
+            let announcements = announcements_from_index(ui.get_announcements());
+            let theme_idx = ui.get_theme();
+            let cover_colors = ui.get_cover_colors();
+            let click_through = ui.get_click_through();
+            let hide_when_idle = ui.get_hide_when_idle();
+            let level_meter = ui.get_level_meter();
+            let layout = layout_from_index(ui.get_layout());
+            let track_toasts = toasts_from_index(ui.get_track_toasts());
+            let crash_toasts = ui.get_crash_toasts();
 
             let ui = ui.as_weak();
             tokio::spawn(async move {
@@ -114,16 +154,31 @@ impl SettingsWindow {
                     settings.always_on_top = always_on_top;
                     settings.source_app = source_id;
                     settings.main_window_scale = scale_factor;
+                    settings.upscale_thumbnails = Some(upscale_thumbnails);
+                    settings.confirm_quit = Some(confirm_quit);
+                    settings.countdown = Some(countdown);
+                    settings.announcements = Some(announcements);
+                    settings.theme = Some(theme_from_index(
+                        theme_idx,
+                        settings.theme.take().unwrap_or_default(),
+                    ));
+                    settings.cover_colors = Some(cover_colors);
+                    settings.click_through = Some(click_through);
+                    settings.hide_when_idle = Some(hide_when_idle);
+                    settings.level_meter = Some(level_meter);
+                    settings.layout = Some(layout);
+                    settings.track_toasts = Some(track_toasts);
+                    settings.crash_toasts = Some(crash_toasts);
                     log::info!("{:?}", settings);
                 }
 
                 // Save settings
-                show_msg(&ui, "Saving...", MsgType::Info);
+                show_msg(&ui, &notifier, "Saving...", MsgType::Info);
                 if let Err(e) = sg.save().await {
                     let msg = format!("Failed to save settings: {}", e);
-                    show_msg(&ui, msg, MsgType::Error);
+                    show_msg(&ui, &notifier, msg, MsgType::Error);
                 } else {
-                    show_msg(&ui, "Settings saved", MsgType::Success);
+                    show_msg(&ui, &notifier, "Settings saved", MsgType::Success);
                 }
 
                 // Apply possible changes to the media service
@@ -142,53 +197,193 @@ impl SettingsWindow {
 
         // Open window displaying all available sessions when requested
         let media_service = Arc::downgrade(&self.media_service);
+        let notifier = self.notifier.clone();
         callback!(on_select_session, |ui| {
-            if let Some(media_service) = media_service.upgrade() {
-                match media_service
-                    .blocking_read()
-                    .get_available_source_apps_ids()
-                {
+            let Some(media_service) = media_service.upgrade() else {
+                show_msg(
+                    &ui.as_weak(),
+                    &notifier,
+                    "BUG: No media service",
+                    MsgType::Error,
+                );
+                return;
+            };
+
+            let ui = ui.as_weak();
+            let notifier = notifier.clone();
+            // Querying every session may take a moment
+            tokio::spawn(async move {
+                let sessions = {
+                    let media_service = media_service.read().await;
+                    tokio::task::block_in_place(|| media_service.list_sessions())
+                };
+                match sessions {
                     Ok(sessions) => {
-                        let sessions: Vec<SharedString> =
-                            sessions.into_iter().map(SharedString::from).collect();
-                        let dialog = DialogWindow::new(
-                            ui.clone_strong(),
-                            move |dialog_res| {
-                                let win = SlintAvailableSessionsWindow::new()?;
-                                win.set_session_ids(ModelRc::from(Rc::new(VecModel::from(
-                                    sessions,
-                                ))));
-                                callback!(on_select_session, |win, res| {
-                                    *dialog_res.borrow_mut() = Some(res);
-                                    close_dialog!(win);
-                                });
-                                Ok(win)
-                            },
-                            |attr| attr.with_enabled_buttons(WindowButtons::CLOSE),
-                        )
-                        .unwrap();
-                        dialog
-                            .show_dialog({
-                                let ui = ui.as_weak();
-                                move |res| {
-                                    if let Some(res) = res {
-                                        let _ = ui.upgrade_in_event_loop(move |ui| {
-                                            ui.set_media_application_id(res);
-                                            ui.invoke_settings_changed();
-                                        });
-                                    }
-                                }
-                            })
-                            .unwrap();
+                        let _ = ui.upgrade_in_event_loop(move |ui| {
+                            show_sessions_dialog(&ui, sessions);
+                        });
                     }
                     Err(e) => {
                         let msg = format!("Could not get sessions: {}", e);
-                        show_msg(&ui.as_weak(), msg, MsgType::Error);
+                        show_msg(&ui, &notifier, msg, MsgType::Error);
                     }
                 }
-            } else {
-                show_msg(&ui.as_weak(), "BUG: No media service", MsgType::Error);
-            }
+            });
+        });
+
+        // Open the rules table of the source schedule when requested
+        let settings = self.app_settings.clone();
+        let notifier = self.notifier.clone();
+        callback!(on_edit_schedule, |ui| {
+            let ui = ui.as_weak();
+            let settings = settings.clone();
+            let notifier = notifier.clone();
+            tokio::spawn(async move {
+                let rules = settings.read().await.get_settings().source_schedule.clone();
+                let _ = ui.clone().upgrade_in_event_loop(move |ui| {
+                    show_schedule_dialog(&ui, rules.unwrap_or_default(), settings, notifier);
+                });
+            });
+        });
+
+        // Open the rules editor of the track metadata when requested
+        let settings = self.app_settings.clone();
+        let notifier = self.notifier.clone();
+        callback!(on_edit_metadata_rules, |ui| {
+            let ui = ui.as_weak();
+            let settings = settings.clone();
+            let notifier = notifier.clone();
+            tokio::spawn(async move {
+                let rules = settings.read().await.get_settings().metadata_rules.clone();
+                let _ = ui.clone().upgrade_in_event_loop(move |ui| {
+                    show_metadata_rules_dialog(&ui, rules.unwrap_or_default(), settings, notifier);
+                });
+            });
+        });
+
+        let notifier = self.notifier.clone();
+        callback!(on_create_backup, |ui| {
+            let ui = ui.as_weak();
+            let notifier = notifier.clone();
+            tokio::spawn(async move {
+                let Some(file) = rfd::AsyncFileDialog::new()
+                    .add_filter("Spotick backup", &["zip"])
+                    .set_file_name(default_backup_name())
+                    .save_file()
+                    .await
+                else {
+                    return;
+                };
+
+                let path = file.path().to_path_buf();
+                let res =
+                    tokio::task::spawn_blocking(move || create_backup(&get_data_dir(), &path))
+                        .await
+                        .unwrap_or_else(|e| Err(e.into()));
+                match res {
+                    Ok(count) => {
+                        let msg = format!("Backed up {} files", count);
+                        show_msg(&ui, &notifier, msg, MsgType::Success);
+                    }
+                    Err(e) => {
+                        let msg = format!("Could not create backup: {}", e);
+                        show_msg(&ui, &notifier, msg, MsgType::Error);
+                    }
+                }
+            });
+        });
+
+        let settings = self.app_settings.clone();
+        let notifier = self.notifier.clone();
+        callback!(on_select_placeholder, |ui| {
+            let ui = ui.as_weak();
+            let settings = settings.clone();
+            let notifier = notifier.clone();
+            tokio::spawn(async move {
+                let Some(file) = rfd::AsyncFileDialog::new()
+                    .add_filter("Image", &["png", "jpg", "jpeg", "bmp", "webp"])
+                    .pick_file()
+                    .await
+                else {
+                    return;
+                };
+
+                let path = file.path().to_path_buf();
+                let res =
+                    tokio::task::spawn_blocking(move || cache_placeholder(&path, &get_data_dir()))
+                        .await
+                        .unwrap_or_else(|e| Err(e.into()));
+                match res {
+                    Ok(name) => match set_placeholder(&settings, Some(name)).await {
+                        Ok(_) => show_msg(&ui, &notifier, "Placeholder changed", MsgType::Success),
+                        Err(e) => {
+                            let msg = format!("Could not save placeholder: {}", e);
+                            show_msg(&ui, &notifier, msg, MsgType::Error);
+                        }
+                    },
+                    Err(e) => {
+                        let msg = format!("Could not use the image: {}", e);
+                        show_msg(&ui, &notifier, msg, MsgType::Error);
+                    }
+                }
+            });
+        });
+
+        let settings = self.app_settings.clone();
+        let notifier = self.notifier.clone();
+        callback!(on_reset_placeholder, |ui| {
+            let ui = ui.as_weak();
+            let settings = settings.clone();
+            let notifier = notifier.clone();
+            tokio::spawn(async move {
+                match set_placeholder(&settings, None).await {
+                    Ok(_) => show_msg(&ui, &notifier, "Placeholder reset", MsgType::Success),
+                    Err(e) => {
+                        let msg = format!("Could not save placeholder: {}", e);
+                        show_msg(&ui, &notifier, msg, MsgType::Error);
+                    }
+                }
+            });
+        });
+
+        let settings = self.app_settings.clone();
+        let notifier = self.notifier.clone();
+        callback!(on_restore_backup, |ui| {
+            let ui = ui.as_weak();
+            let settings = settings.clone();
+            let notifier = notifier.clone();
+            tokio::spawn(async move {
+                let Some(file) = rfd::AsyncFileDialog::new()
+                    .add_filter("Spotick backup", &["zip"])
+                    .pick_file()
+                    .await
+                else {
+                    return;
+                };
+
+                let path = file.path().to_path_buf();
+                let res =
+                    tokio::task::spawn_blocking(move || restore_backup(&get_data_dir(), &path))
+                        .await
+                        .unwrap_or_else(|e| Err(e.into()));
+                let count = match res {
+                    Ok(count) => count,
+                    Err(e) => {
+                        let msg = format!("Could not restore backup: {}", e);
+                        show_msg(&ui, &notifier, msg, MsgType::Error);
+                        return;
+                    }
+                };
+
+                // Apply the restored settings right away. The state cache is rewritten on quit anyway.
+                if let Err(e) = settings.write().await.load().await {
+                    let msg = format!("Could not load restored settings: {}", e);
+                    show_msg(&ui, &notifier, msg, MsgType::Error);
+                } else {
+                    let msg = format!("Restored {} files", count);
+                    show_msg(&ui, &notifier, msg, MsgType::Success);
+                }
+            });
         });
     }
 }
@@ -199,12 +394,350 @@ impl Window<SlintSettingsWindow> for SettingsWindow {
     }
 }
 
-fn show_msg(ui: &Weak<SlintSettingsWindow>, msg: impl Into<SharedString>, success: MsgType) {
+/// Saves the cached placeholder image [name] and removes the previous one.
+async fn set_placeholder(settings: &SpotickAppSettings, name: Option<String>) -> Result<()> {
+    let mut sg = settings.write().await;
+    let previous = std::mem::replace(&mut sg.get_settings_mut().placeholder_image, name);
+    sg.save().await?;
+    if let Some(previous) = previous {
+        if let Err(e) = tokio::fs::remove_file(get_data_dir().join(previous)).await {
+            log::warn!("Could not remove previous placeholder image: {}", e);
+        }
+    }
+    Ok(())
+}
+
+/// Maps the [AnnouncementVerbosity] to the index of its option in the settings window.
+fn announcements_to_index(verbosity: AnnouncementVerbosity) -> i32 {
+    match verbosity {
+        AnnouncementVerbosity::Off => 0,
+        AnnouncementVerbosity::Tracks => 1,
+        AnnouncementVerbosity::All => 2,
+    }
+}
+
+fn announcements_from_index(idx: i32) -> AnnouncementVerbosity {
+    match idx {
+        0 => AnnouncementVerbosity::Off,
+        2 => AnnouncementVerbosity::All,
+        _ => AnnouncementVerbosity::Tracks,
+    }
+}
+
+fn toasts_to_index(mode: ToastMode) -> i32 {
+    match mode {
+        ToastMode::Off => 0,
+        ToastMode::WhenHidden => 1,
+        ToastMode::Always => 2,
+    }
+}
+
+fn toasts_from_index(idx: i32) -> ToastMode {
+    match idx {
+        1 => ToastMode::WhenHidden,
+        2 => ToastMode::Always,
+        _ => ToastMode::Off,
+    }
+}
+
+fn layout_to_index(layout: WindowLayout) -> i32 {
+    match layout {
+        WindowLayout::Full => 0,
+        WindowLayout::Compact => 1,
+    }
+}
+
+fn layout_from_index(idx: i32) -> WindowLayout {
+    match idx {
+        1 => WindowLayout::Compact,
+        _ => WindowLayout::Full,
+    }
+}
+
+/// Maps the [ThemeSetting] to the index of its option in the settings window.
+fn theme_to_index(theme: &ThemeSetting) -> i32 {
+    match theme {
+        ThemeSetting::Dark => 0,
+        ThemeSetting::Light => 1,
+        ThemeSetting::Auto => 2,
+        ThemeSetting::Custom(_) => 3,
+    }
+}
+
+/// Picking "Custom" keeps the [current] custom theme or starts one from the current dark
+/// or light theme. Custom themes are edited in the settings file.
+fn theme_from_index(idx: i32, current: ThemeSetting) -> ThemeSetting {
+    match (idx, current) {
+        (0, _) => ThemeSetting::Dark,
+        (1, _) => ThemeSetting::Light,
+        (2, _) => ThemeSetting::Auto,
+        (_, ThemeSetting::Custom(theme)) => ThemeSetting::Custom(theme),
+        (_, current) => ThemeSetting::Custom(current.resolve(None)),
+    }
+}
+
+/// Lets the user pick one of the [sessions] as the new source app.
+fn show_sessions_dialog(ui: &SlintSettingsWindow, sessions: Vec<MediaSessionInfo>) {
+    let sessions: Vec<SessionInfo> = sessions
+        .into_iter()
+        .map(|session| {
+            let track = if session.track_title.is_empty() || session.track_artist.is_empty() {
+                session.track_title
+            } else {
+                format!("{} - {}", session.track_title, session.track_artist)
+            };
+            SessionInfo {
+                id: session.app_id.into(),
+                name: session.app_name.into(),
+                track: track.into(),
+                playing: session.is_playing,
+            }
+        })
+        .collect();
+    let dialog = DialogWindow::new(
+        ui.clone_strong(),
+        move |dialog_res| {
+            let win = SlintAvailableSessionsWindow::new()?;
+            win.set_sessions(ModelRc::from(Rc::new(VecModel::from(sessions))));
+            callback!(on_select_session, |win, res| {
+                *dialog_res.borrow_mut() = Some(res);
+                close_dialog!(win);
+            });
+            Ok(win)
+        },
+        |attr| attr.with_enabled_buttons(WindowButtons::CLOSE),
+    )
+    .unwrap();
+    dialog
+        .show_dialog({
+            let ui = ui.as_weak();
+            move |res| {
+                if let Some(res) = res {
+                    let _ = ui.upgrade_in_event_loop(move |ui| {
+                        ui.set_media_application_id(res);
+                        ui.invoke_settings_changed();
+                    });
+                }
+            }
+        })
+        .unwrap();
+}
+
+/// Lets the user edit the [rules] of the source schedule in a table.
+/// Saved rules are picked up by [crate::schedule::register_source_schedule].
+fn show_schedule_dialog(
+    ui: &SlintSettingsWindow,
+    rules: Vec<SourceScheduleRule>,
+    settings: SpotickAppSettings,
+    notifier: Notifier,
+) {
+    let rows: Vec<ScheduleRule> = rules.iter().map(schedule_rule_to_row).collect();
+    let dialog = DialogWindow::new(
+        ui.clone_strong(),
+        move |dialog_res| {
+            let win = SlintScheduleWindow::new()?;
+            let rows = Rc::new(VecModel::from(rows));
+            win.set_rules(ModelRc::from(rows.clone()));
+            win.on_add_rule({
+                let rows = rows.clone();
+                move || {
+                    rows.push(ScheduleRule {
+                        days: "Mon-Fri".into(),
+                        start_hour: 9,
+                        end_hour: 17,
+                        source_app: SharedString::default(),
+                    })
+                }
+            });
+            win.on_remove_rule(move |idx| {
+                if idx >= 0 && (idx as usize) < rows.row_count() {
+                    rows.remove(idx as usize);
+                }
+            });
+            callback!(on_save, |win| {
+                let rules: Result<Vec<_>> = win
+                    .get_rules()
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, row)| {
+                        schedule_rule_from_row(&row).with_context(|| format!("Rule {}", idx + 1))
+                    })
+                    .collect();
+                match rules {
+                    Ok(rules) => {
+                        *dialog_res.borrow_mut() = Some(rules);
+                        close_dialog!(win);
+                    }
+                    Err(e) => win.set_error(format!("{:#}", e).into()),
+                }
+            });
+            Ok(win)
+        },
+        |attr| attr.with_enabled_buttons(WindowButtons::CLOSE),
+    )
+    .unwrap();
+    dialog
+        .show_dialog({
+            let ui = ui.as_weak();
+            move |res| {
+                let Some(rules) = res else {
+                    return;
+                };
+                tokio::spawn(async move {
+                    let mut sg = settings.write().await;
+                    sg.get_settings_mut().source_schedule = (!rules.is_empty()).then_some(rules);
+                    if let Err(e) = sg.save().await {
+                        let msg = format!("Failed to save schedule: {}", e);
+                        show_msg(&ui, &notifier, msg, MsgType::Error);
+                    } else {
+                        show_msg(&ui, &notifier, "Schedule saved", MsgType::Success);
+                    }
+                });
+            }
+        })
+        .unwrap();
+}
+
+fn schedule_rule_to_row(rule: &SourceScheduleRule) -> ScheduleRule {
+    ScheduleRule {
+        days: format_days(&rule.days).into(),
+        start_hour: rule.start_hour as i32,
+        end_hour: rule.end_hour as i32,
+        source_app: rule.source_app.to_shared_string(),
+    }
+}
+
+fn schedule_rule_from_row(row: &ScheduleRule) -> Result<SourceScheduleRule> {
+    let source_app = row.source_app.trim();
+    if source_app.is_empty() {
+        bail!("No application given");
+    }
+    if row.start_hour == row.end_hour {
+        bail!("Starts and ends at the same hour");
+    }
+    Ok(SourceScheduleRule {
+        days: parse_days(&row.days)?,
+        start_hour: row.start_hour.clamp(0, 23) as u32,
+        end_hour: row.end_hour.clamp(0, 24) as u32,
+        source_app: source_app.to_string(),
+    })
+}
+
+/// Lets the user edit the find and replace [rules] for track metadata in a table.
+/// Saved rules are picked up by [crate::service::register_metadata_rules_changed].
+fn show_metadata_rules_dialog(
+    ui: &SlintSettingsWindow,
+    rules: Vec<MetadataRule>,
+    settings: SpotickAppSettings,
+    notifier: Notifier,
+) {
+    let rows: Vec<MetadataRuleRow> = rules.iter().map(metadata_rule_to_row).collect();
+    let dialog = DialogWindow::new(
+        ui.clone_strong(),
+        move |dialog_res| {
+            let win = SlintMetadataRulesWindow::new()?;
+            let rows = Rc::new(VecModel::from(rows));
+            win.set_rules(ModelRc::from(rows.clone()));
+            win.on_add_rule({
+                let rows = rows.clone();
+                move || rows.push(MetadataRuleRow::default())
+            });
+            win.on_remove_rule(move |idx| {
+                if idx >= 0 && (idx as usize) < rows.row_count() {
+                    rows.remove(idx as usize);
+                }
+            });
+            callback!(on_save, |win| {
+                let rules: Result<Vec<_>> = win
+                    .get_rules()
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, row)| {
+                        metadata_rule_from_row(&row).with_context(|| format!("Rule {}", idx + 1))
+                    })
+                    .collect();
+                match rules {
+                    Ok(rules) => {
+                        *dialog_res.borrow_mut() = Some(rules);
+                        close_dialog!(win);
+                    }
+                    Err(e) => win.set_error(format!("{:#}", e).into()),
+                }
+            });
+            Ok(win)
+        },
+        |attr| attr.with_enabled_buttons(WindowButtons::CLOSE),
+    )
+    .unwrap();
+    dialog
+        .show_dialog({
+            let ui = ui.as_weak();
+            move |res| {
+                let Some(rules) = res else {
+                    return;
+                };
+                tokio::spawn(async move {
+                    let mut sg = settings.write().await;
+                    sg.get_settings_mut().metadata_rules = (!rules.is_empty()).then_some(rules);
+                    if let Err(e) = sg.save().await {
+                        let msg = format!("Failed to save metadata rules: {}", e);
+                        show_msg(&ui, &notifier, msg, MsgType::Error);
+                    } else {
+                        show_msg(&ui, &notifier, "Metadata rules saved", MsgType::Success);
+                    }
+                });
+            }
+        })
+        .unwrap();
+}
+
+fn metadata_rule_to_row(rule: &MetadataRule) -> MetadataRuleRow {
+    MetadataRuleRow {
+        source_app: rule.source_app.clone().unwrap_or_default().into(),
+        field: match rule.field {
+            MetadataField::Title => 0,
+            MetadataField::Artist => 1,
+            MetadataField::AlbumTitle => 2,
+        },
+        find: rule.find.to_shared_string(),
+        replace: rule.replace.to_shared_string(),
+    }
+}
+
+fn metadata_rule_from_row(row: &MetadataRuleRow) -> Result<MetadataRule> {
+    if row.find.is_empty() {
+        bail!("Nothing to find given");
+    }
+    Regex::new(&row.find)?;
+    let source_app = row.source_app.trim();
+    Ok(MetadataRule {
+        source_app: (!source_app.is_empty()).then(|| source_app.to_string()),
+        field: match row.field {
+            1 => MetadataField::Artist,
+            2 => MetadataField::AlbumTitle,
+            _ => MetadataField::Title,
+        },
+        find: row.find.to_string(),
+        replace: row.replace.to_string(),
+    })
+}
+
+/// Shows [msg] in the settings window for a few seconds.
+/// Anything but progress info is also kept as a notification.
+fn show_msg(
+    ui: &Weak<SlintSettingsWindow>,
+    notifier: &Notifier,
+    msg: impl Into<SharedString>,
+    success: MsgType,
+) {
     let msg = msg.into();
     match success {
         MsgType::Success | MsgType::Info => log::info!("{msg}"),
         MsgType::Error => log::error!("{msg}"),
     };
+    if success != MsgType::Info {
+        notifier.notify(msg.clone(), success);
+    }
     let _ = ui.upgrade_in_event_loop(move |ui| {
         ui.invoke_show_msg(msg, success);
     });